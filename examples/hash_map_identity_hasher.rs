@@ -0,0 +1,42 @@
+extern crate hash_ord;
+extern crate time;
+
+use hash_ord::hash_map::HashMap;
+
+fn main() {
+    let max_num = 10_000_000u64;
+    let v: Vec<u64> = (0..max_num).map(|i| i.wrapping_mul(2654435761)).collect();
+
+    println!("\ntest hash avl map with default hasher");
+    let mut map = HashMap::new();
+    map.reserve(v.len());
+    for i in &v {
+        map.insert(*i, *i);
+    }
+    let start = time::now();
+    let mut cnt = 0;
+    for i in &v {
+        cnt += if map.get(i).is_none() { 0 } else { 1 };
+    }
+    let duration = time::now() - start;
+    println!("find {}, time {}", cnt, duration);
+    drop(map);
+
+    println!("\ntest hash avl map with identity hasher");
+    let mut map = HashMap::with_identity_hasher();
+    map.reserve(v.len());
+    for i in &v {
+        map.insert(*i, *i);
+    }
+    println!(
+        "max node num of single index: {}",
+        map.get_max_node_of_single_index()
+    );
+    let start = time::now();
+    let mut cnt = 0;
+    for i in &v {
+        cnt += if map.get(i).is_none() { 0 } else { 1 };
+    }
+    let duration = time::now() - start;
+    println!("find {}, time {}", cnt, duration);
+}
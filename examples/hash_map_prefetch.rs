@@ -0,0 +1,55 @@
+extern crate hash_ord;
+extern crate rand;
+extern crate time;
+
+use hash_ord::hash_map;
+
+// Compare the lookup throughput with and without the `prefetch` feature:
+//
+//     cargo run --release --example hash_map_prefetch
+//     cargo run --release --example hash_map_prefetch --features prefetch
+
+pub fn default_make_avl_element(n: usize) -> Vec<usize> {
+    let mut v = vec![0usize; n];
+    for idx in 0..v.len() {
+        v[idx] = idx;
+        let pos = rand::random::<usize>() % (idx + 1);
+        v.swap(idx, pos);
+    }
+    v
+}
+
+fn main() {
+    println!("prefetch enabled: {}", cfg!(feature = "prefetch"));
+    run(10_000_000);
+}
+
+fn run(max_num: usize) {
+    let v = default_make_avl_element(max_num);
+    let mut map = hash_map::HashMap::new();
+    map.reserve(max_num);
+    for i in &v {
+        map.insert(*i, *i);
+    }
+    println!("size {}", map.len());
+    println!(
+        "max node num of single index: {}",
+        map.get_max_node_of_single_index()
+    );
+
+    let start = time::now();
+    let mut cnt = 0;
+    for i in &v {
+        cnt += if map.get(i).is_none() { 0 } else { 1 };
+    }
+    let duration = time::now() - start;
+    println!("find {}, time {}", cnt, duration);
+
+    let start = time::now();
+    let mut cnt = 0;
+    for i in &v {
+        cnt += if map.contains_key(&(i + max_num)) { 1 } else { 0 };
+    }
+    let duration = time::now() - start;
+    println!("miss {}, time {}", cnt, duration);
+}
@@ -0,0 +1,44 @@
+extern crate hash_ord;
+extern crate rand;
+extern crate time;
+
+use hash_ord::ord_map::OrdMap;
+
+pub fn default_make_avl_element(n: usize) -> Vec<usize> {
+    let mut v = vec![0usize; n];
+    for idx in 0..v.len() {
+        v[idx] = idx;
+        let pos = rand::random::<usize>() % (idx + 1);
+        v.swap(idx, pos);
+    }
+    v
+}
+
+fn main() {
+    let max_num = 1_000_000;
+    let v = default_make_avl_element(max_num);
+    test_unpooled(&v);
+    test_pooled(max_num, &v);
+}
+
+fn test_unpooled(v: &Vec<usize>) {
+    println!("\ntest ord map without node pool");
+    let start = time::now();
+    let mut map = OrdMap::new();
+    for i in v {
+        map.insert(*i, *i);
+    }
+    let duration = time::now() - start;
+    println!("insert {}, time {}", map.len(), duration);
+}
+
+fn test_pooled(max_num: usize, v: &Vec<usize>) {
+    println!("\ntest ord map with node pool");
+    let start = time::now();
+    let mut map = OrdMap::with_node_pool_capacity(max_num);
+    for i in v {
+        map.insert(*i, *i);
+    }
+    let duration = time::now() - start;
+    println!("insert {}, time {}", map.len(), duration);
+}
@@ -0,0 +1,34 @@
+use std::cmp::Ordering;
+
+/// Decides the order of keys which share a hash value under the same HashIndex.
+///
+/// Keys that compare `Equal` are treated as the same key, so a comparator must be consistent
+/// with the `Hash` of the map: whenever `compare(a, b) == Equal`, `a` and `b` must hash to the
+/// same value. Otherwise equal keys may land in different HashIndexes and never meet.
+///
+/// Any `Fn(&K, &K) -> Ordering` is a `Comparator<K>`.
+pub trait Comparator<Q: ?Sized> {
+    /// Compares `a` with `b`.
+    fn compare(&self, a: &Q, b: &Q) -> Ordering;
+}
+
+/// The default comparator of `HashMap`, which orders keys by `Ord`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OrdComparator;
+
+impl<Q: ?Sized + Ord> Comparator<Q> for OrdComparator {
+    #[inline]
+    fn compare(&self, a: &Q, b: &Q) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<K: ?Sized, F> Comparator<K> for F
+where
+    F: Fn(&K, &K) -> Ordering,
+{
+    #[inline]
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        self(a, b)
+    }
+}
@@ -1,4 +1,4 @@
-use libc::{c_void, free, malloc};
+use libc::{self, c_void, free};
 use std::{cmp, mem};
 
 pub type VoidPtr = *mut u8;
@@ -20,6 +20,9 @@ pub struct Fastbin {
     end: VoidPtr,
     next: VoidPtr,
     pages: VoidPtr,
+    allocated: usize,
+    reused: usize,
+    fresh: usize,
 }
 
 impl Default for Fastbin {
@@ -56,6 +59,41 @@ impl Fastbin {
         unsafe { (self as FastbinPtr).fastbin_new() }
     }
 
+    /// Like `alloc`, but returns `None` instead of panicking if a new page can't be obtained.
+    #[inline]
+    pub fn try_alloc(&mut self) -> Option<VoidPtr> {
+        let obj = unsafe { (self as FastbinPtr).fastbin_try_new() };
+        if obj.is_null() {
+            None
+        } else {
+            Some(obj)
+        }
+    }
+
+    /// Makes sure at least `additional` objects can be allocated without calling `malloc`.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        (self as FastbinPtr).fastbin_reserve(additional);
+    }
+
+    /// The number of bytes of all pages obtained from `malloc`.
+    #[inline]
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated
+    }
+
+    /// The number of objects handed out from the free list, i.e. reusing a deleted object.
+    #[inline]
+    pub fn reused_count(&self) -> usize {
+        self.reused
+    }
+
+    /// The number of objects handed out from the untouched tail of a page.
+    #[inline]
+    pub fn fresh_alloc_count(&self) -> usize {
+        self.fresh
+    }
+
     #[inline]
     fn destroy(&mut self) {
         (self as FastbinPtr).fastbin_destroy();
@@ -70,6 +108,9 @@ impl Fastbin {
         self.end = VOID_PTR_NULL;
         self.next = VOID_PTR_NULL;
         self.pages = VOID_PTR_NULL;
+        self.allocated = 0;
+        self.reused = 0;
+        self.fresh = 0;
     }
 
     pub fn move_to(&mut self) -> Self {
@@ -85,8 +126,52 @@ impl Drop for Fastbin {
     }
 }
 
+/// The number of bytes a `Fastbin` created for objects of `obj_size` bytes takes per object.
+#[inline]
+pub fn slot_size(obj_size: usize) -> usize {
+    round_up_to_next(
+        cmp::max(mem::size_of::<VoidPtr>(), obj_size),
+        mem::align_of::<VoidPtr>(),
+    )
+}
+
 pub type FastbinPtr = *mut Fastbin;
 
+#[cfg(test)]
+thread_local!(
+    static FAIL_MALLOC: ::std::cell::Cell<bool> = const { ::std::cell::Cell::new(false) }
+);
+
+#[cfg(test)]
+thread_local!(
+    static MALLOC_CNT: ::std::cell::Cell<usize> = const { ::std::cell::Cell::new(0) }
+);
+
+/// Makes every `malloc` of this thread fail while `fail` is set, to test out of memory paths.
+#[cfg(test)]
+pub fn set_fail_malloc(fail: bool) {
+    FAIL_MALLOC.with(|f| f.set(fail));
+}
+
+/// The number of `malloc` calls this thread made, to test which operations allocate.
+#[cfg(test)]
+pub fn malloc_count() -> usize {
+    MALLOC_CNT.with(|c| c.get())
+}
+
+/// `malloc`, except that it is counted and can be made to fail in tests.
+#[inline]
+pub unsafe fn malloc(size: usize) -> *mut c_void {
+    #[cfg(test)]
+    {
+        MALLOC_CNT.with(|c| c.set(c.get() + 1));
+        if FAIL_MALLOC.with(|f| f.get()) {
+            return ::std::ptr::null_mut();
+        }
+    }
+    libc::malloc(size)
+}
+
 #[inline]
 fn get_page_next(ptr: VoidPtr) -> VoidPtr {
     unsafe { *(ptr as *mut VoidPtr) }
@@ -120,7 +205,9 @@ pub trait FastbinPtrOperation {
     fn fastbin_init(self, obj_size: usize, page_obj_cnt: usize, maximum: usize);
     fn fastbin_destroy(self);
     unsafe fn fastbin_new(self) -> VoidPtr;
+    unsafe fn fastbin_try_new(self) -> VoidPtr;
     fn fastbin_del(self, ptr: VoidPtr);
+    fn fastbin_reserve(self, additional: usize);
 }
 
 impl FastbinPtrOperation for *mut Fastbin {
@@ -158,22 +245,34 @@ impl FastbinPtrOperation for *mut Fastbin {
         self.set_end(VOID_PTR_NULL);
         self.set_next(VOID_PTR_NULL);
         self.set_pages(VOID_PTR_NULL);
+        unsafe { (*self).allocated = 0 };
     }
 
     #[inline]
     unsafe fn fastbin_new(self) -> VoidPtr {
+        let obj = self.fastbin_try_new();
+        if obj.is_null() {
+            panic!("memory overflow");
+        }
+        obj
+    }
+
+    #[inline]
+    unsafe fn fastbin_try_new(self) -> VoidPtr {
         let obj_size = self.obj_size() as isize;
         let mut obj = self.next();
         if !obj.is_null() {
             self.set_next(get_page_next(self.next()));
+            (*self).reused += 1;
             return obj;
         }
         if self.start().offset(obj_size) > self.end() {
             let page = malloc(self.page_size()) as VoidPtr;
             if page.is_null() {
-                panic!("memory overflow");
+                return VOID_PTR_NULL;
             }
             let mut line_ptr = page;
+            (*self).allocated += self.page_size();
             set_page_next(page, self.pages());
             self.set_pages(page);
             line_ptr = round_up_to_next(line_ptr as usize + mem::size_of::<VoidPtr>(), self.align())
@@ -185,6 +284,7 @@ impl FastbinPtrOperation for *mut Fastbin {
             }
         }
         obj = self.start();
+        (*self).fresh += 1;
         self.set_start(self.start().offset(obj_size));
         debug_assert!(self.start() <= self.end());
         obj
@@ -195,6 +295,35 @@ impl FastbinPtrOperation for *mut Fastbin {
         set_page_next(ptr, self.next());
         self.set_next(ptr);
     }
+
+    fn fastbin_reserve(self, additional: usize) {
+        let obj_size = self.obj_size();
+        let avail = (self.end() as usize - self.start() as usize) / obj_size;
+        if avail >= additional {
+            return;
+        }
+        // move the rest of current page into free list, then carve a page big enough for others
+        while self.start() as usize + obj_size <= self.end() as usize {
+            let obj = self.start();
+            self.set_start(unsafe { obj.add(obj_size) });
+            self.fastbin_del(obj);
+        }
+        let need = (additional - avail)
+            .checked_mul(obj_size)
+            .and_then(|n| n.checked_add(round_up_to_next(mem::size_of::<VoidPtr>(), self.align())))
+            .expect("capacity overflow");
+        let page = unsafe { malloc(need) } as VoidPtr;
+        if page.is_null() {
+            panic!("memory overflow");
+        }
+        unsafe { (*self).allocated += need };
+        set_page_next(page, self.pages());
+        self.set_pages(page);
+        let line_ptr =
+            round_up_to_next(page as usize + mem::size_of::<VoidPtr>(), self.align()) as VoidPtr;
+        self.set_start(line_ptr);
+        self.set_end(unsafe { page.add(need) });
+    }
 }
 
 impl FastbinPtrBase for *mut Fastbin {
@@ -326,6 +455,62 @@ mod test {
         assert_ne!(fb.pages, page);
     }
 
+    #[test]
+    fn test_fastbin_reserve() {
+        struct Node {
+            a: u64,
+        }
+        let mut fb = Fastbin::new(mem::size_of::<Node>());
+        fb.alloc();
+        let before = fb.allocated_bytes();
+        let avail = (fb.end as usize - fb.start as usize) / fb.obj_size;
+        fb.reserve(1000);
+        assert!(fb.allocated_bytes() >= before + (1000 - avail) * mem::size_of::<Node>());
+        let page = fb.pages;
+        for _ in 0..1000 {
+            let _ = fb.alloc() as *mut Node;
+        }
+        assert_eq!(fb.pages, page);
+        fb.alloc();
+        assert_ne!(fb.pages, page);
+    }
+
+    #[test]
+    fn test_fastbin_reuse_count() {
+        let mut fb = Fastbin::new(mem::size_of::<u64>());
+        let objs: Vec<_> = (0..100).map(|_| fb.alloc()).collect();
+        assert_eq!(fb.fresh_alloc_count(), 100);
+        assert_eq!(fb.reused_count(), 0);
+        for &obj in objs.iter() {
+            fb.del(obj);
+        }
+        let allocated = fb.allocated_bytes();
+        for _ in 0..100 {
+            fb.alloc();
+        }
+        assert_eq!(fb.fresh_alloc_count(), 100);
+        assert_eq!(fb.reused_count(), 100);
+        assert_eq!(fb.allocated_bytes(), allocated);
+        fb.alloc();
+        assert_eq!(fb.fresh_alloc_count(), 101);
+    }
+
+    #[test]
+    fn test_fastbin_try_alloc() {
+        let mut fb = Fastbin::new(mem::size_of::<u64>());
+        fastbin::set_fail_malloc(true);
+        assert!(fb.try_alloc().is_none());
+        assert!(fb.pages.is_null());
+        fastbin::set_fail_malloc(false);
+        let obj = fb.try_alloc().unwrap();
+        fb.del(obj);
+        fastbin::set_fail_malloc(true);
+        // served from the free list and the current page, no malloc needed
+        assert_eq!(fb.try_alloc(), Some(obj));
+        assert!(fb.try_alloc().is_some());
+        fastbin::set_fail_malloc(false);
+    }
+
     #[test]
     fn test_fastbin_del() {
         struct Node {
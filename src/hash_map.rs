@@ -1,19 +1,32 @@
 extern crate fnv;
 
-use fastbin::{Fastbin, VoidPtr};
-use hash_table::{HashIndexPtrOperation, HashNode, HashNodeOperation, HashNodePtrOperation,
-                 HashTable, HashUint};
+use fastbin::{self, Fastbin, VoidPtr};
+use hash_table::{HashIndex, HashIndexPtrOperation, HashNode, HashNodeOperation, HashNodePtrOperation,
+                 HashTable, HashUint, ModCount};
 use hash_table;
 use std::hash::BuildHasher;
-use std::hash::Hash;
-use std::{mem, ptr};
-use avl_node::{AVLNodePtr, AVLNodePtrBase};
-use list::ListHeadPtrFn;
+use std::hash::{Hash, Hasher};
+use std::{cmp, mem, ptr, slice};
+use std::ptr::NonNull;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::io::{self, Read, Write};
+use std::cmp::Ordering;
+use avl_node::{AVLNodePtr, AVLNodePtrBase, AVLRoot};
+use list::{ListHead, ListHeadPtr, ListHeadPtrFn};
 use avl_node;
-use std::ops::Index;
+use std::ops::{Deref, DerefMut, Index};
 use std::borrow::Borrow;
 use std::iter::FromIterator;
-use self::fnv::FnvBuildHasher as RandomState;
+use std::collections::HashMap as StdHashMap;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::vec;
+use std::marker;
+pub use self::fnv::FnvBuildHasher as RandomState;
+use comparator::{Comparator, OrdComparator};
+use hashers::IdentityState;
+use pod::Pod;
 
 /// A hash map which uses AVL to resolve collision.
 ///
@@ -164,11 +177,31 @@ use self::fnv::FnvBuildHasher as RandomState;
 ///      ("Iceland", 10)]
 ///      .iter().cloned().collect();
 /// ```
-pub struct HashMap<K, V, S = RandomState> {
+///
+/// The first `INIT` HashIndex slots are stored inline in the table, so the index array is not
+/// allocated on its own until the map outgrows them. `INIT` must be a power of two and
+/// defaults to 8; a map which is always medium-sized can pick a larger one to skip the early
+/// resizes:
+///
+/// ```
+/// use hash_ord::hash_map::{HashMap, RandomState};
+/// use hash_ord::comparator::OrdComparator;
+///
+/// let mut map: HashMap<u32, u32, RandomState, OrdComparator, 64> = HashMap::default();
+/// for i in 0..40 {
+///     map.insert(i, i);
+/// }
+/// assert_eq!(map.resize_info().index_size, 64);
+/// ```
+pub struct HashMap<K, V, S = RandomState, C = OrdComparator, const INIT: usize = 8> {
     entry_fastbin: Fastbin,
     kv_fastbin: Fastbin,
-    hash_table: Box<HashTable<K, V>>,
+    hash_table: Box<HashTable<K, V, INIT>>,
     hash_builder: S,
+    comparator: C,
+    insertion_order: Option<Box<ListHead>>,
+    auto_shrink: bool,
+    rehash_deferred: bool,
 }
 
 struct InternalHashEntry<K, V> {
@@ -176,6 +209,67 @@ struct InternalHashEntry<K, V> {
     value: *mut V,
 }
 
+/// The entry layout of a map created by `with_insertion_order`. Every entry is also
+/// threaded into a list in insertion order.
+#[repr(C)]
+struct OrderedHashEntry<K, V> {
+    entry: InternalHashEntry<K, V>,
+    order: ListHead,
+}
+
+#[inline]
+fn entry_size<K, V>(insertion_order: bool) -> usize {
+    if insertion_order {
+        mem::size_of::<OrderedHashEntry<K, V>>()
+    } else {
+        mem::size_of::<InternalHashEntry<K, V>>()
+    }
+}
+
+/// With auto shrinking on, a `HashMap` shrinks once its load drops below `1 / 8`.
+const AUTO_SHRINK_LOAD_INV: usize = 8;
+
+/// A snapshot of the index sizing of a `HashMap`, returned by [`resize_info`].
+///
+/// [`resize_info`]: struct.HashMap.html#method.resize_info
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResizeInfo {
+    /// The current number of HashIndex slots.
+    pub index_size: usize,
+    /// The number of elements in the map.
+    pub entries: usize,
+    /// The number of elements at which the index array grows.
+    pub next_resize_at: usize,
+    /// The number of HashIndex slots after the next growth.
+    pub would_grow_to: usize,
+}
+
+/// What [`retain_until`] does with an entry, as decided by its closure.
+///
+/// [`retain_until`]: struct.HashMap.html#method.retain_until
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetainAction {
+    /// Keep the entry and go on.
+    Keep,
+    /// Remove the entry and go on.
+    Remove,
+    /// Keep the entry and every entry not visited yet, and stop.
+    StopKeepRest,
+}
+
+/// Where an entry sits in the AVL of its bucket, as reported by [`iter_nodes`].
+///
+/// [`iter_nodes`]: struct.HashMap.html#method.iter_nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeRole {
+    /// The root of the AVL, whether it has children or not.
+    Root,
+    /// A node with a parent and at least one child.
+    Internal,
+    /// A node with a parent and no children.
+    Leaf,
+}
+
 /// An iterator over the keys of a `HashMap`.
 ///
 /// This `struct` is created by the [`keys`] method on [`HashMap`]. See its
@@ -183,20 +277,22 @@ struct InternalHashEntry<K, V> {
 ///
 /// [`keys`]: struct.HashMap.html#method.keys
 /// [`HashMap`]: struct.HashMap.html
-pub struct Keys<'a, K, V, S>
+pub struct Keys<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
-    inner: Iter<'a, K, V, S>,
+    inner: Iter<'a, K, V, S, C, INIT>,
 }
 
-impl<'a, K, V, S> Iterator for Keys<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Iterator for Keys<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     type Item = &'a K;
 
@@ -217,20 +313,22 @@ where
 ///
 /// [`values`]: struct.HashMap.html#method.values
 /// [`HashMap`]: struct.HashMap.html
-pub struct Values<'a, K, V, S>
+pub struct Values<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
-    inner: Iter<'a, K, V, S>,
+    inner: Iter<'a, K, V, S, C, INIT>,
 }
 
-impl<'a, K, V, S> Iterator for Values<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Iterator for Values<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     type Item = &'a V;
 
@@ -251,20 +349,22 @@ where
 ///
 /// [`values_mut`]: struct.HashMap.html#method.values_mut
 /// [`HashMap`]: struct.HashMap.html
-pub struct ValuesMut<'a, K, V, S>
+pub struct ValuesMut<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
-    inner: IterMut<'a, K, V, S>,
+    inner: IterMut<'a, K, V, S, C, INIT>,
 }
 
-impl<'a, K, V, S> Iterator for ValuesMut<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Iterator for ValuesMut<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     type Item = &'a mut V;
 
@@ -279,27 +379,31 @@ where
 }
 
 /// An iterator over the (key, value) of a `HashMap`.
-pub struct Iter<'a, K, V, S>
+pub struct Iter<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     inner: *mut InternalHashEntry<K, V>,
-    map: &'a HashMap<K, V, S>,
+    map: &'a HashMap<K, V, S, C, INIT>,
     len: usize,
+    mod_count: ModCount,
 }
 
-impl<'a, K, V, S> Iterator for Iter<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Iterator for Iter<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     type Item = (&'a K, &'a V);
 
     #[inline]
     fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.map.hash_table.check_mod_count(self.mod_count);
         let entry = self.inner;
         if entry.is_null() || self.len == 0 {
             return None;
@@ -316,28 +420,119 @@ where
     }
 }
 
+/// An iterator over the (key, value, role in its bucket) of a `HashMap`.
+///
+/// This `struct` is created by the [`iter_nodes`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_nodes`]: struct.HashMap.html#method.iter_nodes
+/// [`HashMap`]: struct.HashMap.html
+pub struct IterNodes<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    inner: Iter<'a, K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Iterator for IterNodes<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    type Item = (&'a K, &'a V, NodeRole);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V, NodeRole)> {
+        let entry = self.inner.inner;
+        self.inner.next().map(|(k, v)| {
+            let node = entry.node_ptr().avl_node_ptr();
+            let role = if node.parent().is_null() {
+                NodeRole::Root
+            } else if node.left().not_null() || node.right().not_null() {
+                NodeRole::Internal
+            } else {
+                NodeRole::Leaf
+            };
+            (k, v, role)
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> ExactSizeIterator for IterNodes<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Iter<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    /// Returns the pair the next call to `next` will yield, without advancing the iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..3).map(|i| (i, i)).collect();
+    /// let mut iter = map.iter();
+    /// let peeked = iter.peek();
+    /// assert_eq!(peeked, iter.next());
+    /// ```
+    #[inline]
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        self.map.hash_table.check_mod_count(self.mod_count);
+        let entry = self.inner;
+        if entry.is_null() || self.len == 0 {
+            return None;
+        }
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
+    }
+}
+
 /// An iterator over the (key, mut value) of a `HashMap`.
-pub struct IterMut<'a, K, V, S>
+pub struct IterMut<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     inner: *mut InternalHashEntry<K, V>,
-    map: &'a HashMap<K, V, S>,
+    map: &'a HashMap<K, V, S, C, INIT>,
     len: usize,
+    mod_count: ModCount,
 }
 
-impl<'a, K, V, S> Iterator for IterMut<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Iterator for IterMut<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
     type Item = (&'a K, &'a mut V);
 
     #[inline]
     fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.map.hash_table.check_mod_count(self.mod_count);
         let entry = self.inner;
         if entry.is_null() || self.len == 0 {
             return None;
@@ -354,1793 +549,8973 @@ where
     }
 }
 
-#[inline]
-fn key_deref_to_kv<K, V>(key: *mut K) -> *mut (K, V) {
-    container_of!(key, (K, V), 0)
+/// An iterator over the (key, mut value) of a `HashMap` whose key matches a predicate.
+///
+/// This `struct` is created by the [`iter_mut_where`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_mut_where`]: struct.HashMap.html#method.iter_mut_where
+/// [`HashMap`]: struct.HashMap.html
+pub struct IterMutWhere<'a, K, V, S, C, F, const INIT: usize>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    inner: IterMut<'a, K, V, S, C, INIT>,
+    pred: F,
 }
 
-trait HashEntryBase<K, V> {
-    fn node_ptr(self) -> *mut HashNode<K>;
-    fn value(self) -> *mut V;
-    fn set_value(self, value: *mut V);
-    fn key(self) -> *mut K;
-    fn set_key(self, key: *mut K);
-    fn set_hash_value(self, hash_value: HashUint);
-}
+impl<'a, K, V, S, C, F, const INIT: usize> Iterator for IterMutWhere<'a, K, V, S, C, F, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+    F: Fn(&K) -> bool,
+{
+    type Item = (&'a K, &'a mut V);
 
-impl<K, V> HashEntryBase<K, V> for *mut InternalHashEntry<K, V> {
     #[inline]
-    fn node_ptr(self) -> *mut HashNode<K> {
-        unsafe { &mut (*self).node as *mut HashNode<K> }
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        for (k, v) in self.inner.by_ref() {
+            if (self.pred)(k) {
+                return Some((k, v));
+            }
+        }
+        None
     }
+
     #[inline]
-    fn value(self) -> *mut V {
-        unsafe { (*self).value }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
     }
+}
+
+/// An iterator over the non-empty buckets of a `HashMap`.
+///
+/// This `struct` is created by the [`buckets`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`buckets`]: struct.HashMap.html#method.buckets
+/// [`HashMap`]: struct.HashMap.html
+pub struct Buckets<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    index: *mut HashIndex,
+    map: &'a HashMap<K, V, S, C, INIT>,
+    mod_count: ModCount,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Iterator for Buckets<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    type Item = BucketRef<'a, K, V>;
+
     #[inline]
-    fn set_value(self, value: *mut V) {
-        unsafe {
-            (*self).value = value;
+    fn next(&mut self) -> Option<BucketRef<'a, K, V>> {
+        self.map.hash_table.check_mod_count(self.mod_count);
+        let index = self.index;
+        if index.is_null() {
+            return None;
         }
+        self.index = self.map.hash_table.index_next(index);
+        Some(BucketRef {
+            position: self.map.hash_table.index_position(index),
+            root: index.avl_root_node(),
+            _marker: marker::PhantomData,
+        })
     }
-    #[inline]
-    fn key(self) -> *mut K {
-        unsafe { (*self).node.key }
+}
+
+/// A non-empty bucket of a `HashMap`: the entries under one HashIndex.
+///
+/// A `BucketRef` only borrows the map, and may be sent to another thread when the keys and
+/// values are `Sync`, so a read-only scan can be sharded by bucket.
+pub struct BucketRef<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    position: usize,
+    root: AVLNodePtr,
+    _marker: marker::PhantomData<&'a (K, V)>,
+}
+
+unsafe impl<'a, K: Sync, V: Sync> Send for BucketRef<'a, K, V> {}
+unsafe impl<'a, K: Sync, V: Sync> Sync for BucketRef<'a, K, V> {}
+
+impl<'a, K, V> Clone for BucketRef<'a, K, V> {
+    fn clone(&self) -> BucketRef<'a, K, V> {
+        BucketRef {
+            position: self.position,
+            root: self.root,
+            _marker: marker::PhantomData,
+        }
     }
+}
+
+impl<'a, K, V> BucketRef<'a, K, V> {
+    /// The position of the bucket in the index array, below `index_size`.
     #[inline]
-    fn set_key(self, key: *mut K) {
-        unsafe {
-            (*self).node.key = key;
-        }
+    pub fn index(&self) -> usize {
+        self.position
     }
+
+    /// An iterator visiting the entries of the bucket, ordered by hash value, then by key.
     #[inline]
-    fn set_hash_value(self, hash_value: HashUint) {
-        unsafe {
-            (*self).node.hash_val = hash_value;
+    pub fn iter(&self) -> BucketIter<'a, K, V> {
+        BucketIter {
+            node: self.root.first_node(),
+            _marker: marker::PhantomData,
         }
     }
 }
 
-trait HashNodeDerefToHashEntry<K, V> {
-    fn deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V>;
-}
+impl<'a, K, V> IntoIterator for BucketRef<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = BucketIter<'a, K, V>;
 
-impl<K, V> HashNodeDerefToHashEntry<K, V> for *mut HashNode<K> {
-    /// Dereference HashNode to InternalHashEntry
-    fn deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V> {
-        container_of!(self, InternalHashEntry<K, V>, node)
+    #[inline]
+    fn into_iter(self) -> BucketIter<'a, K, V> {
+        self.iter()
     }
 }
 
-/// Update HashTable with a new InternalHashEntry and return the old one.
-#[inline]
-unsafe fn hash_table_update<K, V>(
-    hash_table: &mut HashTable<K, V>,
-    new_entry: *mut InternalHashEntry<K, V>,
-) -> *mut InternalHashEntry<K, V>
+/// An iterator over the (key, value) of a bucket.
+///
+/// This `struct` is created by the [`iter`] method on [`BucketRef`].
+///
+/// [`iter`]: struct.BucketRef.html#method.iter
+/// [`BucketRef`]: struct.BucketRef.html
+pub struct BucketIter<'a, K, V>
 where
-    K: Ord + Hash,
+    K: 'a,
+    V: 'a,
 {
-    debug_assert!(!new_entry.is_null());
-    let new_node = new_entry.node_ptr();
-    let duplicate = hash_table.hash_add(new_node);
-    if !duplicate.is_null() {
-        return duplicate.deref_to_hash_entry();
-    }
-    ptr::null_mut()
+    node: AVLNodePtr,
+    _marker: marker::PhantomData<&'a (K, V)>,
 }
 
-/// Alloc an InternalHashEntry in `Fastbin`
-#[inline]
-fn entry_alloc<K, V>(
-    entry_fastbin: &mut Fastbin,
-    key: *mut K,
-    value: *mut V,
-    hash_value: HashUint,
-) -> *mut InternalHashEntry<K, V> {
-    let entry = entry_fastbin.alloc() as *mut InternalHashEntry<K, V>;
-    debug_assert!(!entry.is_null());
-    entry.set_value(value);
-    entry.set_key(key);
-    entry.set_hash_value(hash_value);
-    entry
-}
+unsafe impl<'a, K: Sync, V: Sync> Send for BucketIter<'a, K, V> {}
 
-/// Alloc a (Key, Value) pair in `Fastbin`
-#[inline]
-fn kv_alloc<K, V>(kv_fastbin: &mut Fastbin, key: K, value: V) -> *mut (K, V) {
-    let kv = kv_fastbin.alloc() as *mut (K, V);
-    unsafe {
-        let key_ptr = &mut (*kv).0 as *mut K;
-        let value_ptr = &mut (*kv).1 as *mut V;
-        ptr::write(key_ptr, key);
-        ptr::write(value_ptr, value);
+impl<'a, K, V> Iterator for BucketIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.node;
+        if node.is_null() {
+            return None;
+        }
+        self.node = node.next();
+        let entry = node.avl_hash_deref_mut::<K>().deref_to_hash_entry();
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
-    kv
 }
 
-/// A view into a single entry in a map, which may either be vacant or occupied.
+/// An iterator over the entries of a `HashMap` stored in a range of buckets.
 ///
-/// This `enum` is constructed from the [`entry`] method on [`HashMap`].
+/// This `struct` is created by the [`iter_index_range`] method on [`HashMap`]. See its
+/// documentation for more.
 ///
+/// [`iter_index_range`]: struct.HashMap.html#method.iter_index_range
 /// [`HashMap`]: struct.HashMap.html
-/// [`entry`]: struct.HashMap.html#method.entry
-pub enum Entry<'a, K, V, S>
+pub struct IterIndexRange<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
-    /// An occupied entry.
-    Occupied(OccupiedEntry<'a, K, V, S>),
-
-    /// A vacant entry.
-    Vacant(VacantEntry<'a, K, V, S>),
+    position: usize,
+    end: usize,
+    node: AVLNodePtr,
+    map: &'a HashMap<K, V, S, C, INIT>,
+    mod_count: ModCount,
 }
 
-impl<'a, K, V, S> Entry<'a, K, V, S> {
-    /// Returns a reference to this entry's key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
-    /// ```
-    pub fn key(&self) -> &K {
-        match *self {
-            Entry::Occupied(ref entry) => entry.key(),
-            Entry::Vacant(ref entry) => entry.key(),
+impl<'a, K, V, S, C, const INIT: usize> Iterator for IterIndexRange<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.map.hash_table.check_mod_count(self.mod_count);
+        while self.node.is_null() {
+            if self.position >= self.end {
+                return None;
+            }
+            let index = self.map.hash_table.index_at(self.position);
+            self.node = index.avl_root_node().first_node();
+            self.position += 1;
         }
+        let node = self.node;
+        self.node = node.next();
+        let entry = node.avl_hash_deref_mut::<K>().deref_to_hash_entry();
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
 }
 
-impl<'a, K, V, S> Entry<'a, K, V, S>
+/// An iterator over the (key, value) of a `HashMap` sharing one hash value.
+///
+/// This `struct` is created by the [`entries_with_hash`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`entries_with_hash`]: struct.HashMap.html#method.entries_with_hash
+/// [`HashMap`]: struct.HashMap.html
+pub struct EntriesWithHash<'a, K, V>
 where
-    K: Ord + Hash,
-    S: BuildHasher,
+    K: 'a,
+    V: 'a,
 {
-    /// Ensures a value is in the entry by inserting the default if empty, and returns
-    /// a mutable reference to the value in the entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// assert_eq!(map["poneyland"], 12);
-    ///
-    /// *map.entry("poneyland").or_insert(12) += 10;
-    /// assert_eq!(map["poneyland"], 22);
-    /// ```
-    pub fn or_insert(self, default: V) -> &'a mut V {
-        match self {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => entry.insert(default),
-        }
-    }
+    node: *mut HashNode<K>,
+    hash: HashUint,
+    _marker: marker::PhantomData<&'a (K, V)>,
+}
 
-    /// Ensures a value is in the entry by inserting the result of the default function if empty,
-    /// and returns a mutable reference to the value in the entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, String> = HashMap::new();
-    /// let s = "hoho".to_string();
-    ///
-    /// map.entry("poneyland").or_insert_with(|| s);
-    ///
-    /// assert_eq!(map["poneyland"], "hoho".to_string());
-    /// ```
-    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
-        match self {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => entry.insert(default()),
+impl<'a, K, V> Iterator for EntriesWithHash<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.node;
+        if node.is_null() || node.hash_val() != self.hash {
+            return None;
         }
+        // nodes of a bucket are ordered by hash value first, so equal ones are adjacent
+        let next = node.avl_node_ptr().next();
+        self.node = if next.is_null() {
+            ptr::null_mut()
+        } else {
+            next.avl_hash_deref_mut::<K>()
+        };
+        let entry: *mut InternalHashEntry<K, V> = node.deref_to_hash_entry();
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
+}
 
-    /// Provides in-place mutable access to an occupied entry before any
-    /// potential inserts into the map.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    ///
-    /// map.entry("poneyland")
-    ///    .and_modify(|e| { *e += 1 })
-    ///    .or_insert(42);
-    /// assert_eq!(map["poneyland"], 42);
-    ///
-    /// map.entry("poneyland")
-    ///    .and_modify(|e| { *e += 1 })
-    ///    .or_insert(42);
-    /// assert_eq!(map["poneyland"], 43);
-    /// ```
-    pub fn and_modify<F>(self, mut f: F) -> Self
-    where
-        F: FnMut(&mut V),
-    {
-        match self {
-            Entry::Occupied(mut entry) => {
-                f(entry.get_mut());
-                Entry::Occupied(entry)
+/// An iterator over the (key, value, bucket index, depth) of a `HashMap`.
+///
+/// This `struct` is created by the [`iter_with_layout`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_with_layout`]: struct.HashMap.html#method.iter_with_layout
+/// [`HashMap`]: struct.HashMap.html
+pub struct IterWithLayout<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    index: *mut HashIndex,
+    bucket: usize,
+    queue: VecDeque<(AVLNodePtr, usize)>,
+    map: &'a HashMap<K, V, S, C, INIT>,
+    len: usize,
+    mod_count: ModCount,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Iterator for IterWithLayout<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    type Item = (&'a K, &'a V, usize, usize);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V, usize, usize)> {
+        self.map.hash_table.check_mod_count(self.mod_count);
+        loop {
+            if let Some((node, depth)) = self.queue.pop_front() {
+                // breadth first, so depths never decrease within a bucket
+                if node.left().not_null() {
+                    self.queue.push_back((node.left(), depth + 1));
+                }
+                if node.right().not_null() {
+                    self.queue.push_back((node.right(), depth + 1));
+                }
+                self.len -= 1;
+                let entry: *mut InternalHashEntry<K, V> =
+                    node.avl_hash_deref_mut::<K>().deref_to_hash_entry();
+                return unsafe { Some((&(*entry.key()), &(*entry.value()), self.bucket, depth)) };
             }
-            Entry::Vacant(entry) => Entry::Vacant(entry),
+            let index = self.index;
+            if index.is_null() {
+                return None;
+            }
+            self.index = self.map.hash_table.index_next(index);
+            self.bucket = self.map.hash_table.index_position(index);
+            self.queue.push_back((index.avl_root_node(), 0));
         }
     }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
-/// A view into an occupied entry in a `HashMap`.
-/// It is part of the [`Entry`] enum.
-///
-/// [`Entry`]: enum.Entry.html
-pub struct OccupiedEntry<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> ExactSizeIterator for IterWithLayout<'a, K, V, S, C, INIT>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
-    key: Option<K>,
-    hash_entry: *mut InternalHashEntry<K, V>,
-    hash_map_mut: &'a mut HashMap<K, V, S>,
 }
 
-/// A view into a vacant entry in a `HashMap`.
-/// It is part of the [`Entry`] enum.
+/// An iterator over the (key, value) of a `HashMap` in ascending key order.
 ///
-/// [`Entry`]: enum.Entry.html
-pub struct VacantEntry<'a, K, V, S>
+/// This `struct` is created by the [`iter_sorted`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_sorted`]: struct.HashMap.html#method.iter_sorted
+/// [`HashMap`]: struct.HashMap.html
+pub struct SortedIter<'a, K, V>
 where
     K: 'a,
     V: 'a,
-    S: 'a,
 {
-    hash_value: HashUint,
-    key: K,
-    parent: AVLNodePtr,
-    link: *mut AVLNodePtr,
-    hash_map_mut: &'a mut HashMap<K, V, S>,
+    inner: vec::IntoIter<(&'a K, &'a V)>,
 }
 
-impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+impl<'a, K, V> Iterator for SortedIter<'a, K, V>
 where
-    K: Ord + Hash,
-    S: BuildHasher,
+    K: 'a,
+    V: 'a,
 {
-    /// Take the ownership of the key and value from the map.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// if let Entry::Occupied(o) = map.entry("poneyland") {
-    ///     // We delete the entry from the map.
-    ///     o.remove_entry();
-    /// }
-    ///
-    /// assert_eq!(map.contains_key("poneyland"), false);
-    /// ```
-    pub fn remove_entry(self) -> (K, V) {
-        let hash_entry = self.hash_entry;
-        self.hash_map_mut.erase(hash_entry).unwrap()
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next()
     }
 
-    /// Takes the value out of the entry, and returns it.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// if let Entry::Occupied(o) = map.entry("poneyland") {
-    ///     assert_eq!(o.remove(), 12);
-    /// }
-    ///
-    /// assert_eq!(map.contains_key("poneyland"), false);
-    /// ```
-    pub fn remove(self) -> V {
-        self.remove_entry().1
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
-    /// Gets a reference to the key in the entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
-    /// ```
-    pub fn key(&self) -> &K {
-        unsafe { &*self.hash_entry.key() }
+impl<'a, K, V> DoubleEndedIterator for SortedIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next_back()
     }
+}
 
-    /// Returns a key that was used for search.
-    ///
-    /// The key was retained for further use.
-    fn take_key(&mut self) -> Option<K> {
-        self.key.take()
+impl<'a, K, V> ExactSizeIterator for SortedIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
     }
+}
 
-    /// Replaces the key in the hash map with the key used to create this entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    /// use std::rc::Rc;
-    ///
-    /// let mut map: HashMap<Rc<String>, u32> = HashMap::new();
-    /// let mut known_strings: Vec<Rc<String>> = Vec::new();
-    ///
-    /// // Initialise known strings, run program, etc.
-    ///
-    /// reclaim_memory(&mut map, &known_strings);
-    ///
-    /// fn reclaim_memory(map: &mut HashMap<Rc<String>, u32>, known_strings: &[Rc<String>] ) {
-    ///     for s in known_strings {
-    ///         if let Entry::Occupied(entry) = map.entry(s.clone()) {
-    ///             // Replaces the entry's key with our version of it in `known_strings`.
-    ///             entry.replace_key();
-    ///         }
-    ///     }
-    /// }
-    /// ```
-    pub fn replace_key(self) -> K {
-        let old_key = unsafe { &mut *self.hash_entry.key() };
-        mem::replace(old_key, self.key.unwrap())
+/// A mutable iterator over the entries of a `HashMap` in ascending key order.
+///
+/// This `struct` is created by the [`iter_mut_sorted`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_mut_sorted`]: struct.HashMap.html#method.iter_mut_sorted
+/// [`HashMap`]: struct.HashMap.html
+pub struct SortedIterMut<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    inner: vec::IntoIter<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for SortedIterMut<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    type Item = (&'a K, &'a mut V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.inner.next()
     }
 
-    /// Gets a reference to the value in the entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// if let Entry::Occupied(o) = map.entry("poneyland") {
-    ///     assert_eq!(o.get(), &12);
-    /// }
-    /// ```
-    pub fn get(&self) -> &V {
-        unsafe { &*self.hash_entry.value() }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
+}
 
-    /// Gets a mutable reference to the value in the entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// assert_eq!(map["poneyland"], 12);
-    /// if let Entry::Occupied(mut o) = map.entry("poneyland") {
-    ///      *o.get_mut() += 10;
-    /// }
-    ///
-    /// assert_eq!(map["poneyland"], 22);
-    /// ```
-    pub fn get_mut(&mut self) -> &mut V {
-        unsafe { &mut *self.hash_entry.value() }
+impl<'a, K, V> DoubleEndedIterator for SortedIterMut<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.inner.next_back()
     }
+}
 
-    /// Converts the OccupiedEntry into a mutable reference to the value in the entry
-    /// with a lifetime bound to the map itself.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// assert_eq!(map["poneyland"], 12);
-    /// if let Entry::Occupied(o) = map.entry("poneyland") {
-    ///     *o.into_mut() += 10;
-    /// }
-    ///
-    /// assert_eq!(map["poneyland"], 22);
-    /// ```
-    pub fn into_mut(self) -> &'a mut V {
-        unsafe { &mut *self.hash_entry.value() }
+impl<'a, K, V> ExactSizeIterator for SortedIterMut<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
     }
+}
 
-    /// Sets the value of the entry, and returns the entry's old value.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// map.entry("poneyland").or_insert(12);
-    ///
-    /// if let Entry::Occupied(mut o) = map.entry("poneyland") {
-    ///     assert_eq!(o.insert(15), 12);
-    /// }
-    ///
-    /// assert_eq!(map["poneyland"], 15);
-    /// ```
-    pub fn insert(&mut self, mut value: V) -> V {
-        let old_value = self.get_mut();
-        mem::swap(&mut value, old_value);
-        value
+/// A draining iterator over the entries of a `HashMap` in ascending key order.
+///
+/// This `struct` is created by the [`drain_sorted`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`drain_sorted`]: struct.HashMap.html#method.drain_sorted
+/// [`HashMap`]: struct.HashMap.html
+pub struct DrainSorted<K, V> {
+    inner: vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for DrainSorted<K, V> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next()
     }
 
-    /// Replaces the entry, returning the old key and value. The new key in the hash map will be
-    /// the key used to create this entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    /// use std::rc::Rc;
-    ///
-    /// let mut map: HashMap<Rc<String>, u32> = HashMap::new();
-    /// map.insert(Rc::new("Stringthing".to_string()), 15);
-    ///
-    /// let my_key = Rc::new("Stringthing".to_string());
-    ///
-    /// if let Entry::Occupied(entry) = map.entry(my_key) {
-    ///     // Also replace the key with a handle to our other key.
-    ///     let (old_key, old_value): (Rc<String>, u32) = entry.replace_entry(16);
-    ///     assert_eq!(Rc::strong_count(&old_key), 1);
-    ///     assert!(old_value == 15);
-    /// }
-    ///
-    /// ```
-    pub fn replace_entry(self, value: V) -> (K, V) {
-        let (old_key, old_value) =
-            unsafe { (&mut *self.hash_entry.key(), &mut *self.hash_entry.value()) };
-        let old_key = mem::replace(old_key, self.key.unwrap());
-        let old_value = mem::replace(old_value, value);
-        (old_key, old_value)
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-impl<'a, K, V, S> VacantEntry<'a, K, V, S> {
-    /// Gets a reference to the key that would be used when inserting a value
-    /// through the `VacantEntry`.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
-    /// ```
-    pub fn key(&self) -> &K {
-        &self.key
+impl<K, V> DoubleEndedIterator for DrainSorted<K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.inner.next_back()
     }
+}
 
-    /// Take ownership of the key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    ///
-    /// if let Entry::Vacant(v) = map.entry("poneyland") {
-    ///     v.into_key();
-    /// }
-    /// ```
-    pub fn into_key(self) -> K {
-        self.key
+impl<K, V> ExactSizeIterator for DrainSorted<K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
     }
 }
 
-impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+/// An iterator over the entries of a `HashMap` in insertion order.
+///
+/// This `struct` is created by the [`iter_insertion_order`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`iter_insertion_order`]: struct.HashMap.html#method.iter_insertion_order
+/// [`HashMap`]: struct.HashMap.html
+pub struct InsertionOrderIter<'a, K, V>
 where
-    K: Ord + Hash,
-    S: BuildHasher,
+    K: 'a,
+    V: 'a,
 {
-    unsafe fn _internal_insert(self, value: V) -> &'a mut V {
-        let hash_value = self.hash_value;
-        let index = self.hash_map_mut.hash_table.get_hash_index(hash_value);
-        let key = self.key;
-        let kv_ptr = self.hash_map_mut.kv_alloc(key, value);
-        let new_entry = self.hash_map_mut.entry_alloc(
-            &mut (*kv_ptr).0 as *mut K,
-            &mut (*kv_ptr).1 as *mut V,
-            hash_value,
-        );
-        let new_node = new_entry.node_ptr();
-        if index.avl_root_node().is_null() {
-            self.hash_map_mut
-                .hash_table
-                .head_ptr()
-                .list_add_tail(index.node_ptr());
+    front: ListHeadPtr,
+    back: ListHeadPtr,
+    len: usize,
+    _marker: marker::PhantomData<&'a (K, V)>,
+}
+
+impl<'a, K, V> Iterator for InsertionOrderIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
         }
-        avl_node::link_node(new_node.avl_node_ptr(), self.parent, self.link);
-        avl_node::node_post_insert(new_node.avl_node_ptr(), index.avl_root_ptr());
-        self.hash_map_mut.hash_table.inc_count(1);
-        let new_len = self.hash_map_mut.len();
-        self.hash_map_mut.rehash(new_len);
-        &mut *new_entry.value()
+        let entry: *mut InternalHashEntry<K, V> = self.front.order_deref_to_hash_entry();
+        self.front = self.front.next();
+        self.len -= 1;
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
 
-    /// Sets the value of the entry with the VacantEntry's key,
-    /// and returns a mutable reference to it.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::{HashMap, Entry};
-    ///
-    /// let mut map: HashMap<&str, u32> = HashMap::new();
-    /// if let Entry::Vacant(o) = map.entry("poneyland") {
-    ///     o.insert(37);
-    /// }
-    /// assert_eq!(map["poneyland"], 37);
-    /// ```
-    pub fn insert(self, value: V) -> &'a mut V {
-        unsafe { self._internal_insert(value) }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
 }
 
-impl<K, V, S> HashMap<K, V, S> {
-    fn recurse_destroy<F>(&mut self, node: avl_node::AVLNodePtr, f: &mut F)
-    where
-        F: FnMut((K, V)),
-    {
-        if node.left().not_null() {
-            self.recurse_destroy(node.left(), f);
-        }
-        if node.right().not_null() {
-            self.recurse_destroy(node.right(), f);
+impl<'a, K, V> DoubleEndedIterator for InsertionOrderIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
         }
-        let hash_node = node.avl_hash_deref_mut::<K>();
-        let entry: *mut InternalHashEntry<K, V> = hash_node.deref_to_hash_entry();
-        self.entry_fastbin.del(entry as VoidPtr);
-        let kv_ptr = key_deref_to_kv::<K, V>(hash_node.key_ptr());
-        unsafe { (*f)(ptr::read(kv_ptr)) };
-        self.kv_fastbin.del(kv_ptr as VoidPtr);
-        self.hash_table.dec_count(1);
+        let entry: *mut InternalHashEntry<K, V> = self.back.order_deref_to_hash_entry();
+        self.back = self.back.prev();
+        self.len -= 1;
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
+}
 
-    /// Clears the map, removing all key-value pairs. Keeps the allocated memory
-    /// for reuse.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use hash_ord::hash_map::HashMap;
-    ///
-    /// let mut a = HashMap::new();
-    /// a.insert(1, "a");
-    /// a.clear();
-    /// assert!(a.is_empty());
-    /// ```
-    pub fn clear(&mut self) {
-        let mut destroy_callback = |_| {};
-        loop {
-            let node = self.hash_table.pop_first_index();
-            if node.is_null() {
-                break;
-            }
-            self.recurse_destroy(node, &mut destroy_callback);
-        }
-        debug_assert_eq!(self.hash_table.size(), 0);
-    }
+impl<'a, K, V> ExactSizeIterator for InsertionOrderIter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+}
 
-    /// Returns the number of elements the map can hold without reallocating.
-    ///
-    /// This number is a lower bound; the `HashMap<K, V>` might be able to hold
-    /// more, but is guaranteed to be able to hold at least this many.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use std::collections::HashMap;
-    ///
-    /// let map: HashMap<isize, isize> = HashMap::with_capacity(100);
-    /// assert!(map.capacity() >= 100);
-    /// ```
+#[inline]
+fn key_deref_to_kv<K, V>(key: *mut K) -> *mut (K, V) {
+    container_of!(key, (K, V), 0)
+}
+
+trait HashEntryBase<K, V> {
+    fn node_ptr(self) -> *mut HashNode<K>;
+    fn value(self) -> *mut V;
+    fn set_value(self, value: *mut V);
+    fn key(self) -> *mut K;
+    fn set_key(self, key: *mut K);
+    fn set_hash_value(self, hash_value: HashUint);
+    fn order_node(self) -> ListHeadPtr;
+}
+
+impl<K, V> HashEntryBase<K, V> for *mut InternalHashEntry<K, V> {
     #[inline]
-    pub fn capacity(&self) -> usize {
-        self.hash_table.capacity()
+    fn node_ptr(self) -> *mut HashNode<K> {
+        unsafe { &mut (*self).node as *mut HashNode<K> }
     }
-
-    /// Returns the maximum node count under a simgle HashIndex
     #[inline]
-    pub fn get_max_node_of_single_index(&self) -> i32 {
-        self.hash_table.get_max_node_of_single_index()
+    fn value(self) -> *mut V {
+        unsafe { (*self).value }
     }
-
     #[inline]
-    fn first(&self) -> *mut InternalHashEntry<K, V> {
-        let hash_node = self.hash_table.node_first();
-        if hash_node.is_null() {
-            return ptr::null_mut();
+    fn set_value(self, value: *mut V) {
+        unsafe {
+            (*self).value = value;
         }
-        hash_node.deref_to_hash_entry()
     }
-
     #[inline]
-    fn last(&self) -> *mut InternalHashEntry<K, V> {
-        let hash_node = self.hash_table.node_last();
-        if hash_node.is_null() {
-            return ptr::null_mut();
-        }
-        hash_node.deref_to_hash_entry()
+    fn key(self) -> *mut K {
+        unsafe { (*self).node.key }
     }
-
     #[inline]
-    fn next(&self, entry: *mut InternalHashEntry<K, V>) -> *mut InternalHashEntry<K, V> {
-        let hash_node = self.hash_table.node_next(entry.node_ptr());
-        if hash_node.is_null() {
-            return ptr::null_mut();
+    fn set_key(self, key: *mut K) {
+        unsafe {
+            (*self).node.key = key;
         }
-        hash_node.deref_to_hash_entry()
     }
-
     #[inline]
-    fn prev(&self, entry: *mut InternalHashEntry<K, V>) -> *mut InternalHashEntry<K, V> {
-        let hash_node = self.hash_table.node_prev(entry.node_ptr());
-        if hash_node.is_null() {
-            return ptr::null_mut();
+    fn set_hash_value(self, hash_value: HashUint) {
+        unsafe {
+            (*self).node.hash_val = hash_value;
         }
-        hash_node.deref_to_hash_entry()
     }
+    /// Only valid for entries of a map which tracks insertion order.
+    #[inline]
+    fn order_node(self) -> ListHeadPtr {
+        unsafe { &mut (*(self as *mut OrderedHashEntry<K, V>)).order as ListHeadPtr }
+    }
+}
 
-    /// Returns the number of elements in the map.
+trait ListHeadDerefToHashEntry<K, V> {
+    fn order_deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V>;
+}
+
+impl<K, V> ListHeadDerefToHashEntry<K, V> for ListHeadPtr {
+    /// Dereference the insertion order ListHead to InternalHashEntry
+    fn order_deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V> {
+        container_of!(self, OrderedHashEntry<K, V>, order) as *mut InternalHashEntry<K, V>
+    }
+}
+
+trait HashNodeDerefToHashEntry<K, V> {
+    fn deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V>;
+}
+
+impl<K, V> HashNodeDerefToHashEntry<K, V> for *mut HashNode<K> {
+    /// Dereference HashNode to InternalHashEntry
+    fn deref_to_hash_entry(self) -> *mut InternalHashEntry<K, V> {
+        container_of!(self, InternalHashEntry<K, V>, node)
+    }
+}
+
+/// Update HashTable with a new InternalHashEntry and return the old one.
+#[inline]
+unsafe fn hash_table_update<K, V, C, const INIT: usize>(
+    hash_table: &mut HashTable<K, V, INIT>,
+    new_entry: *mut InternalHashEntry<K, V>,
+    cmp: &C,
+) -> *mut InternalHashEntry<K, V>
+where
+    K: Ord + Hash,
+    C: Comparator<K>,
+{
+    debug_assert!(!new_entry.is_null());
+    let new_node = new_entry.node_ptr();
+    let duplicate = hash_table.hash_add(new_node, cmp);
+    if !duplicate.is_null() {
+        return duplicate.deref_to_hash_entry();
+    }
+    ptr::null_mut()
+}
+
+/// Alloc an InternalHashEntry in `Fastbin`
+#[inline]
+fn entry_alloc<K, V>(
+    entry_fastbin: &mut Fastbin,
+    key: *mut K,
+    value: *mut V,
+    hash_value: HashUint,
+) -> *mut InternalHashEntry<K, V> {
+    let entry = entry_fastbin.alloc() as *mut InternalHashEntry<K, V>;
+    debug_assert!(!entry.is_null());
+    entry_init(entry, key, value, hash_value)
+}
+
+/// Point a freshly allocated InternalHashEntry to its key and value
+#[inline]
+fn entry_init<K, V>(
+    entry: *mut InternalHashEntry<K, V>,
+    key: *mut K,
+    value: *mut V,
+    hash_value: HashUint,
+) -> *mut InternalHashEntry<K, V> {
+    entry.set_value(value);
+    entry.set_key(key);
+    entry.set_hash_value(hash_value);
+    entry
+}
+
+/// Alloc a (Key, Value) pair in `Fastbin`
+#[inline]
+fn kv_alloc<K, V>(kv_fastbin: &mut Fastbin, key: K, value: V) -> *mut (K, V) {
+    let kv = kv_fastbin.alloc() as *mut (K, V);
+    unsafe {
+        let key_ptr = &mut (*kv).0 as *mut K;
+        let value_ptr = &mut (*kv).1 as *mut V;
+        ptr::write(key_ptr, key);
+        ptr::write(value_ptr, value);
+    }
+    kv
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`] method on [`HashMap`].
+///
+/// [`HashMap`]: struct.HashMap.html
+/// [`entry`]: struct.HashMap.html#method.entry
+pub enum Entry<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, S, C, INIT>),
+
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, S, C, INIT>),
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Entry<'a, K, V, S, C, INIT> {
+    /// Returns a reference to this entry's key.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut a = HashMap::new();
-    /// assert_eq!(a.len(), 0);
-    /// a.insert(1, "a");
-    /// assert_eq!(a.len(), 1);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
     /// ```
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.hash_table.size()
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
     }
 
-    /// Returns true if the map contains no element.
+    /// Returns a reference to this entry's value if it is occupied, without consuming the
+    /// entry.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut a = HashMap::new();
-    /// assert!(a.is_empty());
-    /// a.insert(1, "a");
-    /// assert!(!a.is_empty());
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.insert("poneyland", 12);
+    /// assert_eq!(map.entry("poneyland").get(), Some(&12));
+    /// assert_eq!(map.entry("horseland").get(), None);
     /// ```
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
-    }
-
-    #[inline]
-    fn entry_alloc(
-        &mut self,
-        key: *mut K,
-        value: *mut V,
-        hash_value: HashUint,
-    ) -> *mut InternalHashEntry<K, V> {
-        entry_alloc(&mut self.entry_fastbin, key, value, hash_value)
-    }
-
-    #[inline]
-    fn kv_alloc(&mut self, key: K, value: V) -> *mut (K, V) {
-        kv_alloc(&mut self.kv_fastbin, key, value)
+    pub fn get(&self) -> Option<&V> {
+        match *self {
+            Entry::Occupied(ref entry) => Some(entry.get()),
+            Entry::Vacant(_) => None,
+        }
     }
 
-    /// An iterator visiting all keys in arbitrary order.
-    /// The iterator element type is `&'a K`.
+    /// Returns true if the entry is occupied.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert("a", 1);
-    /// map.insert("b", 2);
-    /// map.insert("c", 3);
-    ///
-    /// for key in map.keys() {
-    ///     println!("{}", key);
-    /// }
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.insert("poneyland", 12);
+    /// assert!(map.entry("poneyland").is_occupied());
+    /// assert!(!map.entry("horseland").is_occupied());
     /// ```
-    pub fn keys(&self) -> Keys<K, V, S> {
-        Keys { inner: self.iter() }
+    #[inline]
+    pub fn is_occupied(&self) -> bool {
+        match *self {
+            Entry::Occupied(_) => true,
+            Entry::Vacant(_) => false,
+        }
     }
+}
 
-    /// An iterator visiting all values in arbitrary order.
-    /// The iterator element type is `&'a V`.
+impl<'a, K, V, S, C, const INIT: usize> Entry<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert("a", 1);
-    /// map.insert("b", 2);
-    /// map.insert("c", 3);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
     ///
-    /// for val in map.values() {
-    ///     println!("{}", val);
-    /// }
+    /// assert_eq!(map["poneyland"], 12);
+    ///
+    /// *map.entry("poneyland").or_insert(12) += 10;
+    /// assert_eq!(map["poneyland"], 22);
     /// ```
-    pub fn values(&self) -> Values<K, V, S> {
-        Values { inner: self.iter() }
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
     }
 
-    /// An iterator visiting all values mutably in arbitrary order.
-    /// The iterator element type is `&'a mut V`.
+    /// Sets the value of the entry, whether it was occupied or vacant, and returns an
+    /// `OccupiedEntry`. Unlike [`or_insert`], an existing value is overwritten.
+    ///
+    /// The returned entry retains the search key only if it was occupied.
+    ///
+    /// [`or_insert`]: #method.or_insert
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    ///
-    /// map.insert("a", 1);
-    /// map.insert("b", 2);
-    /// map.insert("c", 3);
-    ///
-    /// for val in map.values_mut() {
-    ///     *val = *val + 10;
-    /// }
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// let entry = map.entry("poneyland").insert(12);
+    /// assert_eq!(entry.key(), &"poneyland");
     ///
-    /// for val in map.values() {
-    ///     println!("{}", val);
-    /// }
+    /// map.entry("poneyland").insert(37);
+    /// assert_eq!(map["poneyland"], 37);
     /// ```
-    pub fn values_mut(&mut self) -> ValuesMut<K, V, S> {
-        ValuesMut {
-            inner: self.iter_mut(),
+    pub fn insert(self, value: V) -> OccupiedEntry<'a, K, V, S, C, INIT> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                entry
+            }
+            Entry::Vacant(entry) => entry.insert_entry(value),
         }
     }
 
-    /// An iterator visiting all key-value pairs in arbitrary order.
-    /// The iterator element type is `(&'a K, &'a V)`.
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert("a", 1);
-    /// map.insert("b", 2);
-    /// map.insert("c", 3);
+    /// let mut map: HashMap<&str, String> = HashMap::new();
+    /// let s = "hoho".to_string();
     ///
-    /// for (key, val) in map.iter() {
-    ///     println!("key: {} val: {}", key, val);
-    /// }
+    /// map.entry("poneyland").or_insert_with(|| s);
+    ///
+    /// assert_eq!(map["poneyland"], "hoho".to_string());
     /// ```
-    pub fn iter(&self) -> Iter<K, V, S> {
-        Iter {
-            inner: self.first(),
-            map: self,
-            len: self.len(),
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
         }
     }
 
-    /// An iterator visiting all key-value pairs in arbitrary order,
-    /// with mutable references to the values.
-    /// The iterator element type is `(&'a K, &'a mut V)`.
+    /// Ensures a value is in the entry by inserting the result of the fallible function `f`
+    /// if empty, and returns a mutable reference to the value in the entry.
+    ///
+    /// If `f` fails, its error is returned and the map is left unchanged: nothing is
+    /// allocated for a vacant entry until its value exists.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert("a", 1);
-    /// map.insert("b", 2);
-    /// map.insert("c", 3);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
     ///
-    /// // Update all values
-    /// for (_, val) in map.iter_mut() {
-    ///     *val *= 2;
-    /// }
+    /// let res: Result<_, ()> = map.entry("poneyland").or_try_insert_with(|| Ok(3));
+    /// assert_eq!(res, Ok(&mut 3));
     ///
-    /// for (key, val) in &map {
-    ///     println!("key: {} val: {}", key, val);
-    /// }
+    /// let res = map.entry("horseland").or_try_insert_with(|| "12a".parse::<u32>());
+    /// assert!(res.is_err());
+    /// assert!(!map.contains_key("horseland"));
     /// ```
-    pub fn iter_mut(&mut self) -> IterMut<K, V, S> {
-        IterMut {
-            inner: self.first(),
-            map: self,
-            len: self.len(),
+    pub fn or_try_insert_with<F, E>(self, f: F) -> Result<&'a mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(f()?)),
         }
     }
 
-    /// Clears the map, returning all key-value pairs as an iterator. Keeps the
-    /// allocated memory for reuse.
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut a = HashMap::new();
-    /// a.insert(1, "a");
-    /// a.insert(2, "b");
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
     ///
-    /// for (k, v) in a.drain().take(1) {
-    ///     assert!(k == 1 || k == 2);
-    ///     assert!(v == "a" || v == "b");
-    /// }
+    /// map.entry("poneyland")
+    ///    .and_modify(|e| { *e += 1 })
+    ///    .or_insert(42);
+    /// assert_eq!(map["poneyland"], 42);
     ///
-    /// assert!(a.is_empty());
+    /// map.entry("poneyland")
+    ///    .and_modify(|e| { *e += 1 })
+    ///    .or_insert(42);
+    /// assert_eq!(map["poneyland"], 43);
     /// ```
-    #[inline]
-    pub fn drain(&mut self) -> Drain<K, V, S> {
-        Drain {
-            entry: self.first(),
-            map: self,
+    pub fn and_modify<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
         }
     }
+}
 
-    fn erase(&mut self, entry: *mut InternalHashEntry<K, V>) -> Option<(K, V)> {
-        debug_assert!(!entry.is_null());
-        debug_assert!(!entry.node_ptr().avl_node_ptr().empty());
-        self.hash_table.hash_erase(entry.node_ptr());
-        let kv = key_deref_to_kv::<K, V>(entry.key());
-        self.entry_fastbin.del(entry as VoidPtr);
-        let res = unsafe { Some(ptr::read(kv)) };
-        self.kv_fastbin.del(kv as VoidPtr);
-        res
-    }
+/// A view into an occupied entry in a `HashMap`.
+/// It is part of the [`Entry`] enum.
+///
+/// [`Entry`]: enum.Entry.html
+pub struct OccupiedEntry<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    key: Option<K>,
+    hash_entry: *mut InternalHashEntry<K, V>,
+    hash_map_mut: &'a mut HashMap<K, V, S, C, INIT>,
 }
 
-impl<K, V, S> HashMap<K, V, S>
+/// A view into a vacant entry in a `HashMap`.
+/// It is part of the [`Entry`] enum.
+///
+/// [`Entry`]: enum.Entry.html
+pub struct VacantEntry<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    hash_value: HashUint,
+    key: K,
+    parent: AVLNodePtr,
+    link: *mut AVLNodePtr,
+    hash_map_mut: &'a mut HashMap<K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> OccupiedEntry<'a, K, V, S, C, INIT>
 where
     K: Ord + Hash,
     S: BuildHasher,
+    C: Comparator<K>,
 {
-    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    /// Take the ownership of the key and value from the map.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut letters = HashMap::new();
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
     ///
-    /// for ch in "a short treatise on fungi".chars() {
-    ///     let counter = letters.entry(ch).or_insert(0);
-    ///     *counter += 1;
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     // We delete the entry from the map.
+    ///     o.remove_entry();
     /// }
     ///
-    /// assert_eq!(letters[&'s'], 2);
-    /// assert_eq!(letters[&'t'], 3);
-    /// assert_eq!(letters[&'u'], 1);
-    /// assert_eq!(letters.get(&'y'), None);
+    /// assert_eq!(map.contains_key("poneyland"), false);
     /// ```
-    pub fn entry(&mut self, mut key: K) -> Entry<K, V, S> {
-        let hash_val = self.make_hash(&key);
-        let link = self.hash_table.get_hash_index(hash_val).avl_root_node_ptr();
-        let (duplicate, parent, link) =
-            unsafe { hash_table::find_duplicate_hash_node(link, &mut key as *mut K, hash_val) };
-        if duplicate.is_null() {
-            return Entry::Vacant(VacantEntry {
-                hash_value: hash_val,
-                key,
-                parent,
-                link,
-                hash_map_mut: self,
-            });
-        } else {
-            return Entry::Occupied(OccupiedEntry {
-                key: Some(key),
-                hash_entry: duplicate.deref_to_hash_entry(),
-                hash_map_mut: self,
-            });
-        };
-    }
-
-    #[inline]
-    fn make_hash<X: ?Sized>(&self, x: &X) -> HashUint
-    where
-        X: Hash,
-    {
-        hash_table::make_hash(&self.hash_builder, x)
+    pub fn remove_entry(self) -> (K, V) {
+        let hash_entry = self.hash_entry;
+        let res = self.hash_map_mut.erase(hash_entry).unwrap();
+        self.hash_map_mut.maybe_auto_shrink();
+        res
     }
 
-    /// Creates an empty `HashMap` which will use the given hash builder to hash
-    /// keys.
-    ///
-    /// The created map has the default initial capacity.
-    ///
-    /// Warning: `hash_builder` is normally randomly generated, and
-    /// is designed to allow HashMaps to be resistant to attacks that
-    /// cause many collisions and very poor performance. Setting it
-    /// manually using this function can expose a DoS attack vector.
+    /// Takes the value out of the entry, and returns it.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
-    /// use std::collections::hash_map::RandomState;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let s = RandomState::new();
-    /// let mut map = HashMap::with_hasher(s);
-    /// map.insert(1, 2);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    ///
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     assert_eq!(o.remove(), 12);
+    /// }
+    ///
+    /// assert_eq!(map.contains_key("poneyland"), false);
     /// ```
-    pub fn with_hasher(hash_builder: S) -> Self {
-        HashMap::with_capacity_and_hasher(0, hash_builder)
-    }
-
-    #[inline]
-    fn find<Q: ?Sized>(&self, q: &Q) -> *mut InternalHashEntry<K, V>
-    where
-        K: Borrow<Q>,
-        Q: Ord + Hash,
-    {
-        let node = self.hash_table.hash_find(self.make_hash(q), q);
-        if node.is_null() {
-            ptr::null_mut()
-        } else {
-            node.deref_to_hash_entry()
-        }
+    pub fn remove(self) -> V {
+        self.remove_entry().1
     }
+}
 
-    /// Returns a reference to the value corresponding to the key.
-    ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// `Hash` and `Ord` on the borrowed form *must* match those for
-    /// the key type.
+impl<'a, K, V, S, C, const INIT: usize> OccupiedEntry<'a, K, V, S, C, INIT> {
+    /// Gets a reference to the key in the entry.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert(1, "a");
-    /// assert_eq!(map.get(&1), Some(&"a"));
-    /// assert_eq!(map.get(&2), None);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
     /// ```
-    #[inline]
-    pub fn get<Q: ?Sized>(&self, q: &Q) -> Option<&V>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Ord,
-    {
-        let entry = self.find(q);
-        if entry.is_null() {
-            return None;
-        }
-        unsafe { Some(&(*entry.value())) }
+    pub fn key(&self) -> &K {
+        unsafe { &*self.hash_entry.key() }
     }
 
-    /// Returns a mutable reference to the value corresponding to the key.
-    ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// `Hash` and `Ord` on the borrowed form *must* match those for
-    /// the key type.
+    /// Returns the number of entries in the bucket holding this entry, itself included.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert(1, "a");
-    /// if let Some(x) = map.get_mut(&1) {
-    ///     *x = "b";
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.insert("poneyland", 12);
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     assert_eq!(o.bucket_len(), 1);
     /// }
-    /// assert_eq!(map[&1], "b");
     /// ```
-    #[inline]
-    pub fn get_mut<Q: ?Sized>(&mut self, q: &Q) -> Option<&mut V>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Ord,
-    {
-        let entry = self.find(q);
-        if entry.is_null() {
-            return None;
-        }
-        unsafe { Some(&mut (*entry.value())) }
+    pub fn bucket_len(&self) -> usize {
+        let hash_val = self.hash_entry.node_ptr().hash_val();
+        let index = self.hash_map_mut.hash_table.get_hash_index(hash_val);
+        index.avl_root_node().get_node_num() as usize
     }
 
-    #[inline]
-    fn rehash(&mut self, len: usize) {
-        self.hash_table.rehash(len);
+    /// Returns a key that was used for search.
+    ///
+    /// The key was retained for further use.
+    fn take_key(&mut self) -> Option<K> {
+        self.key.take()
     }
 
-    /// Reserves capacity for at least `additional` more elements to be inserted
-    /// in the `HashMap`. The collection may reserve more space to avoid
-    /// frequent reallocations.
+    /// Replaces the key in the hash map with the key used to create this entry.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    /// use std::rc::Rc;
     ///
-    /// let mut map: HashMap<&str, isize> = HashMap::new();
-    /// map.reserve(10);
+    /// let mut map: HashMap<Rc<String>, u32> = HashMap::new();
+    /// let mut known_strings: Vec<Rc<String>> = Vec::new();
+    ///
+    /// // Initialise known strings, run program, etc.
+    ///
+    /// reclaim_memory(&mut map, &known_strings);
+    ///
+    /// fn reclaim_memory(map: &mut HashMap<Rc<String>, u32>, known_strings: &[Rc<String>] ) {
+    ///     for s in known_strings {
+    ///         if let Entry::Occupied(entry) = map.entry(s.clone()) {
+    ///             // Replaces the entry's key with our version of it in `known_strings`.
+    ///             entry.replace_key();
+    ///         }
+    ///     }
+    /// }
     /// ```
-    pub fn reserve(&mut self, additional: usize) {
-        self.try_reserve(additional);
+    pub fn replace_key(self) -> K {
+        let old_key = unsafe { &mut *self.hash_entry.key() };
+        mem::replace(old_key, self.key.unwrap())
     }
 
-    pub fn try_reserve(&mut self, additional: usize) {
-        let remaining = self.capacity() - self.len();
-        if remaining < additional {
-            match self.len().checked_add(additional) {
-                None => panic!("capacity overflow"),
-                Some(min_cap) => self.rehash(min_cap),
-            };
-        }
-        // we use BST to restore concrete data, so there is no need to do
-        // any thing if capacity is equal to len
+    /// Gets a reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    ///
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     assert_eq!(o.get(), &12);
+    /// }
+    /// ```
+    pub fn get(&self) -> &V {
+        unsafe { &*self.hash_entry.value() }
     }
 
-    /// Returns true if the map contains a value for the specified key.
-    ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// `Hash` and `Ord` on the borrowed form *must* match those for
-    /// the key type.
+    /// Gets a mutable reference to the value in the entry.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert(1, "a");
-    /// assert_eq!(map.contains_key(&1), true);
-    /// assert_eq!(map.contains_key(&2), false);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    ///
+    /// assert_eq!(map["poneyland"], 12);
+    /// if let Entry::Occupied(mut o) = map.entry("poneyland") {
+    ///      *o.get_mut() += 10;
+    /// }
+    ///
+    /// assert_eq!(map["poneyland"], 22);
     /// ```
-    pub fn contains_key<Q: ?Sized>(&self, q: &Q) -> bool
-    where
-        K: Borrow<Q>,
-        Q: Hash + Ord,
-    {
-        !self.find(q).is_null()
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut *self.hash_entry.value() }
     }
 
-    /// Inserts a key-value pair into the map.
+    /// Converts the OccupiedEntry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the map itself.
     ///
-    /// If the map did not have this key present, [`None`] is returned.
+    /// # Examples
     ///
-    /// If the map did have this key present, update map with new (key, value)
-    /// and return the old one.
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    ///
+    /// assert_eq!(map["poneyland"], 12);
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     *o.into_mut() += 10;
+    /// }
+    ///
+    /// assert_eq!(map["poneyland"], 22);
+    /// ```
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut *self.hash_entry.value() }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut map = HashMap::new();
-    /// assert_eq!(map.insert(37, "a"), None);
-    /// assert_eq!(map.is_empty(), false);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// map.entry("poneyland").or_insert(12);
     ///
-    /// map.insert(37, "b");
-    /// assert_eq!(map.insert(37, "c"), Some((37, "b")));
-    /// assert_eq!(map[&37], "c");
+    /// if let Entry::Occupied(mut o) = map.entry("poneyland") {
+    ///     assert_eq!(o.insert(15), 12);
+    /// }
+    ///
+    /// assert_eq!(map["poneyland"], 15);
     /// ```
-    #[inline]
-    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
-        self.reserve(1);
-        let hash_value = self.make_hash(&key);
-        let kv_ptr = self.kv_alloc(key, value);
-        let new_entry = unsafe {
-            self.entry_alloc(
-                &mut (*kv_ptr).0 as *mut K,
-                &mut (*kv_ptr).1 as *mut V,
-                hash_value,
-            )
-        };
-        let old_entry = unsafe { hash_table_update(self.hash_table.as_mut(), new_entry) };
-        if old_entry.is_null() {
-            None
-        } else {
-            let old_kv_ptr = key_deref_to_kv(old_entry.key());
-            let res = unsafe { Some(ptr::read(old_kv_ptr)) };
-            self.kv_fastbin.del(old_kv_ptr as VoidPtr);
-            self.entry_fastbin.del(old_entry as VoidPtr);
-            res
-        }
+    pub fn insert(&mut self, mut value: V) -> V {
+        let old_value = self.get_mut();
+        mem::swap(&mut value, old_value);
+        value
     }
 
-    /// Removes a key from the map, returning the stored key and value if the
-    /// key was previously in the map.
+    /// Moves the value out of the entry, passes it to `f` and writes the result back in place,
+    /// returning a mutable reference to it. Unlike `get_mut` this allows transformations which
+    /// consume the old value.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// `Hash` and `Ord` on the borrowed form *must* match those for
-    /// the key type.
+    /// If `f` panics, the old value is already gone, so the entry is removed from the map and
+    /// its key is dropped.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut map = HashMap::new();
-    /// map.insert(1, "a");
-    /// assert_eq!(map.remove(&1), Some((1, "a")));
-    /// assert_eq!(map.remove(&1), None);
+    /// let mut map: HashMap<&str, Vec<u32>> = HashMap::new();
+    /// map.insert("poneyland", vec![3, 1, 2]);
+    ///
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     let v = o.replace_with(|mut v| {
+    ///         v.sort();
+    ///         v
+    ///     });
+    ///     v.push(4);
+    /// }
+    ///
+    /// assert_eq!(map["poneyland"], [1, 2, 3, 4]);
     /// ```
-    #[inline]
-    pub fn remove<Q: ?Sized>(&mut self, q: &Q) -> Option<(K, V)>
+    pub fn replace_with<F>(self, f: F) -> &'a mut V
     where
-        K: Borrow<Q>,
-        Q: Hash + Ord,
+        F: FnOnce(V) -> V,
     {
-        let entry = self.find(q);
-        if entry.is_null() {
-            return None;
+        let value_ptr = self.hash_entry.value();
+        let guard = ReplaceGuard {
+            map: self.hash_map_mut,
+            entry: self.hash_entry,
+        };
+        unsafe {
+            let value = f(ptr::read(value_ptr));
+            ptr::write(value_ptr, value);
         }
-        self.erase(entry)
+        mem::forget(guard);
+        unsafe { &mut *value_ptr }
     }
 
-    /// Creates an empty `HashMap` with the specified capacity, using `hash_builder`
-    /// to hash the keys.
+    /// Replaces the entry, returning the old key and value. The new key in the hash map will be
+    /// the key used to create this entry.
     ///
-    /// The hash map will be able to hold at least `capacity` elements without
-    /// reallocating. If `capacity` is 0, the hash map will not allocate.
+    /// # Examples
     ///
-    /// Warning: `hash_builder` is normally randomly generated, and
-    /// is designed to allow HashMaps to be resistant to attacks that
-    /// cause many collisions and very poor performance. Setting it
-    /// manually using this function can expose a DoS attack vector.
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    /// use std::rc::Rc;
+    ///
+    /// let mut map: HashMap<Rc<String>, u32> = HashMap::new();
+    /// map.insert(Rc::new("Stringthing".to_string()), 15);
+    ///
+    /// let my_key = Rc::new("Stringthing".to_string());
+    ///
+    /// if let Entry::Occupied(entry) = map.entry(my_key) {
+    ///     // Also replace the key with a handle to our other key.
+    ///     let (old_key, old_value): (Rc<String>, u32) = entry.replace_entry(16);
+    ///     assert_eq!(Rc::strong_count(&old_key), 1);
+    ///     assert!(old_value == 15);
+    /// }
+    ///
+    /// ```
+    pub fn replace_entry(self, value: V) -> (K, V) {
+        let (old_key, old_value) =
+            unsafe { (&mut *self.hash_entry.key(), &mut *self.hash_entry.value()) };
+        let old_key = mem::replace(old_key, self.key.unwrap());
+        let old_value = mem::replace(old_value, value);
+        (old_key, old_value)
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> VacantEntry<'a, K, V, S, C, INIT> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the `VacantEntry`.
     ///
     /// # Examples
     ///
     /// ```
     /// use hash_ord::hash_map::HashMap;
-    /// use std::collections::hash_map::RandomState;
     ///
-    /// let s = RandomState::new();
-    /// let mut map = HashMap::with_capacity_and_hasher(10, s);
-    /// map.insert(1, 2);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// assert_eq!(map.entry("poneyland").key(), &"poneyland");
     /// ```
-    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> HashMap<K, V, S> {
-        let mut hash_map = HashMap {
-            entry_fastbin: Fastbin::new(mem::size_of::<InternalHashEntry<K, V>>()),
-            kv_fastbin: Fastbin::new(mem::size_of::<(K, V)>()),
-            hash_table: hash_table::HashTable::new_with_box(),
-            hash_builder,
-        };
-        hash_map.reserve(capacity);
-        hash_map
+    pub fn key(&self) -> &K {
+        &self.key
     }
 
-    /// Shrinks the capacity of the map as much as possible. It will drop
-    /// down as much as possible while maintaining the internal rules
-    /// and possibly leaving some space in accordance with the resize policy.
+    /// Returns the number of entries already in the bucket the key would be inserted into.
+    /// Inserting may grow the index array first, which spreads the bucket.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hash_map::{HashMap, Entry};
     ///
-    /// let mut map: HashMap<isize, isize> = HashMap::with_capacity(100);
-    /// map.insert(1, 2);
-    /// map.insert(3, 4);
-    /// assert!(map.capacity() >= 100);
-    /// map.shrink_to_fit();
-    /// assert!(map.capacity() >= 2);
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// if let Entry::Vacant(v) = map.entry("poneyland") {
+    ///     assert_eq!(v.bucket_len(), 0);
+    /// }
     /// ```
-    pub fn shrink_to_fit(&mut self) {
-        let limit = hash_table::calc_limit(self.len());
-        let old_cap = self.capacity();
-        let new_cap = {
-            let mut tmp = old_cap / 2;
-            let mut res = old_cap;
-            while tmp >= limit {
-                res = tmp;
-                tmp /= 2;
-            }
-            res
-        };
-        if new_cap >= old_cap {
-            return;
-        }
-        let mut new_entry_fastbin = Fastbin::new(mem::size_of::<InternalHashEntry<K, V>>());
-        let mut new_kv_fastbin = Fastbin::new(mem::size_of::<(K, V)>());
-        let mut new_hash_table = hash_table::HashTable::new_with_box();
-        new_hash_table.rehash(self.len());
-        let mut new_kv_vec = Vec::with_capacity(self.len());
-        {
-            let mut destroy_callback = |(k, v): (K, V)| {
-                let kv_ptr = kv_alloc(&mut new_kv_fastbin, k, v);
-                new_kv_vec.push(kv_ptr);
-            };
-            loop {
-                let node = self.hash_table.pop_first_index();
-                if node.is_null() {
-                    break;
-                }
-                self.recurse_destroy(node, &mut destroy_callback);
-            }
-            debug_assert_eq!(self.hash_table.size(), 0);
-        }
-        for kv_ptr in new_kv_vec {
-            unsafe {
-                let key_ptr = &mut (*kv_ptr).0 as *mut K;
-                let value_ptr = &mut (*kv_ptr).1 as *mut V;
-                let entry = entry_alloc(
-                    &mut new_entry_fastbin,
-                    key_ptr,
-                    value_ptr,
-                    self.make_hash(&(*key_ptr)),
-                );
-                hash_table_update(&mut new_hash_table, entry);
-            }
-        }
-        self.kv_fastbin = new_kv_fastbin;
-        self.entry_fastbin = new_entry_fastbin;
-        self.hash_table = new_hash_table;
+    pub fn bucket_len(&self) -> usize {
+        let index = self.hash_map_mut.hash_table.get_hash_index(self.hash_value);
+        index.avl_root_node().get_node_num() as usize
+    }
+
+    /// Take ownership of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    ///
+    /// if let Entry::Vacant(v) = map.entry("poneyland") {
+    ///     v.into_key();
+    /// }
+    /// ```
+    pub fn into_key(self) -> K {
+        self.key
     }
 }
 
-impl<K, V> HashMap<K, V, RandomState>
+impl<'a, K, V, S, C, const INIT: usize> VacantEntry<'a, K, V, S, C, INIT>
 where
-    K: Hash + Ord,
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
 {
-    /// Creates an empty `HashMap`.
-    ///
-    /// The hash map is initially created with a capacity of 0, so it will not allocate until it
-    /// is first inserted into.
+    unsafe fn _internal_insert(
+        self,
+        value: V,
+    ) -> (*mut InternalHashEntry<K, V>, &'a mut HashMap<K, V, S, C, INIT>) {
+        let hash_value = self.hash_value;
+        let index = self.hash_map_mut.hash_table.get_hash_index(hash_value);
+        let key = self.key;
+        let kv_ptr = self.hash_map_mut.kv_alloc(key, value);
+        let new_entry = self.hash_map_mut.entry_alloc(
+            &mut (*kv_ptr).0 as *mut K,
+            &mut (*kv_ptr).1 as *mut V,
+            hash_value,
+        );
+        let new_node = new_entry.node_ptr();
+        if index.avl_root_node().is_null() {
+            self.hash_map_mut
+                .hash_table
+                .head_ptr()
+                .list_add_tail(index.node_ptr());
+        }
+        avl_node::link_node(new_node.avl_node_ptr(), self.parent, self.link);
+        avl_node::node_post_insert(new_node.avl_node_ptr(), index.avl_root_ptr());
+        self.hash_map_mut.hash_table.inc_count(1);
+        let new_len = self.hash_map_mut.len();
+        self.hash_map_mut.auto_rehash(new_len);
+        (new_entry, self.hash_map_mut)
+    }
+
+    /// Sets the value of the entry with the VacantEntry's key,
+    /// and returns a mutable reference to it.
     ///
     /// # Examples
     ///
     /// ```
-    /// use hash_ord::hash_map::HashMap;
-    /// let mut map: HashMap<&str, isize> = HashMap::new();
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// if let Entry::Vacant(o) = map.entry("poneyland") {
+    ///     o.insert(37);
+    /// }
+    /// assert_eq!(map["poneyland"], 37);
     /// ```
-    #[inline]
-    pub fn new() -> HashMap<K, V, RandomState> {
-        Default::default()
+    pub fn insert(self, value: V) -> &'a mut V {
+        unsafe { &mut *self._internal_insert(value).0.value() }
     }
 
-    #[inline]
-    pub fn with_capacity(capacity: usize) -> HashMap<K, V, RandomState> {
-        let mut hash_map = HashMap::<K, V, RandomState>::default();
-        hash_map.reserve(capacity);
-        hash_map
+    /// Sets the value of the entry with the VacantEntry's key,
+    /// and returns an `OccupiedEntry` of it.
+    ///
+    /// The returned entry retains no search key, so calling `replace_key` or `replace_entry`
+    /// on it panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, Entry};
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    /// if let Entry::Vacant(v) = map.entry("poneyland") {
+    ///     let o = v.insert_entry(37);
+    ///     assert_eq!(o.get(), &37);
+    /// }
+    /// assert_eq!(map["poneyland"], 37);
+    /// ```
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S, C, INIT> {
+        let (hash_entry, hash_map_mut) = unsafe { self._internal_insert(value) };
+        OccupiedEntry {
+            key: None,
+            hash_entry,
+            hash_map_mut,
+        }
     }
 }
 
-impl<K, V, S> Default for HashMap<K, V, S>
+/// A builder for looking up an entry of a `HashMap` by a precomputed hash value.
+///
+/// This `struct` is created by the [`raw_entry`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`raw_entry`]: struct.HashMap.html#method.raw_entry
+/// [`HashMap`]: struct.HashMap.html
+pub struct RawEntryBuilder<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    map: &'a HashMap<K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> RawEntryBuilder<'a, K, V, S, C, INIT>
 where
     K: Ord + Hash,
-    S: BuildHasher + Default,
+    S: BuildHasher,
+    C: Comparator<K>,
 {
-    /// Creates an empty `HashMap<K, V, S>`, with the `Default` value for the hasher.
-    fn default() -> HashMap<K, V, S> {
-        HashMap::with_hasher(Default::default())
+    /// Looks up an entry by its key, like [`get`] but returning the stored key too.
+    ///
+    /// [`get`]: struct.HashMap.html#method.get
+    pub fn from_key<Q: ?Sized>(self, k: &Q) -> Option<(&'a K, &'a V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let hash = self.map.make_hash(k) as u64;
+        self.from_key_hashed_nocheck(hash, k)
+    }
+
+    /// Looks up an entry by its key and the hash value of the key, which is not checked.
+    ///
+    /// The hash must be computed by the [`hasher`] of the map, otherwise the entry won't
+    /// be found.
+    ///
+    /// [`hasher`]: struct.HashMap.html#method.hasher
+    pub fn from_key_hashed_nocheck<Q: ?Sized>(self, hash: u64, k: &Q) -> Option<(&'a K, &'a V)>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let node = self
+            .map
+            .hash_table
+            .hash_find(hash as HashUint, k, &self.map.comparator);
+        if node.is_null() {
+            return None;
+        }
+        let entry: *mut InternalHashEntry<K, V> = node.deref_to_hash_entry();
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
     }
+
+    /// Looks up an entry by a hash value and a predicate over the keys sharing it.
+    ///
+    /// Keys with the same hash value are visited in the order of the comparator, and the
+    /// first one accepted by `is_match` is returned.
+    pub fn from_hash<F>(self, hash: u64, mut is_match: F) -> Option<(&'a K, &'a V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let hash = hash as HashUint;
+        let mut node = self.map.hash_table.hash_find_first(hash);
+        while !node.is_null() && node.hash_val() == hash {
+            let entry: *mut InternalHashEntry<K, V> = node.deref_to_hash_entry();
+            unsafe {
+                if is_match(&(*entry.key())) {
+                    return Some((&(*entry.key()), &(*entry.value())));
+                }
+            }
+            let next = node.avl_node_ptr().next();
+            if next.is_null() {
+                break;
+            }
+            node = next.avl_hash_deref_mut::<K>();
+        }
+        None
+    }
+}
+
+/// Tears down an AVL detached from its HashIndex, dropping whatever is left of it when dropped.
+struct TearGuard<'a, K, V, S, C, const INIT: usize>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    map: &'a mut HashMap<K, V, S, C, INIT>,
+    root: AVLRoot,
+    next: AVLNodePtr,
 }
 
-impl<K, V, S> Drop for HashMap<K, V, S> {
+impl<'a, K, V, S, C, const INIT: usize> TearGuard<'a, K, V, S, C, INIT> {
+    /// Detach the next node in post-order from the map and move its pair out.
     #[inline]
+    fn pop(&mut self) -> Option<(K, V)> {
+        let node = unsafe { avl_node::avl_node_tear(&mut self.root, &mut self.next) };
+        if node.is_null() {
+            return None;
+        }
+        let hash_node = node.avl_hash_deref_mut::<K>();
+        let entry: *mut InternalHashEntry<K, V> = hash_node.deref_to_hash_entry();
+        let kv_ptr = key_deref_to_kv::<K, V>(hash_node.key_ptr());
+        let kv = unsafe { ptr::read(kv_ptr) };
+        self.map.entry_fastbin.del(entry as VoidPtr);
+        self.map.kv_fastbin.del(kv_ptr as VoidPtr);
+        self.map.hash_table.dec_count(1);
+        Some(kv)
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Drop for TearGuard<'a, K, V, S, C, INIT> {
     fn drop(&mut self) {
-        self.clear();
+        while let Some(kv) = self.pop() {
+            drop(kv);
+        }
     }
 }
 
-/// A draining iterator over the entries of a `HashMap`.
-///
-/// This `struct` is created by the [`drain`] method on [`HashMap`]. See its
-/// documentation for more.
-///
-/// [`drain`]: struct.HashMap.html#method.drain
-/// [`HashMap`]: struct.HashMap.html
-pub struct Drain<'a, K, V, S>
+/// Unlinks an entry whose value has been moved out, dropping only its key when dropped.
+struct ReplaceGuard<'a, K, V, S, C, const INIT: usize>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
+    map: &'a mut HashMap<K, V, S, C, INIT>,
     entry: *mut InternalHashEntry<K, V>,
-    map: &'a mut HashMap<K, V, S>,
 }
 
-impl<'a, K, V, S> Drop for Drain<'a, K, V, S>
+impl<'a, K, V, S, C, const INIT: usize> Drop for ReplaceGuard<'a, K, V, S, C, INIT> {
+    fn drop(&mut self) {
+        let entry = self.entry;
+        self.map.hash_table.hash_erase(entry.node_ptr());
+        if self.map.insertion_order.is_some() {
+            entry.order_node().list_del();
+        }
+        let kv = key_deref_to_kv::<K, V>(entry.key());
+        let key = unsafe { ptr::read(&(*kv).0) };
+        self.map.entry_fastbin.del(entry as VoidPtr);
+        self.map.kv_fastbin.del(kv as VoidPtr);
+        drop(key);
+    }
+}
+
+/// Destroys every bucket left in a map when dropped.
+struct ClearGuard<'a, K, V, S, C, const INIT: usize>
 where
     K: 'a,
     V: 'a,
     S: 'a,
+    C: 'a,
 {
+    map: &'a mut HashMap<K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Drop for ClearGuard<'a, K, V, S, C, INIT> {
     fn drop(&mut self) {
-        for _ in self {}
+        self.map.destroy_all();
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> HashMap<K, V, S, C, INIT> {
+    /// Destroy every entry of an AVL detached from its HashIndex.
+    ///
+    /// The tree is torn down in post-order with `avl_node_tear`, so children are released before
+    /// their parent and no recursion is needed, however deep the bucket is.
+    ///
+    /// Every pair is unlinked, its slots are freed and the count is decreased before it is
+    /// handed to `f`, so a panicking `f` (or a panicking `Drop` of `K` or `V`) never leaves a
+    /// freed slot reachable. If it does panic, the rest of the tree is still destroyed while
+    /// unwinding, like `ptr::drop_in_place` does for slices.
+    fn destroy_tree<F>(&mut self, node: AVLNodePtr, f: &mut F)
+    where
+        F: FnMut((K, V)),
+    {
+        let mut guard = TearGuard {
+            map: self,
+            root: AVLRoot { node },
+            next: ptr::null_mut(),
+        };
+        while let Some(kv) = guard.pop() {
+            (*f)(kv);
+        }
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated memory
+    /// for reuse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a = HashMap::new();
+    /// a.insert(1, "a");
+    /// a.clear();
+    /// assert!(a.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        // reset first: the list must not reach freed entries if a destructor panics
+        if let Some(ref mut head) = self.insertion_order {
+            (head.as_mut() as ListHeadPtr).list_init();
+        }
+        // if a destructor panics, the guard destroys the remaining buckets while unwinding
+        let guard = ClearGuard { map: self };
+        guard.map.destroy_all();
+        debug_assert_eq!(guard.map.hash_table.size(), 0);
+    }
+
+    /// Clears the map like [`clear`], but catches the panics of destructors: every entry is
+    /// dropped, even after some destructors panicked, and the panics are returned in `Err`
+    /// in the order they happened. The map is always empty afterwards.
+    ///
+    /// [`clear`]: #method.clear
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, String> = (0..10).map(|i| (i, i.to_string())).collect();
+    /// assert!(map.clear_catching().is_ok());
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn clear_catching(&mut self) -> Result<(), Vec<Box<dyn Any + Send>>> {
+        let mut panics = Vec::new();
+        loop {
+            let entry = self.first();
+            if entry.is_null() {
+                break;
+            }
+            // unlinked before dropping, so a panic never leaves a dropped entry in the map
+            let kv = self.erase(entry);
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(move || drop(kv))) {
+                panics.push(payload);
+            }
+        }
+        if panics.is_empty() {
+            Ok(())
+        } else {
+            Err(panics)
+        }
+    }
+
+    /// Replaces the map with `other`, returning the old map. The same as `mem::replace`, as a
+    /// method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a = HashMap::new();
+    /// a.insert(1, "a");
+    /// let old = a.replace(HashMap::new());
+    /// assert!(a.is_empty());
+    /// assert_eq!(old[&1], "a");
+    /// ```
+    #[inline]
+    pub fn replace(&mut self, other: HashMap<K, V, S, C, INIT>) -> HashMap<K, V, S, C, INIT> {
+        mem::replace(self, other)
+    }
+
+    fn destroy_all(&mut self) {
+        let mut destroy_callback = |_| {};
+        loop {
+            let node = self.hash_table.pop_first_index();
+            if node.is_null() {
+                break;
+            }
+            self.destroy_tree(node, &mut destroy_callback);
+        }
+    }
+
+    /// Returns the number of elements the map can hold without reallocating.
+    ///
+    /// This is the entry headroom, not the number of HashIndex slots: the index array is
+    /// resized once the load factor `len * 6 / 4` exceeds its size, so a map with `n` slots
+    /// can hold `(4 * n + 3) / 6` elements. `len() <= capacity()` always holds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<isize, isize> = HashMap::with_capacity(100);
+    /// assert!(map.capacity() >= 100);
+    /// ```
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.hash_table.capacity()
+    }
+
+    /// Returns the number of HashIndex slots in the index array, always a power of two.
+    /// Unlike [`capacity`], this is the raw size of the index, not the number of elements
+    /// the map can hold before growing.
+    ///
+    /// [`capacity`]: #method.capacity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// assert!(map.index_size().is_power_of_two());
+    /// assert_eq!(map.load(), 100.0 / map.index_size() as f64);
+    /// ```
+    #[inline]
+    pub fn index_size(&self) -> usize {
+        self.hash_table.index_size()
+    }
+
+    /// Returns the load of the map: the number of elements per HashIndex slot. The index
+    /// array grows once it would exceed `2 / 3`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..4).map(|i| (i, i)).collect();
+    /// assert_eq!(map.load(), 0.5);
+    /// ```
+    #[inline]
+    pub fn load(&self) -> f64 {
+        self.len() as f64 / self.hash_table.index_size() as f64
+    }
+
+    /// Enables or disables auto shrinking, which is off by default.
+    ///
+    /// The index array only grows by itself, so a map which had many elements keeps its
+    /// memory after they are removed. With auto shrinking, [`remove`] (and removing through an
+    /// occupied entry) calls [`shrink_to_fit`] once the load drops below `1 / 8`. Bulk removals
+    /// like [`retain`] and [`drain`] never shrink, call [`shrink_to_fit`] after them instead.
+    ///
+    /// [`remove`]: #method.remove
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    /// [`retain`]: #method.retain
+    /// [`drain`]: #method.drain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    /// map.set_auto_shrink(true);
+    /// for i in 0..990 {
+    ///     map.remove(&i);
+    /// }
+    /// assert!(map.capacity() < 100);
+    /// ```
+    #[inline]
+    pub fn set_auto_shrink(&mut self, enabled: bool) {
+        self.auto_shrink = enabled;
+    }
+
+    /// Returns how the index array is sized now and when and how it will grow next, so a
+    /// resize can be scheduled ahead of time with [`reserve`].
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, 1);
+    /// let info = map.resize_info();
+    /// assert_eq!(info.entries, 1);
+    /// assert_eq!(info.next_resize_at, map.capacity() + 1);
+    /// assert!(info.would_grow_to > info.index_size);
+    /// ```
+    pub fn resize_info(&self) -> ResizeInfo {
+        let next_resize_at = self.capacity() + 1;
+        ResizeInfo {
+            index_size: self.hash_table.index_size(),
+            entries: self.len(),
+            next_resize_at,
+            would_grow_to: self.hash_table.grown_index_size(next_resize_at),
+        }
+    }
+
+    /// Returns the number of bytes used by the map: the map itself, its hash table, the index
+    /// array if it's on the heap, and every page of the fastbins holding entries and pairs,
+    /// including free slots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// let empty = map.memory_usage();
+    /// for i in 0..1000u64 {
+    ///     map.insert(i, i);
+    /// }
+    /// assert!(map.memory_usage() >= empty + 1000 * 16);
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        let order_head = if self.insertion_order.is_some() {
+            mem::size_of::<ListHead>()
+        } else {
+            0
+        };
+        mem::size_of::<Self>() + mem::size_of::<HashTable<K, V, INIT>>()
+            + self.hash_table.heap_size() + order_head
+            + self.entry_fastbin.allocated_bytes() + self.kv_fastbin.allocated_bytes()
+    }
+
+    /// Turns the map into a [`FrozenHashMap`], a read-only view which can be shared across
+    /// threads without a lock.
+    ///
+    /// [`FrozenHashMap`]: struct.FrozenHashMap.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i * i)).collect();
+    /// let frozen = Arc::new(map.freeze());
+    /// let other = frozen.clone();
+    /// let handle = thread::spawn(move || other.get(&7).cloned());
+    /// assert_eq!(handle.join().unwrap(), Some(49));
+    /// assert_eq!(frozen.len(), 100);
+    /// ```
+    #[inline]
+    pub fn freeze(self) -> FrozenHashMap<K, V, S, C, INIT> {
+        FrozenHashMap { map: self }
+    }
+
+    /// Estimates the number of bytes [`memory_usage`] reports for a map holding `len`
+    /// entries, so the footprint can be predicted before building the map.
+    ///
+    /// Unlike open addressing, the memory is not proportional to the capacity:
+    ///
+    /// ```text
+    /// index_size * size_of::<HashIndex>() + len * (size_of::<InternalHashEntry>() + size_of::<(K, V)>())
+    /// ```
+    ///
+    /// plus the fixed size of the map and its hash table. `index_size` is the smallest
+    /// `INIT * 2^n` holding `len * 6 / 4`, and the index array only counts once it outgrows the
+    /// inline one. The estimate leaves out the unused tail of the last fastbin pages, which
+    /// is at most 64KiB per fastbin, and the insertion list of `with_insertion_order`.
+    ///
+    /// [`memory_usage`]: #method.memory_usage
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let small = <HashMap<u64, u64>>::estimate_memory_for(10);
+    /// let large = <HashMap<u64, u64>>::estimate_memory_for(1_000_000);
+    /// assert!(large > 1_000_000 * 16);
+    /// assert!(small < large);
+    /// ```
+    pub fn estimate_memory_for(len: usize) -> usize {
+        let limit = hash_table::calc_limit(len);
+        let mut index_size = INIT;
+        while index_size < limit {
+            index_size = match index_size.checked_mul(2) {
+                Some(x) => x,
+                None => panic!("capacity overflow"),
+            };
+        }
+        let index_bytes = if index_size > INIT {
+            index_size * mem::size_of::<HashIndex>()
+        } else {
+            0
+        };
+        let slot_bytes = fastbin::slot_size(entry_size::<K, V>(false))
+            + fastbin::slot_size(mem::size_of::<(K, V)>());
+        mem::size_of::<Self>() + mem::size_of::<HashTable<K, V, INIT>>() + index_bytes
+            + len.saturating_mul(slot_bytes)
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let hasher = RandomState::new();
+    /// let map: HashMap<i32, i32, _> = HashMap::with_hasher(hasher);
+    /// let hasher: &RandomState = map.hasher();
+    /// ```
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns false if no key of the map has the hash value `hash`, without comparing any
+    /// key. A `true` may be a false positive when a different key shares the hash value, so
+    /// this is a cheap "definitely absent" check in front of a full lookup.
+    ///
+    /// The hash value must be computed by the [`hasher`] of the map.
+    ///
+    /// [`hasher`]: #method.hasher
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 100);
+    ///
+    /// let hash_of = |k: &str| {
+    ///     let mut state = map.hasher().build_hasher();
+    ///     k.hash(&mut state);
+    ///     state.finish()
+    /// };
+    /// assert!(map.may_contain_hash(hash_of("a")));
+    /// assert!(!map.may_contain_hash(hash_of("b")));
+    /// ```
+    #[inline]
+    pub fn may_contain_hash(&self, hash: u64) -> bool {
+        self.hash_table.contains_hash(hash as HashUint)
+    }
+
+    /// An iterator visiting the entries whose stored hash value is `hash`, in the order of the
+    /// comparator. Their keys differ, but collide completely, not only on the bucket.
+    ///
+    /// The hash value must be computed by the [`hasher`] of the map.
+    ///
+    /// [`hasher`]: #method.hasher
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 100);
+    /// map.insert("b", 200);
+    ///
+    /// let mut state = map.hasher().build_hasher();
+    /// "a".hash(&mut state);
+    /// let hash = state.finish();
+    /// assert!(map.entries_with_hash(hash).eq(Some((&"a", &100))));
+    /// ```
+    pub fn entries_with_hash(&self, hash: u64) -> EntriesWithHash<K, V> {
+        let hash = hash as HashUint;
+        EntriesWithHash {
+            node: self.hash_table.hash_find_first(hash),
+            hash,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Creates a raw immutable entry builder, which looks up entries by a precomputed
+    /// hash value instead of hashing the key again.
+    ///
+    /// The hash value must be computed by the [`hasher`] of the map.
+    ///
+    /// [`hasher`]: #method.hasher
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 100);
+    ///
+    /// let mut state = map.hasher().build_hasher();
+    /// "a".hash(&mut state);
+    /// let hash = state.finish();
+    ///
+    /// assert_eq!(map.raw_entry().from_key(&"a"), Some((&"a", &100)));
+    /// assert_eq!(map.raw_entry().from_key_hashed_nocheck(hash, &"a"), Some((&"a", &100)));
+    /// assert_eq!(map.raw_entry().from_hash(hash, |k| *k == "a"), Some((&"a", &100)));
+    /// ```
+    #[inline]
+    pub fn raw_entry(&self) -> RawEntryBuilder<K, V, S, C, INIT> {
+        RawEntryBuilder { map: self }
+    }
+
+    /// Return true if the internal bookkeeping of the map is consistent: see the invariants
+    /// listed on the hash table, plus the insertion order list holding every entry once when
+    /// it is tracked. Meant for tests and fuzzing, it takes O(n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// assert!(map.check_consistency());
+    /// ```
+    pub fn check_consistency(&self) -> bool {
+        if !self.hash_table.check_consistency() {
+            return false;
+        }
+        match self.insertion_order {
+            Some(ref head) => {
+                let head = head.as_ref() as *const ListHead as ListHeadPtr;
+                let mut cnt = 0;
+                let mut node = head.next();
+                while node != head {
+                    cnt += 1;
+                    if cnt > self.len() {
+                        return false;
+                    }
+                    node = node.next();
+                }
+                cnt == self.len()
+            }
+            None => true,
+        }
+    }
+
+    /// Return true if no two live entries share memory: every HashNode, key and value has its
+    /// own address, every HashNode leads back to the entry holding it, so that
+    /// `container_of!` finds the right entry, and the key and value of an entry are the two
+    /// halves of one stored pair. Meant for tests and fuzzing, it takes O(n log n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// assert!(map.check_no_aliasing());
+    /// ```
+    pub fn check_no_aliasing(&self) -> bool {
+        if !self.hash_table.check_no_aliasing() {
+            return false;
+        }
+        let pair_offset = {
+            let pair = mem::MaybeUninit::<(K, V)>::uninit();
+            let pair = pair.as_ptr();
+            unsafe { ptr::addr_of!((*pair).1) as isize - ptr::addr_of!((*pair).0) as isize }
+        };
+        let mut values = Vec::with_capacity(self.len());
+        let mut entry = self.first();
+        while !entry.is_null() {
+            if entry.node_ptr().deref_to_hash_entry() != entry
+                || entry.value() as isize - entry.key() as isize != pair_offset
+            {
+                return false;
+            }
+            values.push(entry.value() as usize);
+            entry = self.next(entry);
+        }
+        values.sort_unstable();
+        values.windows(2).all(|w| w[0] != w[1])
+    }
+
+    /// Whether the HashIndex array still lives inline in the table.
+    #[inline]
+    fn is_index_inline(&self) -> bool {
+        self.hash_table.is_index_inline()
+    }
+
+    /// Returns true if the map was created by [`with_insertion_order`] and tracks the
+    /// order in which keys were inserted.
+    ///
+    /// [`with_insertion_order`]: #method.with_insertion_order
+    #[inline]
+    pub fn tracks_insertion_order(&self) -> bool {
+        self.insertion_order.is_some()
+    }
+
+    /// An iterator visiting all key-value pairs in the order their keys were first inserted.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// Inserting an existing key replaces the pair in place and keeps its position, while
+    /// removing a key and inserting it again moves it to the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map was not created by [`with_insertion_order`].
+    ///
+    /// [`with_insertion_order`]: #method.with_insertion_order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::with_insertion_order();
+    /// map.insert("c", 3);
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 4);
+    ///
+    /// let pairs: Vec<_> = map.iter_insertion_order().map(|(k, v)| (*k, *v)).collect();
+    /// assert_eq!(pairs, [("c", 4), ("a", 1), ("b", 2)]);
+    /// ```
+    pub fn iter_insertion_order(&self) -> InsertionOrderIter<K, V> {
+        let head = match self.insertion_order {
+            Some(ref head) => head.as_ref() as *const ListHead as ListHeadPtr,
+            None => panic!("HashMap doesn't track insertion order"),
+        };
+        InsertionOrderIter {
+            front: head.next(),
+            back: head.prev(),
+            len: self.len(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Returns the maximum node count under a simgle HashIndex
+    #[inline]
+    pub fn get_max_node_of_single_index(&self) -> i32 {
+        self.hash_table.get_max_node_of_single_index()
+    }
+
+    /// Returns the position in the index array of the bucket holding the most entries, along
+    /// with the keys in it, ordered by hash value, then by key. Returns `None` if the map is
+    /// empty. When several buckets are the largest, the first one in iteration order is picked.
+    /// Meant for finding out which keys collide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let (index, keys) = map.worst_bucket().unwrap();
+    /// assert!(index < map.resize_info().index_size);
+    /// assert_eq!(keys.len() as i32, map.get_max_node_of_single_index());
+    /// ```
+    pub fn worst_bucket(&self) -> Option<(usize, Vec<&K>)> {
+        let mut worst: Option<BucketRef<K, V>> = None;
+        let mut worst_num = 0;
+        for bucket in self.buckets() {
+            let num = bucket.root.get_node_num();
+            if num > worst_num {
+                worst_num = num;
+                worst = Some(bucket);
+            }
+        }
+        worst.map(|bucket| (bucket.index(), bucket.iter().map(|(k, _)| k).collect()))
+    }
+
+    /// Returns how many entries the buckets hold: index `i` is the number of HashIndexes
+    /// holding exactly `i` entries, index 0 counting the empty ones. The sum is the index
+    /// size. Handy for exporting the load distribution as a metric.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let histogram = map.bucket_len_histogram();
+    /// let entries: usize = histogram.iter().enumerate().map(|(len, n)| len * n).sum();
+    /// assert_eq!(entries, 100);
+    /// ```
+    pub fn bucket_len_histogram(&self) -> Vec<usize> {
+        self.hash_table.bucket_len_histogram()
+    }
+
+    /// Returns how uneven the buckets are: the number of entries in the largest bucket
+    /// divided by the mean number of entries of the non-empty buckets. `1.0` means every
+    /// non-empty bucket holds as many entries, which is also returned for an empty map.
+    ///
+    /// Which bucket an entry lands in only depends on its stored hash value and the index
+    /// size, so a high skew is only cured by a different hasher, see [`with_new_hasher`].
+    ///
+    /// [`with_new_hasher`]: #method.with_new_hasher
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    /// assert!(map.bucket_skew() < 4.0);
+    /// ```
+    pub fn bucket_skew(&self) -> f64 {
+        let histogram = self.bucket_len_histogram();
+        let non_empty = self.hash_table.index_size() - histogram[0];
+        if non_empty == 0 {
+            return 1.0;
+        }
+        let max = histogram.len() - 1;
+        max as f64 * non_empty as f64 / self.len() as f64
+    }
+
+    /// Returns the first key-value pair in iteration order. Finding the first bucket is O(1), then
+    /// the walk down its AVL is O(log n) in the size of that bucket.
+    ///
+    /// Notice that "first" follows the hash layout used by [`iter`], not the key order
+    /// as in a `BTreeMap`.
+    ///
+    /// [`iter`]: #method.iter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert_eq!(map.first_key_value(), None);
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// assert_eq!(map.first_key_value(), map.iter().next());
+    /// ```
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let entry = self.first();
+        if entry.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
+    }
+
+    /// Returns the last key-value pair in iteration order. Finding the last bucket is O(1), then
+    /// the walk down its AVL is O(log n) in the size of that bucket.
+    ///
+    /// Like [`first_key_value`], "last" follows the hash layout, not the key order.
+    ///
+    /// [`first_key_value`]: #method.first_key_value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert_eq!(map.last_key_value(), None);
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// assert_eq!(map.last_key_value(), map.iter().last());
+    /// ```
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let entry = self.last();
+        if entry.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*entry.key()), &(*entry.value()))) }
+    }
+
+    #[inline]
+    fn first(&self) -> *mut InternalHashEntry<K, V> {
+        let hash_node = self.hash_table.node_first();
+        if hash_node.is_null() {
+            return ptr::null_mut();
+        }
+        hash_node.deref_to_hash_entry()
+    }
+
+    #[inline]
+    fn last(&self) -> *mut InternalHashEntry<K, V> {
+        let hash_node = self.hash_table.node_last();
+        if hash_node.is_null() {
+            return ptr::null_mut();
+        }
+        hash_node.deref_to_hash_entry()
+    }
+
+    #[inline]
+    fn next(&self, entry: *mut InternalHashEntry<K, V>) -> *mut InternalHashEntry<K, V> {
+        let hash_node = self.hash_table.node_next(entry.node_ptr());
+        if hash_node.is_null() {
+            return ptr::null_mut();
+        }
+        hash_node.deref_to_hash_entry()
+    }
+
+    #[inline]
+    fn prev(&self, entry: *mut InternalHashEntry<K, V>) -> *mut InternalHashEntry<K, V> {
+        let hash_node = self.hash_table.node_prev(entry.node_ptr());
+        if hash_node.is_null() {
+            return ptr::null_mut();
+        }
+        hash_node.deref_to_hash_entry()
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a = HashMap::new();
+    /// assert_eq!(a.len(), 0);
+    /// a.insert(1, "a");
+    /// assert_eq!(a.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hash_table.size()
+    }
+
+    /// Returns true if the map contains no element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a = HashMap::new();
+    /// assert!(a.is_empty());
+    /// a.insert(1, "a");
+    /// assert!(!a.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn entry_alloc(
+        &mut self,
+        key: *mut K,
+        value: *mut V,
+        hash_value: HashUint,
+    ) -> *mut InternalHashEntry<K, V> {
+        let entry = entry_alloc(&mut self.entry_fastbin, key, value, hash_value);
+        self.entry_track_order(entry);
+        entry
+    }
+
+    #[inline]
+    fn entry_track_order(&mut self, entry: *mut InternalHashEntry<K, V>) {
+        if let Some(ref mut head) = self.insertion_order {
+            (head.as_mut() as ListHeadPtr).list_add_tail(entry.order_node());
+        }
+    }
+
+    #[inline]
+    fn kv_alloc(&mut self, key: K, value: V) -> *mut (K, V) {
+        kv_alloc(&mut self.kv_fastbin, key, value)
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    /// The iterator element type is `&'a K`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// for key in map.keys() {
+    ///     println!("{}", key);
+    /// }
+    /// ```
+    pub fn keys(&self) -> Keys<K, V, S, C, INIT> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    /// The iterator element type is `&'a V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// for val in map.values() {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn values(&self) -> Values<K, V, S, C, INIT> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values mutably in arbitrary order.
+    /// The iterator element type is `&'a mut V`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    ///
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// for val in map.values_mut() {
+    ///     *val = *val + 10;
+    /// }
+    ///
+    /// for val in map.values() {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn values_mut(&mut self) -> ValuesMut<K, V, S, C, INIT> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// for (key, val) in map.iter() {
+    ///     println!("key: {} val: {}", key, val);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Iter<K, V, S, C, INIT> {
+        Iter {
+            inner: self.first(),
+            map: self,
+            len: self.len(),
+            mod_count: self.hash_table.mod_count(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in the same order as [`iter`], together with
+    /// the [`NodeRole`] of each entry in the AVL of its bucket. Meant for debug dumps of the
+    /// tree shapes.
+    /// The iterator element type is `(&'a K, &'a V, NodeRole)`.
+    ///
+    /// [`iter`]: #method.iter
+    /// [`NodeRole`]: enum.NodeRole.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, NodeRole};
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let roots = map.iter_nodes().filter(|&(_, _, role)| role == NodeRole::Root).count();
+    /// assert_eq!(roots, map.buckets().count());
+    /// ```
+    pub fn iter_nodes(&self) -> IterNodes<K, V, S, C, INIT> {
+        IterNodes { inner: self.iter() }
+    }
+
+    /// An iterator visiting the non-empty buckets of the map, each of which is an iterable
+    /// [`BucketRef`]. Chaining every bucket yields the same entries as [`iter`], which makes
+    /// the buckets handy units for sharding a read-only scan across threads.
+    ///
+    /// [`BucketRef`]: struct.BucketRef.html
+    /// [`iter`]: #method.iter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let mut sum = 0;
+    /// for bucket in map.buckets() {
+    ///     sum += bucket.iter().map(|(_, v)| *v).sum::<i32>();
+    /// }
+    /// assert_eq!(sum, (0..100).sum());
+    /// ```
+    pub fn buckets(&self) -> Buckets<K, V, S, C, INIT> {
+        Buckets {
+            index: self.hash_table.index_first(),
+            map: self,
+            mod_count: self.hash_table.mod_count(),
+        }
+    }
+
+    /// The positions in the index array of the non-empty buckets, in the order they are
+    /// linked, which is the order they became non-empty. Unless the map keeps insertion order,
+    /// [`iter`] visits the entries bucket by bucket in exactly this order.
+    ///
+    /// [`iter`]: #method.iter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert!(map.bucket_order().is_empty());
+    /// map.insert(1, "a");
+    /// assert_eq!(map.bucket_order().len(), 1);
+    /// assert!(map.bucket_order()[0] < map.resize_info().index_size);
+    /// ```
+    pub fn bucket_order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut index = self.hash_table.index_first();
+        while !index.is_null() {
+            order.push(self.hash_table.index_position(index));
+            index = self.hash_table.index_next(index);
+        }
+        order
+    }
+
+    /// An iterator visiting the key-value pairs stored in the buckets at positions
+    /// `start..end` of the index array, bucket by bucket. Positions past [`index_size`] are
+    /// ignored, so consecutive ranges covering `0..index_size()` visit every entry once,
+    /// which lets a large map be snapshotted in chunks.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// [`index_size`]: #method.index_size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let chunk = map.index_size() / 4;
+    /// let mut count = 0;
+    /// for start in (0..map.index_size()).step_by(chunk) {
+    ///     count += map.iter_index_range(start, start + chunk).count();
+    /// }
+    /// assert_eq!(count, 100);
+    /// ```
+    pub fn iter_index_range(&self, start: usize, end: usize) -> IterIndexRange<K, V, S, C, INIT> {
+        IterIndexRange {
+            position: start,
+            end: cmp::min(end, self.hash_table.index_size()),
+            node: ptr::null_mut(),
+            map: self,
+            mod_count: self.hash_table.mod_count(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs together with where they are stored: the
+    /// index of their bucket in the index array, and their depth in the AVL of the bucket,
+    /// the root being at depth 0. Buckets are visited one after another, each level by level.
+    /// Meant for diagnosing how a hasher spreads keys.
+    /// The iterator element type is `(&'a K, &'a V, usize, usize)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let max_depth = map.iter_with_layout().map(|(_, _, _, depth)| depth).max();
+    /// assert!(max_depth.unwrap() < 4);
+    /// ```
+    pub fn iter_with_layout(&self) -> IterWithLayout<K, V, S, C, INIT> {
+        IterWithLayout {
+            index: self.hash_table.index_first(),
+            bucket: 0,
+            queue: VecDeque::new(),
+            map: self,
+            len: self.len(),
+            mod_count: self.hash_table.mod_count(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order,
+    /// with mutable references to the values.
+    /// The iterator element type is `(&'a K, &'a mut V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// // Update all values
+    /// for (_, val) in map.iter_mut() {
+    ///     *val *= 2;
+    /// }
+    ///
+    /// for (key, val) in &map {
+    ///     println!("key: {} val: {}", key, val);
+    /// }
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<K, V, S, C, INIT> {
+        IterMut {
+            inner: self.first(),
+            len: self.len(),
+            mod_count: self.hash_table.mod_count(),
+            map: self,
+        }
+    }
+
+    /// An iterator visiting the key-value pairs whose key matches `pred` in arbitrary order,
+    /// with mutable references to the values.
+    /// The iterator element type is `(&'a K, &'a mut V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..6).map(|i| (i, i)).collect();
+    /// for (_, val) in map.iter_mut_where(|k| k % 2 == 0) {
+    ///     *val *= 10;
+    /// }
+    /// assert_eq!(map[&2], 20);
+    /// assert_eq!(map[&3], 3);
+    /// ```
+    pub fn iter_mut_where<F>(&mut self, pred: F) -> IterMutWhere<K, V, S, C, F, INIT>
+    where
+        F: Fn(&K) -> bool,
+    {
+        IterMutWhere {
+            inner: self.iter_mut(),
+            pred,
+        }
+    }
+
+    /// Clears the map, returning all key-value pairs as an iterator. Keeps the
+    /// allocated memory for reuse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a = HashMap::new();
+    /// a.insert(1, "a");
+    /// a.insert(2, "b");
+    ///
+    /// for (k, v) in a.drain().take(1) {
+    ///     assert!(k == 1 || k == 2);
+    ///     assert!(v == "a" || v == "b");
+    /// }
+    ///
+    /// assert!(a.is_empty());
+    /// ```
+    #[inline]
+    pub fn drain(&mut self) -> Drain<K, V, S, C, INIT> {
+        Drain {
+            entry: self.first(),
+            map: self,
+        }
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` such that `f(&k, &mut v)` returns `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..8).map(|x| (x, x * 10)).collect();
+    /// map.retain(|&k, _| k % 2 == 0);
+    /// assert_eq!(map.len(), 4);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut entry = self.first();
+        while !entry.is_null() {
+            let next = self.next(entry);
+            let keep = unsafe { f(&(*entry.key()), &mut (*entry.value())) };
+            if !keep {
+                self.erase(entry);
+            }
+            entry = next;
+        }
+    }
+
+    /// Retains only the elements specified by the predicate, like [`retain`], but evaluates
+    /// `pred` on the buckets in parallel with rayon. The removals are then applied serially.
+    /// Worth it when `pred` is expensive; otherwise the serial `retain` is faster.
+    ///
+    /// Only available with feature `rayon`.
+    ///
+    /// [`retain`]: #method.retain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..1000).map(|x| (x, x)).collect();
+    /// map.par_retain(|k, _| k % 3 == 0);
+    /// assert_eq!(map.len(), 334);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_retain<F>(&mut self, pred: F)
+    where
+        K: Sync,
+        V: Sync,
+        F: Fn(&K, &V) -> bool + Sync,
+    {
+        use rayon::prelude::*;
+
+        // walking the buckets one after another visits the entries in the same order as
+        // `first` and `next`, so the flags can be matched back to the entries
+        let keep: Vec<Vec<bool>> = {
+            let buckets: Vec<_> = self.buckets().collect();
+            buckets
+                .into_par_iter()
+                .map(|bucket| bucket.iter().map(|(k, v)| pred(k, v)).collect())
+                .collect()
+        };
+        let mut entry = self.first();
+        for keep in keep.into_iter().flatten() {
+            let next = self.next(entry);
+            if !keep {
+                self.erase(entry);
+            }
+            entry = next;
+        }
+    }
+
+    /// Visits every entry in iteration order with mutable access to its value, threading the
+    /// state `init` through the calls of `f`, and returns the final state. Handy to update
+    /// every value and gather a summary in one pass.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut prices: HashMap<&str, u32> = [("a", 10), ("b", 25)].iter().cloned().collect();
+    /// // raise every price by 10%, counting the prices above 20 before the raise
+    /// let expensive = prices.scan_mut(0, |n, _, price| {
+    ///     if *price > 20 {
+    ///         *n += 1;
+    ///     }
+    ///     *price = *price * 11 / 10;
+    /// });
+    /// assert_eq!(expensive, 1);
+    /// assert_eq!(prices["a"], 11);
+    /// ```
+    pub fn scan_mut<St, F>(&mut self, init: St, mut f: F) -> St
+    where
+        F: FnMut(&mut St, &K, &mut V),
+    {
+        let mut state = init;
+        let mut entry = self.first();
+        while !entry.is_null() {
+            unsafe { f(&mut state, &(*entry.key()), &mut (*entry.value())) };
+            entry = self.next(entry);
+        }
+        state
+    }
+
+    /// Like [`retain`], but the closure can stop the scan early by returning
+    /// [`RetainAction::StopKeepRest`], which keeps the current entry and all entries not
+    /// visited yet. Entries are visited in iteration order.
+    ///
+    /// [`retain`]: #method.retain
+    /// [`RetainAction::StopKeepRest`]: enum.RetainAction.html#variant.StopKeepRest
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::{HashMap, RetainAction};
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..100).map(|x| (x, x)).collect();
+    /// // evict until 10 entries are left
+    /// let mut len = map.len();
+    /// map.retain_until(|_, _| {
+    ///     if len == 10 {
+    ///         return RetainAction::StopKeepRest;
+    ///     }
+    ///     len -= 1;
+    ///     RetainAction::Remove
+    /// });
+    /// assert_eq!(map.len(), 10);
+    /// ```
+    pub fn retain_until<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> RetainAction,
+    {
+        let mut entry = self.first();
+        while !entry.is_null() {
+            let next = self.next(entry);
+            match unsafe { f(&(*entry.key()), &mut (*entry.value())) } {
+                RetainAction::Keep => {}
+                RetainAction::Remove => {
+                    self.erase(entry);
+                }
+                RetainAction::StopKeepRest => return,
+            }
+            entry = next;
+        }
+    }
+
+    /// Retains only the elements whose key is accepted by the predicate.
+    ///
+    /// A shorthand for [`retain`] which ignores the value; this is handy for maps
+    /// like `HashMap<K, ()>` that are used as sets.
+    ///
+    /// [`retain`]: #method.retain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut set: HashMap<i32, ()> = (0..8).map(|x| (x, ())).collect();
+    /// set.retain_keys(|&k| k < 3);
+    /// assert_eq!(set.len(), 3);
+    /// ```
+    pub fn retain_keys<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.retain(|k, _| f(k));
+    }
+
+    fn erase(&mut self, entry: *mut InternalHashEntry<K, V>) -> Option<(K, V)> {
+        debug_assert!(!entry.is_null());
+        debug_assert!(!entry.node_ptr().avl_node_ptr().empty());
+        self.hash_table.hash_erase(entry.node_ptr());
+        if self.insertion_order.is_some() {
+            entry.order_node().list_del();
+        }
+        let kv = key_deref_to_kv::<K, V>(entry.key());
+        self.entry_fastbin.del(entry as VoidPtr);
+        let res = unsafe { Some(ptr::read(kv)) };
+        self.kv_fastbin.del(kv as VoidPtr);
+        res
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    /// Passes the entry of every key of `keys` to `f` in turn, for batch upserts under a
+    /// single mutable borrow of the map. A key may repeat, and then sees the effect of the
+    /// earlier entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut counts = HashMap::new();
+    /// counts.for_each_entry("abracadabra".chars(), |entry| *entry.or_insert(0) += 1);
+    /// assert_eq!(counts[&'a'], 5);
+    /// assert_eq!(counts[&'r'], 2);
+    /// ```
+    pub fn for_each_entry<I, F>(&mut self, keys: I, mut f: F)
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(Entry<K, V, S, C, INIT>),
+    {
+        for key in keys {
+            f(self.entry(key));
+        }
+    }
+
+    /// Like [`for_each_entry`], but first reserves room for the lower bound of the size hint
+    /// of `keys`, so that a batch of new keys grows the index array at most once, up front,
+    /// instead of rehashing several times along the way. The reserve assumes every key is
+    /// new, so for a batch of mostly existing keys, prefer `for_each_entry`.
+    ///
+    /// [`for_each_entry`]: #method.for_each_entry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut squares = HashMap::new();
+    /// squares.entry_batch(0..1000, |entry| {
+    ///     let k = *entry.key();
+    ///     entry.or_insert(k * k);
+    /// });
+    /// assert_eq!(squares.len(), 1000);
+    /// assert_eq!(squares[&12], 144);
+    /// ```
+    pub fn entry_batch<I, F>(&mut self, keys: I, f: F)
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(Entry<K, V, S, C, INIT>),
+    {
+        let keys = keys.into_iter();
+        self.reserve(keys.size_hint().0);
+        self.for_each_entry(keys, f);
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut letters = HashMap::new();
+    ///
+    /// for ch in "a short treatise on fungi".chars() {
+    ///     let counter = letters.entry(ch).or_insert(0);
+    ///     *counter += 1;
+    /// }
+    ///
+    /// assert_eq!(letters[&'s'], 2);
+    /// assert_eq!(letters[&'t'], 3);
+    /// assert_eq!(letters[&'u'], 1);
+    /// assert_eq!(letters.get(&'y'), None);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<K, V, S, C, INIT> {
+        let hash_val = self.make_hash(&key);
+        self.entry_internal(hash_val, key)
+    }
+
+    /// Gets the entry of `key` like [`entry`], but with the hash value of the key given by
+    /// the caller, for instance kept from an earlier lookup, instead of hashing the key again.
+    ///
+    /// The hash must be computed by the [`hasher`] of the map. A wrong hash is not detected
+    /// in release builds, and leaves the map unable to find the key; debug builds panic.
+    ///
+    /// [`entry`]: #method.entry
+    /// [`hasher`]: #method.hasher
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    ///
+    /// let mut map = HashMap::new();
+    /// let mut state = map.hasher().build_hasher();
+    /// "a".hash(&mut state);
+    /// let hash = state.finish();
+    ///
+    /// *map.entry_hashed(hash, "a").or_insert(0) += 1;
+    /// *map.entry_hashed(hash, "a").or_insert(0) += 1;
+    /// assert_eq!(map["a"], 2);
+    /// ```
+    pub fn entry_hashed(&mut self, hash: u64, key: K) -> Entry<K, V, S, C, INIT> {
+        let hash_val = hash as HashUint;
+        debug_assert!(hash_val == self.make_hash(&key), "hash of the key doesn't match");
+        self.entry_internal(hash_val, key)
+    }
+
+    #[inline]
+    fn entry_internal(&mut self, hash_val: HashUint, mut key: K) -> Entry<K, V, S, C, INIT> {
+        let link = self.hash_table.get_hash_index(hash_val).avl_root_node_ptr();
+        let (duplicate, parent, link) =
+            unsafe {
+                hash_table::find_duplicate_hash_node(
+                    link,
+                    &mut key as *mut K,
+                    hash_val,
+                    &self.comparator,
+                )
+            };
+        if duplicate.is_null() {
+            return Entry::Vacant(VacantEntry {
+                hash_value: hash_val,
+                key,
+                parent,
+                link,
+                hash_map_mut: self,
+            });
+        } else {
+            return Entry::Occupied(OccupiedEntry {
+                key: Some(key),
+                hash_entry: duplicate.deref_to_hash_entry(),
+                hash_map_mut: self,
+            });
+        };
+    }
+
+    /// Gets the entry of a borrowed key for in-place manipulation. The lookup only uses `q`;
+    /// `make_key` is called to build the owned key on the vacant path only, so the owned key
+    /// may carry data which the borrowed form does not.
+    ///
+    /// `make_key(q)` must hash and compare equal to `q`.
+    ///
+    /// The returned occupied entry retains no search key, so calling `replace_key` or
+    /// `replace_entry` on it panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<String, u32> = HashMap::new();
+    /// *map.entry_with("a", |q: &str| q.to_owned()).or_insert(0) += 1;
+    /// *map.entry_with("a", |_: &str| unreachable!()).or_insert(0) += 1;
+    /// assert_eq!(map["a"], 2);
+    /// ```
+    pub fn entry_with<Q: ?Sized, F>(&mut self, q: &Q, make_key: F) -> Entry<K, V, S, C, INIT>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+        F: FnOnce(&Q) -> K,
+    {
+        let hash_val = self.make_hash(q);
+        let link = self.hash_table.get_hash_index(hash_val).avl_root_node_ptr();
+        let (duplicate, parent, link) =
+            unsafe { hash_table::find_hash_node_link(link, q, hash_val, &self.comparator) };
+        if duplicate.is_null() {
+            let key = make_key(q);
+            debug_assert!(self.comparator.compare(key.borrow(), q) == Ordering::Equal);
+            Entry::Vacant(VacantEntry {
+                hash_value: hash_val,
+                key,
+                parent,
+                link,
+                hash_map_mut: self,
+            })
+        } else {
+            Entry::Occupied(OccupiedEntry {
+                key: None,
+                hash_entry: duplicate.deref_to_hash_entry(),
+                hash_map_mut: self,
+            })
+        }
+    }
+
+    /// Returns a mutable reference to the value of `key`, inserting `default` first if the
+    /// key is absent. A shorthand for `entry(key).or_insert(default)`.
+    ///
+    /// # Examples
+    ///
+    /// Counting words:
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut counter = HashMap::new();
+    /// for word in "a b a c a b".split(' ') {
+    ///     *counter.get_or_insert(word, 0) += 1;
+    /// }
+    /// assert_eq!(counter["a"], 3);
+    /// assert_eq!(counter["b"], 2);
+    /// assert_eq!(counter["c"], 1);
+    /// ```
+    #[inline]
+    pub fn get_or_insert(&mut self, key: K, default: V) -> &mut V {
+        self.entry(key).or_insert(default)
+    }
+
+    /// Returns a mutable reference to the value of `key`, inserting the result of `default`
+    /// first if the key is absent. A shorthand for `entry(key).or_insert_with(default)`.
+    ///
+    /// # Examples
+    ///
+    /// Interning strings:
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut table = HashMap::new();
+    /// let mut names = Vec::new();
+    /// let mut ids = Vec::new();
+    /// for name in vec!["foo", "bar", "foo", "baz", "bar"] {
+    ///     let id = *table.get_or_insert_with(name.to_string(), || {
+    ///         names.push(name);
+    ///         names.len() - 1
+    ///     });
+    ///     ids.push(id);
+    /// }
+    /// assert_eq!(ids, [0, 1, 0, 2, 1]);
+    /// assert_eq!(names, ["foo", "bar", "baz"]);
+    /// ```
+    ///
+    /// A multimap of vectors:
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut by_len: HashMap<usize, Vec<&str>> = HashMap::new();
+    /// for word in "the quick brown fox jumps over the lazy dog".split(' ') {
+    ///     by_len.get_or_insert_with(word.len(), Vec::new).push(word);
+    /// }
+    /// assert_eq!(by_len[&3], ["the", "fox", "the", "dog"]);
+    /// assert_eq!(by_len[&5], ["quick", "brown", "jumps"]);
+    /// assert_eq!(by_len.len(), 3);
+    /// ```
+    #[inline]
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, default: F) -> &mut V {
+        self.entry(key).or_insert_with(default)
+    }
+
+    /// Applies `modify` to the value of a key if it is present, otherwise inserts `default()`
+    /// under `q.to_owned()`. The key is looked up by its borrowed form, so an owned key is
+    /// only built on a miss, unlike with [`entry`], which always takes an owned key.
+    ///
+    /// [`entry`]: #method.entry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut counts: HashMap<String, u32> = HashMap::new();
+    /// for word in "a b a c a".split(' ') {
+    ///     counts.modify_or_insert(word, |n| *n += 1, || 1);
+    /// }
+    /// assert_eq!(counts["a"], 3);
+    /// assert_eq!(counts["c"], 1);
+    /// ```
+    pub fn modify_or_insert<Q: ?Sized, M, D>(&mut self, q: &Q, modify: M, default: D)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ToOwned<Owned = K>,
+        C: Comparator<Q>,
+        M: FnOnce(&mut V),
+        D: FnOnce() -> V,
+    {
+        let entry = self.find(q);
+        if entry.is_null() {
+            self.insert(q.to_owned(), default());
+        } else {
+            unsafe { modify(&mut *entry.value()) }
+        }
+    }
+
+    #[inline]
+    fn make_hash<X: ?Sized>(&self, x: &X) -> HashUint
+    where
+        X: Hash,
+    {
+        hash_table::make_hash(&self.hash_builder, x)
+    }
+
+    #[inline]
+    fn find<Q: ?Sized>(&self, q: &Q) -> *mut InternalHashEntry<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + Hash,
+        C: Comparator<Q>,
+    {
+        let node = self
+            .hash_table
+            .hash_find(self.make_hash(q), q, &self.comparator);
+        if node.is_null() {
+            ptr::null_mut()
+        } else {
+            node.deref_to_hash_entry()
+        }
+    }
+
+    /// Returns the number of comparisons a lookup of `q` makes: the number of AVL nodes it
+    /// visits in the bucket of `q` until the key is found, or known to be absent. Each visit
+    /// compares the hash values, and the keys too when the hash values are equal.
+    ///
+    /// A bucket of `n` entries costs about `log2(n)` per lookup, so this measures how well a
+    /// hasher spreads a given distribution of keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert_eq!(map.probe_cost(&1), 0);
+    /// map.insert(1, "a");
+    /// assert_eq!(map.probe_cost(&1), 1);
+    /// ```
+    pub fn probe_cost<Q: ?Sized>(&self, q: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + Hash,
+        C: Comparator<Q>,
+    {
+        self.hash_table
+            .hash_probe_cost(self.make_hash(q), q, &self.comparator)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q: ?Sized>(&self, q: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let entry = self.find(q);
+        if entry.is_null() {
+            return None;
+        }
+        unsafe { Some(&(*entry.value())) }
+    }
+
+    /// Looks up every key of `keys`, returning the values in the same positions. Keys may
+    /// repeat.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// assert_eq!(map.get_many(&[&"b", &"x", &"a"]), [Some(&2), None, Some(&1)]);
+    /// ```
+    pub fn get_many<'a, Q: ?Sized>(&'a self, keys: &[&Q]) -> Vec<Option<&'a V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        keys.iter().map(|q| self.get(*q)).collect()
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// if let Some(x) = map.get_mut(&1) {
+    ///     *x = "b";
+    /// }
+    /// assert_eq!(map[&1], "b");
+    /// ```
+    #[inline]
+    pub fn get_mut<Q: ?Sized>(&mut self, q: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let entry = self.find(q);
+        if entry.is_null() {
+            return None;
+        }
+        unsafe { Some(&mut (*entry.value())) }
+    }
+
+    /// Returns a raw pointer to the value corresponding to the key, for handing to C or for
+    /// self-referential structures. Reading through it is fine while the map is not mutably
+    /// borrowed; to write through it, use [`get_mut_ptr`].
+    ///
+    /// Pairs are allocated one by one and never moved by inserting other keys, so the pointer
+    /// stays valid until the entry is removed, its value is replaced by [`insert`]ing the same
+    /// key again, or the storage is rebuilt by [`shrink_to_fit`], [`compact`], [`clear`],
+    /// [`reset`] or dropping the map. While [`set_auto_shrink`] is on, removing any entry may
+    /// call [`shrink_to_fit`], so it invalidates pointers to the other entries as well.
+    ///
+    /// [`get_mut_ptr`]: #method.get_mut_ptr
+    /// [`insert`]: #method.insert
+    /// [`set_auto_shrink`]: #method.set_auto_shrink
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    /// [`compact`]: #method.compact
+    /// [`clear`]: #method.clear
+    /// [`reset`]: #method.reset
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// let ptr = map.get_ptr(&1).unwrap();
+    /// for i in 2..1000 {
+    ///     map.insert(i, "b");
+    /// }
+    /// assert_eq!(unsafe { *ptr.as_ptr() }, "a");
+    /// ```
+    #[inline]
+    pub fn get_ptr<Q: ?Sized>(&self, q: &Q) -> Option<NonNull<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let entry = self.find(q);
+        if entry.is_null() {
+            return None;
+        }
+        NonNull::new(entry.value())
+    }
+
+    /// Like [`get_ptr`], but the pointer may also be written through, as long as the map is
+    /// not borrowed meanwhile.
+    ///
+    /// [`get_ptr`]: #method.get_ptr
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, 10);
+    /// let ptr = map.get_mut_ptr(&1).unwrap();
+    /// unsafe { *ptr.as_ptr() += 1 };
+    /// assert_eq!(map[&1], 11);
+    /// ```
+    #[inline]
+    pub fn get_mut_ptr<Q: ?Sized>(&mut self, q: &Q) -> Option<NonNull<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        self.get_mut(q).map(NonNull::from)
+    }
+
+    /// Replaces the value of a key with `new` only if it currently equals `expected`.
+    ///
+    /// Returns `Ok(())` once the value is replaced. Otherwise the map is left untouched and
+    /// `new` is dropped: `Err(Some(current))` carries a clone of the value found instead, and
+    /// `Err(None)` means the key is absent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("version", 1);
+    /// assert_eq!(map.compare_and_update("version", &1, 2), Ok(()));
+    /// assert_eq!(map.compare_and_update("version", &1, 3), Err(Some(2)));
+    /// assert_eq!(map.compare_and_update("other", &1, 3), Err(None));
+    /// assert_eq!(map["version"], 2);
+    /// ```
+    pub fn compare_and_update<Q: ?Sized>(
+        &mut self,
+        q: &Q,
+        expected: &V,
+        new: V,
+    ) -> Result<(), Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+        V: PartialEq + Clone,
+    {
+        match self.get_mut(q) {
+            None => Err(None),
+            Some(current) => if *current == *expected {
+                *current = new;
+                Ok(())
+            } else {
+                Err(Some(current.clone()))
+            },
+        }
+    }
+
+    /// Attempts to get mutable references to `N` values in the map at once.
+    ///
+    /// Each key is resolved independently: a missing key yields `None` in its slot
+    /// while the other slots are still filled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if two keys refer to the same present entry. Repeated keys which are
+    /// absent from the map do not panic and simply yield `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// {
+    ///     let [a, b, c] = map.get_disjoint_mut([&1, &2, &3]);
+    ///     *a.unwrap() = "x";
+    ///     *b.unwrap() = "y";
+    ///     assert_eq!(c, None);
+    /// }
+    /// assert_eq!(map[&1], "x");
+    /// assert_eq!(map[&2], "y");
+    /// ```
+    pub fn get_disjoint_mut<Q: ?Sized, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> [Option<&mut V>; N]
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let mut entries = [ptr::null_mut::<InternalHashEntry<K, V>>(); N];
+        for (i, q) in keys.iter().enumerate() {
+            let entry = self.find(*q);
+            if !entry.is_null() && entries[..i].contains(&entry) {
+                panic!("duplicate keys passed to get_disjoint_mut");
+            }
+            entries[i] = entry;
+        }
+        entries.map(|entry| {
+            if entry.is_null() {
+                None
+            } else {
+                unsafe { Some(&mut (*entry.value())) }
+            }
+        })
+    }
+
+    #[inline]
+    fn rehash(&mut self, len: usize) {
+        self.hash_table.rehash(len, &self.comparator);
+    }
+
+    /// Grows the index array for `len` elements when inserting, unless [`defer_rehash`]
+    /// holds growth back.
+    ///
+    /// [`defer_rehash`]: #method.defer_rehash
+    #[inline]
+    fn auto_rehash(&mut self, len: usize) {
+        if !self.rehash_deferred {
+            self.rehash(len);
+        }
+    }
+
+    /// Stops inserts from growing the index array until the returned guard, which gives
+    /// access to the map, is dropped. The index array is then resized once, to fit the
+    /// final length.
+    ///
+    /// Every HashIndex holds an AVL, so an overloaded index array only makes the buckets
+    /// deeper meanwhile: lookups and inserts stay O(log n), and a bulk load never stalls on
+    /// a resize halfway. [`reserve`] still grows the index array inside the guard.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = HashMap::new();
+    /// {
+    ///     let mut bulk = map.defer_rehash();
+    ///     for i in 0..1000 {
+    ///         bulk.insert(i, i);
+    ///     }
+    ///     assert_eq!(bulk.resize_info().index_size, 8);
+    /// }
+    /// assert!(map.resize_info().index_size >= 1024);
+    /// ```
+    pub fn defer_rehash(&mut self) -> RehashGuard<K, V, S, C, INIT> {
+        let deferred_before = self.rehash_deferred;
+        self.rehash_deferred = true;
+        RehashGuard {
+            map: self,
+            deferred_before,
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements to be inserted
+    /// in the `HashMap`. The collection may reserve more space to avoid
+    /// frequent reallocations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows `usize`, or if the index array can't be
+    /// allocated. See [`try_reserve`] to handle the latter.
+    ///
+    /// [`try_reserve`]: #method.try_reserve
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<&str, isize> = HashMap::new();
+    /// map.reserve(10);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        if !self.try_reserve(additional) {
+            panic!("memory overflow");
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements like [`reserve`], and
+    /// returns a guard which gives access to the map and calls [`shrink_to_fit`] when dropped.
+    /// Handy to absorb a burst of inserts without keeping the extra memory afterwards.
+    ///
+    /// [`reserve`]: #method.reserve
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = HashMap::new();
+    /// {
+    ///     let mut burst = map.reserve_scoped(1000);
+    ///     assert!(burst.capacity() >= 1000);
+    ///     burst.insert(1, 1);
+    /// }
+    /// assert!(map.capacity() < 1000);
+    /// assert_eq!(map[&1], 1);
+    /// ```
+    pub fn reserve_scoped(&mut self, additional: usize) -> ReserveGuard<K, V, S, C, INIT> {
+        self.reserve(additional);
+        ReserveGuard { map: self }
+    }
+
+    /// Reserves capacity for at least `additional` more elements like [`reserve`], and also
+    /// allocates the memory those elements will be stored in, so that the next `additional`
+    /// inserts don't allocate at all. [`reserve`] only sizes the index array; the entries are
+    /// otherwise allocated page by page as the map grows.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<u32, u32> = HashMap::new();
+    /// map.prewarm(1000);
+    /// let memory = map.memory_usage();
+    /// for i in 0..1000 {
+    ///     map.insert(i, i);
+    /// }
+    /// assert_eq!(map.memory_usage(), memory);
+    /// ```
+    pub fn prewarm(&mut self, additional: usize) {
+        self.reserve(additional);
+        self.entry_fastbin.reserve(additional);
+        self.kv_fastbin.reserve(additional);
+    }
+
+    /// Makes sure the next insert of a new key doesn't allocate: grows the index array now if
+    /// that insert would, and carves the memory of one more element. Returns whether this
+    /// allocated anything. Meant for code which can't afford an allocation at a given insert,
+    /// so the cost is paid ahead of time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<u32, u32> = HashMap::new();
+    /// map.reserve_one();
+    /// let memory = map.memory_usage();
+    /// map.insert(1, 1);
+    /// assert_eq!(map.memory_usage(), memory);
+    /// ```
+    pub fn reserve_one(&mut self) -> bool {
+        let allocated = |map: &Self| {
+            (
+                map.hash_table.resize_count(),
+                map.entry_fastbin.allocated_bytes(),
+                map.kv_fastbin.allocated_bytes(),
+            )
+        };
+        let before = allocated(self);
+        let len = self.len() + 1;
+        self.rehash(len);
+        self.entry_fastbin.reserve(1);
+        self.kv_fastbin.reserve(1);
+        allocated(self) != before
+    }
+
+    /// Tries to reserve capacity for at least `additional` more elements like [`reserve`],
+    /// returning `false` instead of panicking if the larger index array can't be allocated.
+    ///
+    /// The new index array is allocated before any entry moves into it, so on failure the
+    /// map is left exactly as it was, with every entry still reachable.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<&str, isize> = HashMap::new();
+    /// assert!(map.try_reserve(10));
+    /// assert!(map.capacity() >= 10);
+    /// ```
+    pub fn try_reserve(&mut self, additional: usize) -> bool {
+        // inside defer_rehash, the map may hold more than its capacity
+        let remaining = self.capacity().saturating_sub(self.len());
+        if remaining < additional {
+            match self.len().checked_add(additional) {
+                None => panic!("capacity overflow"),
+                Some(min_cap) => return self.hash_table.try_rehash(min_cap, &self.comparator),
+            };
+        }
+        // we use BST to restore concrete data, so there is no need to do
+        // any thing if capacity is equal to len
+        true
+    }
+
+    /// Returns true if the map contains a value for the specified key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.contains_key(&1), true);
+    /// assert_eq!(map.contains_key(&2), false);
+    /// ```
+    pub fn contains_key<Q: ?Sized>(&self, q: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        !self.find(q).is_null()
+    }
+
+    /// Returns true if every key-value pair of the map is also in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let a: HashMap<i32, i32> = (0..5).map(|i| (i, i)).collect();
+    /// let b: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+    /// assert!(a.is_submap_of(&b));
+    /// assert!(!b.is_submap_of(&a));
+    /// ```
+    pub fn is_submap_of(&self, other: &HashMap<K, V, S, C, INIT>) -> bool
+    where
+        V: PartialEq,
+    {
+        if self.len() > other.len() {
+            return false;
+        }
+        self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+
+    /// Returns true if every key-value pair of `other` is also in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let a: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+    /// let mut b: HashMap<i32, i32> = (0..5).map(|i| (i, i)).collect();
+    /// assert!(a.is_supermap_of(&b));
+    /// b.insert(1, 100);
+    /// assert!(!a.is_supermap_of(&b));
+    /// ```
+    #[inline]
+    pub fn is_supermap_of(&self, other: &HashMap<K, V, S, C, INIT>) -> bool
+    where
+        V: PartialEq,
+    {
+        other.is_submap_of(self)
+    }
+
+    /// An iterator visiting all key-value pairs in ascending key order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// Unlike [`iter`], the order doesn't depend on the hash layout, so the output is
+    /// deterministic across runs. Every AVL under a HashIndex is ordered by hash value first
+    /// and key second, so buckets can't simply be merged; the entries are collected and
+    /// sorted instead, which costs O(n log n) time and O(n) extra space.
+    ///
+    /// [`iter`]: #method.iter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("c", 3);
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let keys: Vec<_> = map.iter_sorted().map(|(k, _)| *k).collect();
+    /// assert_eq!(keys, ["a", "b", "c"]);
+    /// ```
+    pub fn iter_sorted(&self) -> SortedIter<K, V> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_unstable_by(|a, b| self.comparator.compare(a.0, b.0));
+        SortedIter {
+            inner: entries.into_iter(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in ascending key order, with mutable
+    /// references to the values, for in-place transforms whose result depends on the order.
+    ///
+    /// As with [`iter_sorted`], the buckets can't be merged since every AVL is ordered by hash
+    /// value first, so the references are collected and sorted, in O(n log n) time. Every
+    /// value lives in its own allocation, so the mutable references never alias.
+    ///
+    /// [`iter_sorted`]: #method.iter_sorted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (1..5).map(|i| (i, i)).collect();
+    /// // running sums over the keys
+    /// let mut total = 0;
+    /// for (_, v) in map.iter_mut_sorted() {
+    ///     total += *v;
+    ///     *v = total;
+    /// }
+    /// assert_eq!(map[&1], 1);
+    /// assert_eq!(map[&4], 10);
+    /// ```
+    pub fn iter_mut_sorted(&mut self) -> SortedIterMut<K, V> {
+        // IterMut only needs a shared borrow, which the comparator can share for the sort
+        let map: &Self = self;
+        let mut inner: Vec<_> = IterMut {
+            inner: map.first(),
+            len: map.len(),
+            mod_count: map.hash_table.mod_count(),
+            map,
+        }
+        .collect();
+        inner.sort_unstable_by(|a, b| map.comparator.compare(a.0, b.0));
+        SortedIterMut {
+            inner: inner.into_iter(),
+        }
+    }
+
+    /// Consumes the map into a `Vec` of its pairs in ascending key order. The pairs are
+    /// moved, never cloned.
+    ///
+    /// Like [`iter_sorted`], the buckets can't be merged since every AVL is ordered by hash
+    /// value first, so the pairs are moved out and sorted, in O(n log n) time.
+    ///
+    /// [`iter_sorted`]: #method.iter_sorted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, char> = vec![(3, 'c'), (1, 'a'), (2, 'b')].into_iter().collect();
+    /// assert_eq!(map.into_sorted_vec(), [(1, 'a'), (2, 'b'), (3, 'c')]);
+    /// ```
+    pub fn into_sorted_vec(mut self) -> Vec<(K, V)> {
+        let mut pairs = Vec::with_capacity(self.len());
+        pairs.extend(self.drain());
+        let cmp = &self.comparator;
+        pairs.sort_unstable_by(|a, b| cmp.compare(&a.0, &b.0));
+        pairs
+    }
+
+    /// Returns the keys of the map in ascending order, leaving the values untouched, for
+    /// exporting the key set. Sorted like [`iter_sorted`], in O(n log n) time.
+    ///
+    /// [`iter_sorted`]: #method.iter_sorted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, Vec<u8>> = (0..3).rev().map(|i| (i, vec![0; 1024])).collect();
+    /// assert_eq!(map.keys_to_vec_sorted(), [&0, &1, &2]);
+    /// ```
+    pub fn keys_to_vec_sorted(&self) -> Vec<&K> {
+        let mut keys: Vec<&K> = self.keys().collect();
+        keys.sort_unstable_by(|a, b| self.comparator.compare(a, b));
+        keys
+    }
+
+    /// Consumes the map into a `Vec` of its keys in ascending order. The values are dropped
+    /// as the keys are moved out, and only the keys are sorted, which is cheaper than
+    /// [`into_sorted_vec`] when the values are large.
+    ///
+    /// [`into_sorted_vec`]: #method.into_sorted_vec
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<&str, [u64; 64]> = vec![("b", [0; 64]), ("a", [1; 64])]
+    ///     .into_iter()
+    ///     .collect();
+    /// assert_eq!(map.into_keys_sorted(), ["a", "b"]);
+    /// ```
+    pub fn into_keys_sorted(mut self) -> Vec<K> {
+        let mut keys = Vec::with_capacity(self.len());
+        keys.extend(self.drain().map(|(k, _)| k));
+        let cmp = &self.comparator;
+        keys.sort_unstable_by(|a, b| cmp.compare(a, b));
+        keys
+    }
+
+    /// Clears the map, returning all key-value pairs in ascending key order as an iterator.
+    /// Keeps the allocated memory for reuse, unlike [`into_sorted_vec`].
+    ///
+    /// Like [`into_sorted_vec`], the buckets can't be merged since every AVL is ordered by
+    /// hash value first, so the pairs are moved out and sorted, in O(n log n) time. The map
+    /// is emptied at once; pairs which are not consumed are dropped with the iterator.
+    ///
+    /// [`into_sorted_vec`]: #method.into_sorted_vec
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, char> = vec![(3, 'c'), (1, 'a'), (2, 'b')].into_iter().collect();
+    /// let capacity = map.capacity();
+    /// assert!(map.drain_sorted().eq(vec![(1, 'a'), (2, 'b'), (3, 'c')]));
+    /// assert!(map.is_empty());
+    /// assert_eq!(map.capacity(), capacity);
+    /// ```
+    pub fn drain_sorted(&mut self) -> DrainSorted<K, V> {
+        let mut pairs = Vec::with_capacity(self.len());
+        pairs.extend(self.drain());
+        let cmp = &self.comparator;
+        pairs.sort_unstable_by(|a, b| cmp.compare(&a.0, &b.0));
+        DrainSorted {
+            inner: pairs.into_iter(),
+        }
+    }
+
+    /// Returns all entries sorted by value with `cmp`, in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut scores = HashMap::new();
+    /// scores.insert("alice", 30);
+    /// scores.insert("bob", 10);
+    /// scores.insert("carol", 20);
+    ///
+    /// let ranking: Vec<_> = scores.values_sorted_by(|a, b| b.cmp(a)).into_iter()
+    ///     .map(|(k, _)| *k).collect();
+    /// assert_eq!(ranking, ["alice", "carol", "bob"]);
+    /// ```
+    pub fn values_sorted_by<F>(&self, mut cmp: F) -> Vec<(&K, &V)>
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| cmp(a.1, b.1));
+        entries
+    }
+
+    /// Returns the first `k` entries of [`values_sorted_by`] without sorting the whole map.
+    ///
+    /// A bounded binary heap keeps the best `k` entries seen so far, so this costs
+    /// O(n log k) time and O(k) extra space. Entries whose values compare equal may be picked
+    /// in any order.
+    ///
+    /// [`values_sorted_by`]: #method.values_sorted_by
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let scores: HashMap<u32, u32> = (0..100).map(|i| (i, i * 7 % 100)).collect();
+    /// let top: Vec<_> = scores.top_k_by(3, |a, b| b.cmp(a)).into_iter()
+    ///     .map(|(_, v)| *v).collect();
+    /// assert_eq!(top, [99, 98, 97]);
+    /// ```
+    pub fn top_k_by<F>(&self, k: usize, mut cmp: F) -> Vec<(&K, &V)>
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        if k == 0 {
+            return Vec::new();
+        }
+        // a max-heap under `cmp`, its root is the worst of the kept entries
+        let mut heap: Vec<(&K, &V)> = Vec::with_capacity(cmp::min(k, self.len()));
+        for entry in self.iter() {
+            if heap.len() < k {
+                heap.push(entry);
+                let mut pos = heap.len() - 1;
+                while pos > 0 {
+                    let parent = (pos - 1) / 2;
+                    if cmp(heap[parent].1, heap[pos].1) != Ordering::Less {
+                        break;
+                    }
+                    heap.swap(parent, pos);
+                    pos = parent;
+                }
+            } else if cmp(entry.1, heap[0].1) == Ordering::Less {
+                heap[0] = entry;
+                let mut pos = 0;
+                loop {
+                    let mut largest = pos;
+                    for child in (2 * pos + 1)..cmp::min(2 * pos + 3, heap.len()) {
+                        if cmp(heap[largest].1, heap[child].1) == Ordering::Less {
+                            largest = child;
+                        }
+                    }
+                    if largest == pos {
+                        break;
+                    }
+                    heap.swap(largest, pos);
+                    pos = largest;
+                }
+            }
+        }
+        heap.sort_by(|a, b| cmp(a.1, b.1));
+        heap
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    ///
+    /// If the map did have this key present, update map with new (key, value)
+    /// and return the old one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.is_empty(), false);
+    ///
+    /// map.insert(37, "b");
+    /// assert_eq!(map.insert(37, "c"), Some((37, "b")));
+    /// assert_eq!(map[&37], "c");
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_internal(key, value).0
+    }
+
+    /// Inserts a key-value pair into the map, returning the displaced value (if any)
+    /// together with a mutable reference to the newly stored value.
+    ///
+    /// This saves the second lookup needed to modify the value right after an [`insert`].
+    ///
+    /// [`insert`]: #method.insert
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, 10);
+    /// {
+    ///     let (old, v) = map.insert_and_get_mut(1, 20);
+    ///     assert_eq!(old, Some(10));
+    ///     *v += 1;
+    /// }
+    /// assert_eq!(map[&1], 21);
+    /// ```
+    pub fn insert_and_get_mut(&mut self, key: K, value: V) -> (Option<V>, &mut V) {
+        let (old, new_entry) = self.insert_internal(key, value);
+        (old.map(|(_, v)| v), unsafe { &mut (*new_entry.value()) })
+    }
+
+    /// Insert a key-value pair, returning the displaced pair and the stored entry.
+    #[inline]
+    fn insert_internal(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> (Option<(K, V)>, *mut InternalHashEntry<K, V>) {
+        let len = self.len() + 1;
+        self.auto_rehash(len);
+        let hash_value = self.make_hash(&key);
+        let kv_ptr = self.kv_alloc(key, value);
+        let new_entry = unsafe {
+            self.entry_alloc(
+                &mut (*kv_ptr).0 as *mut K,
+                &mut (*kv_ptr).1 as *mut V,
+                hash_value,
+            )
+        };
+        self.link_new_entry(new_entry)
+    }
+
+    /// Inserts every pair of `iter`, reserving room for all of them up front: since the exact
+    /// number of pairs is known, the index array is resized at most once. `extend` only
+    /// reserves for half of them when the map is not empty, in case many keys are already in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+    /// map.extend_exact((10..1000).map(|i| (i, i)));
+    /// assert_eq!(map.len(), 1000);
+    /// ```
+    pub fn extend_exact<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        self.reserve(iter.len());
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+
+    /// Like [`insert`], but instead of panicking when memory for the new entry or for growing
+    /// the index array can't be allocated, gives the key and value back in `Err`, leaving the
+    /// map as it was.
+    ///
+    /// [`insert`]: #method.insert
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// match map.try_insert_alloc(1, "a") {
+    ///     Ok(old) => assert_eq!(old, None),
+    ///     Err((k, v)) => println!("out of memory, keeping {} => {}", k, v),
+    /// }
+    /// ```
+    pub fn try_insert_alloc(&mut self, key: K, value: V) -> Result<Option<(K, V)>, (K, V)> {
+        // replacing a key needs no room, so a full index array only fails a new key; inside
+        // defer_rehash the index array takes any load and is left alone
+        if !self.rehash_deferred
+            && self.len() >= self.capacity()
+            && !self.hash_table.try_rehash(self.len() + 1, &self.comparator)
+            && !self.contains_key(&key)
+        {
+            return Err((key, value));
+        }
+        let hash_value = self.make_hash(&key);
+        let kv_ptr = match self.kv_fastbin.try_alloc() {
+            Some(kv) => kv as *mut (K, V),
+            None => return Err((key, value)),
+        };
+        let entry = match self.entry_fastbin.try_alloc() {
+            Some(entry) => entry as *mut InternalHashEntry<K, V>,
+            None => {
+                self.kv_fastbin.del(kv_ptr as VoidPtr);
+                return Err((key, value));
+            }
+        };
+        let new_entry = unsafe {
+            ptr::write(kv_ptr, (key, value));
+            entry_init(
+                entry,
+                &mut (*kv_ptr).0 as *mut K,
+                &mut (*kv_ptr).1 as *mut V,
+                hash_value,
+            )
+        };
+        self.entry_track_order(new_entry);
+        Ok(self.link_new_entry(new_entry).0)
+    }
+
+    /// Adds an allocated entry to the hash table, returning the pair it displaced and the entry.
+    #[inline]
+    fn link_new_entry(
+        &mut self,
+        new_entry: *mut InternalHashEntry<K, V>,
+    ) -> (Option<(K, V)>, *mut InternalHashEntry<K, V>) {
+        let old_entry =
+            unsafe { hash_table_update(self.hash_table.as_mut(), new_entry, &self.comparator) };
+        if old_entry.is_null() {
+            (None, new_entry)
+        } else {
+            if self.insertion_order.is_some() {
+                // the replaced key keeps its original position
+                new_entry.order_node().list_del();
+                ListHeadPtr::list_replace(old_entry.order_node(), new_entry.order_node());
+            }
+            let old_kv_ptr = key_deref_to_kv(old_entry.key());
+            let res = unsafe { Some(ptr::read(old_kv_ptr)) };
+            self.kv_fastbin.del(old_kv_ptr as VoidPtr);
+            self.entry_fastbin.del(old_entry as VoidPtr);
+            (res, new_entry)
+        }
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some((1, "a")));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[inline]
+    pub fn remove<Q: ?Sized>(&mut self, q: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let entry = self.find(q);
+        if entry.is_null() {
+            return None;
+        }
+        let res = self.erase(entry);
+        self.maybe_auto_shrink();
+        res
+    }
+
+    /// Removes `key` from the map, returning the key *stored in the map* together with its
+    /// value. The stored key is equal to `key`, but need not be identical to it, which
+    /// matters when equal keys carry different payloads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(String::from("a"), 1);
+    /// assert_eq!(map.remove_take(&String::from("a")), Some((String::from("a"), 1)));
+    /// assert_eq!(map.remove_take(&String::from("a")), None);
+    /// ```
+    #[inline]
+    pub fn remove_take(&mut self, key: &K) -> Option<(K, V)> {
+        self.remove(key)
+    }
+
+    /// Removes a key from the map, returning the stored key if it was previously in the map.
+    /// The value is dropped. Useful when the map is used as a set.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(String::from("a"), ());
+    /// assert_eq!(map.take("a"), Some(String::from("a")));
+    /// assert_eq!(map.take("a"), None);
+    /// ```
+    #[inline]
+    pub fn take<Q: ?Sized>(&mut self, q: &Q) -> Option<K>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        self.remove(q).map(|(k, _)| k)
+    }
+
+    /// Removes a key from the map only if `pred` returns `true` for the stored key and value,
+    /// returning them. Returns `None` if the key is absent or `pred` returns `false`, leaving
+    /// the map untouched. The key is looked up once, unlike a [`get`] followed by a
+    /// [`remove`].
+    ///
+    /// [`get`]: #method.get
+    /// [`remove`]: #method.remove
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 10);
+    /// assert_eq!(map.remove_if("a", |_, &v| v > 5), None);
+    /// assert_eq!(map.remove_if("b", |_, &v| v > 5), Some(("b", 10)));
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn remove_if<Q: ?Sized, F>(&mut self, q: &Q, pred: F) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+        F: FnOnce(&K, &V) -> bool,
+    {
+        let entry = self.find(q);
+        if entry.is_null() || unsafe { !pred(&*entry.key(), &*entry.value()) } {
+            return None;
+        }
+        let res = self.erase(entry);
+        self.maybe_auto_shrink();
+        res
+    }
+
+    /// Changes the key `old` to `new` in place, returning `true`. The entry is moved to the
+    /// bucket of `new`, but keeps its memory, so the value is not moved at all, and it keeps its
+    /// position in insertion order. The old key is dropped.
+    ///
+    /// Returns `false`, dropping `new`, if `old` is absent or `new` is already in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a".to_string(), 1);
+    /// map.insert("b".to_string(), 2);
+    /// assert!(map.rekey("a", "c".to_string()));
+    /// assert!(!map.rekey("a", "d".to_string()));
+    /// assert!(!map.rekey("b", "c".to_string()));
+    /// assert_eq!(map["c"], 1);
+    /// assert!(!map.contains_key("a"));
+    /// ```
+    pub fn rekey<Q: ?Sized>(&mut self, old: &Q, new: K) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        let entry = self.find(old);
+        if entry.is_null() || self.contains_key::<K>(&new) {
+            return false;
+        }
+        let hash_value = self.make_hash(&new);
+        self.hash_table.hash_erase(entry.node_ptr());
+        let old_key = unsafe { mem::replace(&mut *entry.key(), new) };
+        entry.set_hash_value(hash_value);
+        unsafe {
+            self.hash_table
+                .hash_add_unique(entry.node_ptr(), &self.comparator);
+        }
+        // dropped once the entry is linked again, in case the destructor panics
+        drop(old_key);
+        true
+    }
+
+    /// Moves every entry for which `pred` returns `true` into a new map, which is returned,
+    /// leaving the other entries in `self`. The new map uses clones of the hasher and the
+    /// comparator, and tracks insertion order if `self` does, keeping the relative order.
+    /// Keys and values are moved, not cloned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut odds: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+    /// let evens = odds.split_off_where(|&k, _| k % 2 == 0);
+    /// assert_eq!(evens.len(), 5);
+    /// assert_eq!(odds.len(), 5);
+    /// assert!(evens.keys().all(|k| k % 2 == 0));
+    /// assert!(odds.keys().all(|k| k % 2 == 1));
+    /// ```
+    pub fn split_off_where<F>(&mut self, mut pred: F) -> Self
+    where
+        S: Clone,
+        C: Clone,
+        F: FnMut(&K, &V) -> bool,
+    {
+        // pick the entries first, so a panicking `pred` leaves `self` untouched
+        let mut matched = Vec::new();
+        if let Some(ref head) = self.insertion_order {
+            let head = head.as_ref() as *const ListHead as ListHeadPtr;
+            let mut node = head.next();
+            while node != head {
+                let entry: *mut InternalHashEntry<K, V> = node.order_deref_to_hash_entry();
+                if unsafe { pred(&*entry.key(), &*entry.value()) } {
+                    matched.push(entry);
+                }
+                node = node.next();
+            }
+        } else {
+            let mut entry = self.first();
+            while !entry.is_null() {
+                if unsafe { pred(&*entry.key(), &*entry.value()) } {
+                    matched.push(entry);
+                }
+                entry = self.next(entry);
+            }
+        }
+        let mut map = HashMap::new_in(
+            self.hash_builder.clone(),
+            self.comparator.clone(),
+            self.tracks_insertion_order(),
+        );
+        map.auto_shrink = self.auto_shrink;
+        map.reserve(matched.len());
+        for entry in matched {
+            if let Some((k, v)) = self.erase(entry) {
+                map.insert(k, v);
+            }
+        }
+        map
+    }
+
+    fn new_in(
+        hash_builder: S,
+        comparator: C,
+        insertion_order: bool,
+    ) -> HashMap<K, V, S, C, INIT> {
+        HashMap {
+            entry_fastbin: Fastbin::new(entry_size::<K, V>(insertion_order)),
+            kv_fastbin: Fastbin::new(mem::size_of::<(K, V)>()),
+            hash_table: hash_table::HashTable::new_with_box(),
+            hash_builder,
+            comparator,
+            insertion_order: if insertion_order {
+                let mut head = Box::new(ListHead::default());
+                (head.as_mut() as ListHeadPtr).list_init();
+                Some(head)
+            } else {
+                None
+            },
+            auto_shrink: false,
+            rehash_deferred: false,
+        }
+    }
+
+    /// Reallocates the index array to exactly `index_size` HashIndex slots, rounded up to a
+    /// power of two, and moves every entry into it, bypassing the sizing derived from the load
+    /// factor. Stored hash values are reused, so keys are not hashed again.
+    ///
+    /// The size is never set below what `len()` needs, so `len() <= capacity()` still holds.
+    /// It is kept until an insert pushes `len()` past `capacity()`, at which point the index
+    /// grows as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+    /// map.rehash_to_index_size(1000);
+    /// assert_eq!(map.resize_info().index_size, 1024);
+    /// assert_eq!(map[&3], 3);
+    /// ```
+    pub fn rehash_to_index_size(&mut self, index_size: usize) {
+        let index_size = cmp::max(index_size, hash_table::calc_limit(self.len()));
+        let index_size = match index_size.checked_next_power_of_two() {
+            Some(x) => x,
+            None => panic!("capacity overflow"),
+        };
+        if index_size != self.hash_table.index_size() {
+            self.hash_table.resize_index(index_size, &self.comparator);
+        }
+    }
+
+    /// Shrinks the map if auto shrinking is on and the load dropped below the low-water mark.
+    #[inline]
+    fn maybe_auto_shrink(&mut self) {
+        if self.auto_shrink && self.hash_table.index_size() > INIT
+            && self.len() < self.hash_table.index_size() / AUTO_SHRINK_LOAD_INV
+        {
+            self.shrink_to_fit();
+        }
+    }
+
+    /// Shrinks the capacity of the map as much as possible. It will drop
+    /// down as much as possible while maintaining the internal rules
+    /// and possibly leaving some space in accordance with the resize policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<isize, isize> = HashMap::with_capacity(100);
+    /// map.insert(1, 2);
+    /// map.insert(3, 4);
+    /// assert!(map.capacity() >= 100);
+    /// map.shrink_to_fit();
+    /// assert!(map.capacity() >= 2);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        let limit = hash_table::calc_limit(self.len());
+        let old_index_size = self.hash_table.index_size();
+        let new_index_size = {
+            let mut tmp = old_index_size / 2;
+            let mut res = old_index_size;
+            while tmp > 0 && tmp >= limit {
+                res = tmp;
+                tmp /= 2;
+            }
+            res
+        };
+        if new_index_size >= old_index_size {
+            return;
+        }
+        // the inline index array is never given up
+        self.rebuild_storage(cmp::max(new_index_size, INIT));
+    }
+
+    /// Defragments the storage of the map without changing its index size. Entries are moved
+    /// into freshly allocated fastbins, so after many scattered removals the live entries are
+    /// packed together again and iterating or searching touches fewer pages.
+    ///
+    /// Unlike [`shrink_to_fit`] the index array is kept as is, so no future growth is undone.
+    ///
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    /// map.retain(|k, _| k % 3 == 0);
+    /// let capacity = map.capacity();
+    /// map.compact();
+    /// assert_eq!(map.capacity(), capacity);
+    /// assert_eq!(map.get(&999), Some(&999));
+    /// ```
+    pub fn compact(&mut self) {
+        let index_size = self.hash_table.index_size();
+        self.rebuild_storage(index_size);
+    }
+
+    /// Clears the map and gives back its memory: the index array returns to the inline one and
+    /// the fastbin pages are freed, so the map ends up as small as a new one, still using the
+    /// same hasher, comparator and insertion order tracking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    /// a.reset();
+    /// assert!(a.is_empty());
+    /// assert_eq!(a.memory_usage(), HashMap::<i32, i32>::new().memory_usage());
+    /// a.insert(1, 1);
+    /// assert_eq!(a[&1], 1);
+    /// ```
+    pub fn reset(&mut self) {
+        self.clear();
+        self.hash_table = hash_table::HashTable::new_with_box();
+        self.entry_fastbin = Fastbin::new(entry_size::<K, V>(self.insertion_order.is_some()));
+        self.kv_fastbin = Fastbin::new(mem::size_of::<(K, V)>());
+    }
+
+    /// Empties the map like [`reset`], but without running the destructors of the keys and
+    /// values: the index array and the fastbin pages holding them are freed wholesale, so there
+    /// is no O(n) drop pass.
+    ///
+    /// This is for keys and values with nothing to drop, like `Copy` types, or whose resources
+    /// are managed elsewhere, e.g. in an arena. Skipping destructors is memory safe, but
+    /// anything the keys and values own on their own (a `String`'s buffer, an `Rc` count) is
+    /// leaked, and `Drop` side effects never happen.
+    ///
+    /// [`reset`]: #method.reset
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut a: HashMap<u32, [u64; 4]> = (0..1000).map(|i| (i, [0; 4])).collect();
+    /// a.forget_contents();
+    /// assert!(a.is_empty());
+    /// assert_eq!(a.memory_usage(), HashMap::<u32, [u64; 4]>::new().memory_usage());
+    /// ```
+    pub fn forget_contents(&mut self) {
+        if let Some(ref mut head) = self.insertion_order {
+            (head.as_mut() as ListHeadPtr).list_init();
+        }
+        self.hash_table = hash_table::HashTable::new_with_box();
+        self.entry_fastbin = Fastbin::new(entry_size::<K, V>(self.insertion_order.is_some()));
+        self.kv_fastbin = Fastbin::new(mem::size_of::<(K, V)>());
+    }
+
+    /// Clones the map with exactly the same layout: the index array has the same size, and
+    /// the AVL under every HashIndex is copied node by node, reusing the stored hash values.
+    /// Nothing is hashed, compared or rebalanced, so this is faster than [`clone`] and the
+    /// clone iterates in the same order as the original.
+    ///
+    /// [`clone`]: #impl-Clone
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, String> = (0..100).map(|i| (i, i.to_string())).collect();
+    /// let copy = map.clone_layout();
+    /// assert!(map.iter().eq(copy.iter()));
+    /// ```
+    pub fn clone_layout(&self) -> Self
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+        C: Clone,
+    {
+        /// A cloned node, with the position of its parent among the nodes before it.
+        struct Cloned<K, V> {
+            kv: (K, V),
+            hash_value: HashUint,
+            height: i32,
+            parent: usize,
+            is_left: bool,
+            old_entry: *mut InternalHashEntry<K, V>,
+        }
+        let mut map = HashMap::new_in(
+            self.hash_builder.clone(),
+            self.comparator.clone(),
+            self.tracks_insertion_order(),
+        );
+        map.auto_shrink = self.auto_shrink;
+        if self.hash_table.index_size() != map.hash_table.index_size()
+            || self.hash_table.heap_size() != map.hash_table.heap_size()
+        {
+            map.hash_table
+                .resize_index(self.hash_table.index_size(), &map.comparator);
+        }
+        let mut new_entries = StdHashMap::new();
+        let mut cloned = Vec::new();
+        let mut stack = Vec::new();
+        let mut index = self.hash_table.index_first();
+        while !index.is_null() {
+            // clone the whole bucket in pre-order first, so a panicking `clone` leaves `map`
+            // consistent and only drops what was cloned so far
+            cloned.clear();
+            stack.push((index.avl_root_node(), usize::MAX, false));
+            while let Some((node, parent, is_left)) = stack.pop() {
+                let entry: *mut InternalHashEntry<K, V> =
+                    node.avl_hash_deref_mut::<K>().deref_to_hash_entry();
+                let kv = unsafe { ((*entry.key()).clone(), (*entry.value()).clone()) };
+                let pos = cloned.len();
+                cloned.push(Cloned {
+                    kv,
+                    hash_value: entry.node_ptr().hash_val(),
+                    height: node.height(),
+                    parent,
+                    is_left,
+                    old_entry: entry,
+                });
+                if node.right().not_null() {
+                    stack.push((node.right(), pos, false));
+                }
+                if node.left().not_null() {
+                    stack.push((node.left(), pos, true));
+                }
+            }
+            let nodes = cloned.len();
+            let mut new_nodes: Vec<AVLNodePtr> = Vec::with_capacity(nodes);
+            for c in cloned.drain(..) {
+                let (k, v) = c.kv;
+                let kv_ptr = kv_alloc(&mut map.kv_fastbin, k, v);
+                let entry = unsafe {
+                    entry_alloc(
+                        &mut map.entry_fastbin,
+                        &mut (*kv_ptr).0,
+                        &mut (*kv_ptr).1,
+                        c.hash_value,
+                    )
+                };
+                let node = entry.node_ptr().avl_node_ptr();
+                node.reset(ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), c.height);
+                if c.parent != usize::MAX {
+                    let parent = new_nodes[c.parent];
+                    node.set_parent(parent);
+                    if c.is_left {
+                        parent.set_left(node);
+                    } else {
+                        parent.set_right(node);
+                    }
+                }
+                if map.insertion_order.is_some() {
+                    new_entries.insert(c.old_entry, entry);
+                }
+                new_nodes.push(node);
+            }
+            unsafe {
+                map.hash_table.attach_tree(new_nodes[0], nodes);
+            }
+            index = self.hash_table.index_next(index);
+        }
+        if let Some(ref head) = self.insertion_order {
+            let head = head.as_ref() as *const ListHead as ListHeadPtr;
+            let new_head = map.insertion_order.as_mut().unwrap().as_mut() as ListHeadPtr;
+            let mut node = head.next();
+            while node != head {
+                let entry: *mut InternalHashEntry<K, V> = node.order_deref_to_hash_entry();
+                new_head.list_add_tail(new_entries[&entry].order_node());
+                node = node.next();
+            }
+        }
+        map
+    }
+
+    /// Consumes the map and builds one using `hasher` instead, hashing every key again. The
+    /// pairs are moved, never cloned, and the comparator and insertion order are kept.
+    ///
+    /// Useful to rotate the seed of a long running map, or to move away from a weak hasher.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use hash_ord::hashers::FixedState;
+    ///
+    /// let map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let map = map.with_new_hasher(FixedState::with_seed(42));
+    /// assert_eq!(map.len(), 100);
+    /// assert_eq!(map[&42], 42);
+    /// ```
+    pub fn with_new_hasher<S2>(self, hasher: S2) -> HashMap<K, V, S2, C, INIT>
+    where
+        S2: BuildHasher,
+    {
+        let len = self.len();
+        let insertion_order = self.insertion_order.is_some();
+        let auto_shrink = self.auto_shrink;
+        let mut this = mem::ManuallyDrop::new(self);
+        // move the pairs out (in insertion order if it's tracked), then free the old storage
+        // without dropping them again
+        let mut pairs = Vec::with_capacity(len);
+        unsafe {
+            if let Some(ref mut head) = this.insertion_order {
+                let head = head.as_mut() as ListHeadPtr;
+                let mut node = head.next();
+                while node != head {
+                    let entry: *mut InternalHashEntry<K, V> = node.order_deref_to_hash_entry();
+                    pairs.push(ptr::read(key_deref_to_kv::<K, V>(entry.key())));
+                    node = node.next();
+                }
+            } else {
+                let mut entry = this.first();
+                while !entry.is_null() {
+                    pairs.push(ptr::read(key_deref_to_kv::<K, V>(entry.key())));
+                    entry = this.next(entry);
+                }
+            }
+            let comparator = ptr::read(&this.comparator);
+            ptr::drop_in_place(&mut this.entry_fastbin);
+            ptr::drop_in_place(&mut this.kv_fastbin);
+            ptr::drop_in_place(&mut this.hash_table);
+            ptr::drop_in_place(&mut this.hash_builder);
+            ptr::drop_in_place(&mut this.insertion_order);
+            let mut map = HashMap::new_in(hasher, comparator, insertion_order);
+            map.auto_shrink = auto_shrink;
+            map.reserve(len);
+            for (k, v) in pairs {
+                map.insert(k, v);
+            }
+            map
+        }
+    }
+
+    /// Move every entry into new fastbins and a new hash table of `index_size` slots, keeping
+    /// the insertion order if it's tracked.
+    fn rebuild_storage(&mut self, index_size: usize) {
+        let mut new_entry_fastbin =
+            Fastbin::new(entry_size::<K, V>(self.insertion_order.is_some()));
+        let mut new_kv_fastbin = Fastbin::new(mem::size_of::<(K, V)>());
+        let mut new_hash_table = hash_table::HashTable::new_with_box();
+        new_hash_table.rehash(self.len(), &self.comparator);
+        if new_hash_table.index_size() != index_size {
+            new_hash_table.resize_index(index_size, &self.comparator);
+        }
+        // move pairs (in insertion order if it's tracked) together with their stored hash
+        // values, so the hasher is never called again. The old hash table is dropped as a whole.
+        let mut new_kv_vec = Vec::with_capacity(self.len());
+        {
+            let mut move_entry = |entry: *mut InternalHashEntry<K, V>| {
+                let (k, v) = unsafe { ptr::read(key_deref_to_kv::<K, V>(entry.key())) };
+                let kv_ptr = kv_alloc(&mut new_kv_fastbin, k, v);
+                new_kv_vec.push((kv_ptr, entry.node_ptr().hash_val()));
+            };
+            if let Some(ref mut head) = self.insertion_order {
+                let head = head.as_mut() as ListHeadPtr;
+                let mut node = head.next();
+                while node != head {
+                    move_entry(node.order_deref_to_hash_entry());
+                    node = node.next();
+                }
+                head.list_init();
+            } else {
+                let mut entry = self.first();
+                while !entry.is_null() {
+                    let next = self.next(entry);
+                    move_entry(entry);
+                    entry = next;
+                }
+            }
+        }
+        self.hash_table = hash_table::HashTable::new_with_box();
+        for (kv_ptr, hash_value) in new_kv_vec {
+            unsafe {
+                let key_ptr = &mut (*kv_ptr).0 as *mut K;
+                let value_ptr = &mut (*kv_ptr).1 as *mut V;
+                let entry = entry_alloc(&mut new_entry_fastbin, key_ptr, value_ptr, hash_value);
+                if let Some(ref mut head) = self.insertion_order {
+                    (head.as_mut() as ListHeadPtr).list_add_tail(entry.order_node());
+                }
+                hash_table_update(&mut new_hash_table, entry, &self.comparator);
+            }
+        }
+        self.kv_fastbin = new_kv_fastbin;
+        self.entry_fastbin = new_entry_fastbin;
+        self.hash_table = new_hash_table;
+    }
+}
+
+impl<K, V, S, C> HashMap<K, V, S, C>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    /// Creates an empty `HashMap` which will use the given hash builder to hash keys, and the
+    /// given comparator to order keys with the same hash value under a HashIndex.
+    ///
+    /// Keys which the comparator considers equal are the same key, so they *must* also hash
+    /// to the same value. See [`Comparator`] for the details.
+    ///
+    /// [`Comparator`]: ../comparator/trait.Comparator.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let s = RandomState::new();
+    /// let mut map = HashMap::with_hasher_and_comparator(s, |a: &i32, b: &i32| b.cmp(a));
+    /// map.insert(1, 2);
+    /// assert_eq!(map[&1], 2);
+    /// ```
+    pub fn with_hasher_and_comparator(hash_builder: S, comparator: C) -> HashMap<K, V, S, C> {
+        HashMap::new_in(hash_builder, comparator, false)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    /// Creates an empty `HashMap` which will use the given hash builder to hash
+    /// keys.
+    ///
+    /// The created map has the default initial capacity.
+    ///
+    /// Warning: `hash_builder` is normally randomly generated, and
+    /// is designed to allow HashMaps to be resistant to attacks that
+    /// cause many collisions and very poor performance. Setting it
+    /// manually using this function can expose a DoS attack vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let s = RandomState::new();
+    /// let mut map = HashMap::with_hasher(s);
+    /// map.insert(1, 2);
+    /// ```
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashMap::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Creates an empty `HashMap` with the specified capacity, using `hash_builder`
+    /// to hash the keys.
+    ///
+    /// The hash map will be able to hold at least `capacity` elements without
+    /// reallocating. If `capacity` is 0, the hash map will not allocate.
+    ///
+    /// Warning: `hash_builder` is normally randomly generated, and
+    /// is designed to allow HashMaps to be resistant to attacks that
+    /// cause many collisions and very poor performance. Setting it
+    /// manually using this function can expose a DoS attack vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let s = RandomState::new();
+    /// let mut map = HashMap::with_capacity_and_hasher(10, s);
+    /// map.insert(1, 2);
+    /// ```
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> HashMap<K, V, S> {
+        let mut hash_map = HashMap::new_in(hash_builder, OrdComparator, false);
+        hash_map.reserve(capacity);
+        hash_map
+    }
+
+    /// Creates an empty `HashMap` which uses the given hash builder to hash keys and
+    /// remembers the insertion order of keys. See [`with_insertion_order`].
+    ///
+    /// [`with_insertion_order`]: #method.with_insertion_order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let s = RandomState::new();
+    /// let mut map = HashMap::with_insertion_order_and_hasher(s);
+    /// map.insert(1, 2);
+    /// assert!(map.tracks_insertion_order());
+    /// ```
+    pub fn with_insertion_order_and_hasher(hash_builder: S) -> HashMap<K, V, S> {
+        HashMap::new_in(hash_builder, OrdComparator, true)
+    }
+
+    /// Builds a `HashMap` from a batch of pairs whose keys are known to be unique, such as a
+    /// snapshot dumped from another map. The table is sized once for the whole batch and each
+    /// entry is linked into its AVL without checking for an existing equal key.
+    ///
+    /// The order of the batch does not matter. If two keys are equal, the map is left in an
+    /// unspecified (but memory safe) state; debug builds panic instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = HashMap::from_sorted_unchecked((0..100).map(|i| (i, -i)));
+    /// assert_eq!(map.len(), 100);
+    /// assert_eq!(map[&42], -42);
+    /// ```
+    pub fn from_sorted_unchecked<I>(iter: I) -> HashMap<K, V, S>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        S: Default,
+    {
+        let batch: Vec<(K, V)> = iter.into_iter().collect();
+        let mut map = HashMap::with_capacity_and_hasher(batch.len(), S::default());
+        for (key, value) in batch {
+            let hash_value = map.make_hash(&key);
+            let kv_ptr = map.kv_alloc(key, value);
+            unsafe {
+                let entry = map.entry_alloc(
+                    &mut (*kv_ptr).0 as *mut K,
+                    &mut (*kv_ptr).1 as *mut V,
+                    hash_value,
+                );
+                map.hash_table.hash_add_unique(entry.node_ptr(), &map.comparator);
+            }
+        }
+        map
+    }
+
+    /// Builds a `HashMap` from parallel columns of keys and values, pairing `keys[i]` with
+    /// `values[i]`. The table is sized once for all the keys. As with `insert`, a later
+    /// duplicate key replaces the value of an earlier one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `values` have different lengths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let ids = vec![3, 1, 2];
+    /// let names = vec!["c", "a", "b"];
+    /// let map: HashMap<i32, &str> = HashMap::from_columns(ids, names);
+    /// assert_eq!(map.len(), 3);
+    /// assert_eq!(map[&1], "a");
+    /// ```
+    pub fn from_columns(keys: Vec<K>, values: Vec<V>) -> HashMap<K, V, S>
+    where
+        S: Default,
+    {
+        assert_eq!(
+            keys.len(),
+            values.len(),
+            "keys and values have different lengths"
+        );
+        let mut map = HashMap::with_capacity_and_hasher(keys.len(), S::default());
+        for (key, value) in keys.into_iter().zip(values) {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V, C> HashMap<K, V, RandomState, C>
+where
+    K: Ord + Hash,
+    C: Comparator<K>,
+{
+    /// Creates an empty `HashMap` which orders keys with the same hash value by the given
+    /// comparator instead of `Ord`, so the same key type can be compared differently per map.
+    ///
+    /// Keys which the comparator considers equal are the same key, so they *must* also hash
+    /// to the same value. See [`Comparator`] for the details.
+    ///
+    /// [`Comparator`]: ../comparator/trait.Comparator.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::with_comparator(|a: &u32, b: &u32| b.cmp(a));
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// ```
+    #[inline]
+    pub fn with_comparator(comparator: C) -> HashMap<K, V, RandomState, C> {
+        HashMap::with_hasher_and_comparator(Default::default(), comparator)
+    }
+}
+
+impl<K, V> HashMap<K, V, RandomState>
+where
+    K: Hash + Ord,
+{
+    /// Creates an empty `HashMap`.
+    ///
+    /// The hash map is initially created with a capacity of 0, so it will not allocate until it
+    /// is first inserted into.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// let mut map: HashMap<&str, isize> = HashMap::new();
+    /// ```
+    #[inline]
+    pub fn new() -> HashMap<K, V, RandomState> {
+        Default::default()
+    }
+
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> HashMap<K, V, RandomState> {
+        let mut hash_map = HashMap::<K, V, RandomState>::default();
+        hash_map.reserve(capacity);
+        hash_map
+    }
+
+    /// Creates an empty `HashMap` which only uses the inline HashIndex array embedded in
+    /// the table.
+    ///
+    /// No index array is allocated on the heap until the first resize, which happens once
+    /// more than [`capacity`] elements are inserted: the 8 inline slots hold up to 5 elements
+    /// at the maximum load factor of 2/3. This suits tiny maps which never grow.
+    ///
+    /// [`capacity`]: #method.capacity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::with_capacity_inline();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.capacity(), 5);
+    /// ```
+    #[inline]
+    pub fn with_capacity_inline() -> HashMap<K, V, RandomState> {
+        HashMap::new_in(Default::default(), OrdComparator, false)
+    }
+
+    /// Creates an empty `HashMap` which remembers the order in which keys were inserted,
+    /// as exposed by [`iter_insertion_order`].
+    ///
+    /// Every entry carries two extra list pointers, so maps which don't need the order
+    /// should use [`new`] instead.
+    ///
+    /// [`iter_insertion_order`]: #method.iter_insertion_order
+    /// [`new`]: #method.new
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::with_insertion_order();
+    /// for &k in &[3, 1, 2] {
+    ///     map.insert(k, ());
+    /// }
+    /// let keys: Vec<_> = map.iter_insertion_order().map(|(k, _)| *k).collect();
+    /// assert_eq!(keys, [3, 1, 2]);
+    /// ```
+    #[inline]
+    pub fn with_insertion_order() -> HashMap<K, V, RandomState> {
+        HashMap::with_insertion_order_and_hasher(Default::default())
+    }
+}
+
+impl<K, V> HashMap<K, V, IdentityState>
+where
+    K: Hash + Ord,
+{
+    /// Creates an empty `HashMap` using [`IdentityState`], which hashes integer keys by little
+    /// more than their own bits. A fast choice for maps keyed by `u32`, `u64` and the like.
+    ///
+    /// [`IdentityState`]: ../hashers/struct.IdentityState.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::with_identity_hasher();
+    /// for i in 0..100u64 {
+    ///     map.insert(i, i * 2);
+    /// }
+    /// assert_eq!(map[&21], 42);
+    /// ```
+    #[inline]
+    pub fn with_identity_hasher() -> HashMap<K, V, IdentityState> {
+        HashMap::with_hasher(IdentityState)
+    }
+}
+
+/// The first bytes of a `HashMap` written by `dump_pod`.
+const POD_MAGIC: [u8; 4] = *b"HOPD";
+
+/// `load_pod` allocates up front for at most this many elements, so that a forged header
+/// can't make it allocate much more than the input holds.
+const POD_PREALLOC_LIMIT: usize = 1 << 16;
+
+/// `load_pod` restores a sparse index array up to this many times the size its pairs need.
+const POD_SPARSE_LIMIT: usize = 8;
+
+impl<K, V, S, C, const INIT: usize> HashMap<K, V, S, C, INIT>
+where
+    K: Pod + Ord + Hash,
+    V: Pod,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    /// Writes the map to `w` in a flat binary format, which [`load_pod`] reads back: a header
+    /// holding a magic number, the sizes of `K` and `V`, the length and the index size, followed
+    /// by the bytes of every key and value as they are in memory. Numbers are stored in native
+    /// byte order, so the dump is meant to be loaded on the same kind of machine.
+    ///
+    /// [`load_pod`]: #method.load_pod
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<u64, f64> = (0..100).map(|i| (i, i as f64 / 2.0)).collect();
+    /// let mut buf = Vec::new();
+    /// map.dump_pod(&mut buf).unwrap();
+    /// let loaded: HashMap<u64, f64> = HashMap::load_pod(&mut &buf[..]).unwrap();
+    /// assert!(loaded == map);
+    /// ```
+    pub fn dump_pod<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&POD_MAGIC)?;
+        for &n in [
+            mem::size_of::<K>(),
+            mem::size_of::<V>(),
+            self.len(),
+            // inside defer_rehash, the index array may be too small for the length
+            self.hash_table.grown_index_size(self.len()),
+        ].iter()
+        {
+            w.write_all(&(n as u64).to_ne_bytes())?;
+        }
+        for (k, v) in self.iter() {
+            w.write_all(pod_bytes(k))?;
+            w.write_all(pod_bytes(v))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a map written by [`dump_pod`] from `r`. The index array and the entry storage
+    /// are allocated up front, for a bounded number of elements, then the pairs are inserted.
+    /// Once they are all read, the index array is grown back to its dumped size, unless that
+    /// is far larger than the pairs need.
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the header is not one written by [`dump_pod`]
+    /// for the same `K` and `V` sizes, and with the error of `r` if it ends early.
+    ///
+    /// [`dump_pod`]: #method.dump_pod
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    /// use std::io::ErrorKind;
+    ///
+    /// let err = HashMap::<u32, u32>::load_pod(&mut &b"not a map"[..]).err().unwrap();
+    /// assert_eq!(err.kind(), ErrorKind::InvalidData);
+    /// ```
+    pub fn load_pod<R: Read>(r: &mut R) -> io::Result<Self>
+    where
+        S: Default,
+        C: Default,
+    {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic).map_err(|_| invalid("not a pod dump of a HashMap"))?;
+        if magic != POD_MAGIC {
+            return Err(invalid("not a pod dump of a HashMap"));
+        }
+        let mut header = [0usize; 4];
+        for n in header.iter_mut() {
+            let mut buf = [0u8; 8];
+            r.read_exact(&mut buf)?;
+            *n = u64::from_ne_bytes(buf) as usize;
+        }
+        let [key_size, value_size, len, index_size] = header;
+        if key_size != mem::size_of::<K>() || value_size != mem::size_of::<V>() {
+            return Err(invalid("key or value size doesn't match"));
+        }
+        // a dumped index array always fits its length, and could be allocated
+        if !index_size.is_power_of_two()
+            || index_size < hash_table::calc_limit(len)
+            || index_size > isize::MAX as usize / mem::size_of::<HashIndex>()
+        {
+            return Err(invalid("index size doesn't match the length"));
+        }
+        let mut map = HashMap::new_in(Default::default(), Default::default(), false);
+        map.prewarm(cmp::min(len, POD_PREALLOC_LIMIT));
+        for _ in 0..len {
+            let k = read_pod::<K, R>(r)?;
+            let v = read_pod::<V, R>(r)?;
+            map.insert(k, v);
+        }
+        let sparse_limit = map.hash_table.index_size().saturating_mul(POD_SPARSE_LIMIT);
+        if index_size > map.hash_table.index_size() && index_size <= sparse_limit {
+            map.hash_table.try_resize_index(index_size, &map.comparator);
+        }
+        Ok(map)
+    }
+}
+
+#[inline]
+fn pod_bytes<T: Pod>(t: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(t as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+#[inline]
+fn read_pod<T: Pod, R: Read>(r: &mut R) -> io::Result<T> {
+    // any bit pattern is a valid `T`, so starting from zeroes and overwriting them is fine
+    let mut t: T = unsafe { mem::zeroed() };
+    r.read_exact(unsafe {
+        slice::from_raw_parts_mut(&mut t as *mut T as *mut u8, mem::size_of::<T>())
+    })?;
+    Ok(t)
+}
+
+impl<K, V, S, C, const INIT: usize> Default for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher + Default,
+    C: Comparator<K> + Default,
+{
+    /// Creates an empty `HashMap<K, V, S, C, INIT>`, with the `Default` value for the hasher and
+    /// the comparator.
+    fn default() -> HashMap<K, V, S, C, INIT> {
+        HashMap::new_in(Default::default(), Default::default(), false)
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Drop for HashMap<K, V, S, C, INIT> {
+    #[inline]
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// A draining iterator over the entries of a `HashMap`.
+///
+/// This `struct` is created by the [`drain`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`drain`]: struct.HashMap.html#method.drain
+/// [`HashMap`]: struct.HashMap.html
+pub struct Drain<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    entry: *mut InternalHashEntry<K, V>,
+    map: &'a mut HashMap<K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Drop for Drain<'a, K, V, S, C, INIT>
+where
+    K: 'a,
+    V: 'a,
+    S: 'a,
+    C: 'a,
+{
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Iterator for Drain<'a, K, V, S, C, INIT> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        let entry = self.entry;
+        if entry.is_null() {
+            return None;
+        }
+        self.entry = self.map.next(entry);
+        self.map.erase(entry)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len(), Some(self.map.len()))
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> ExactSizeIterator for Drain<'_, K, V, S, C, INIT> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl<'a, K, Q, V, S, C, const INIT: usize> Index<&'a Q> for HashMap<K, V, S, C, INIT>
+where
+    Q: ?Sized + Hash + Ord,
+    K: Hash + Ord + Borrow<Q>,
+    S: BuildHasher,
+    C: Comparator<K> + Comparator<Q>,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `HashMap`.
+    #[inline]
+    fn index(&self, q: &Q) -> &Self::Output {
+        self.get(q).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Extend<(K, V)> for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        let reserve = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            (iter.size_hint().0 + 1) / 2 + self.len()
+        };
+        self.reserve(reserve);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Extend<(&'a K, &'a V)> for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash + Copy,
+    V: Copy,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> IntoIterator for &'a HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, S, C, INIT>;
+
+    fn into_iter(self) -> Iter<'a, K, V, S, C, INIT> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> IntoIterator for &'a mut HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, S, C, INIT>;
+
+    fn into_iter(self) -> IterMut<'a, K, V, S, C, INIT> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> IntoIterator for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S, C, INIT>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            entry: self.first(),
+            back: self.last(),
+            map: self,
+        }
+    }
+}
+
+/// An owning iterator over the entries of a `HashMap`.
+///
+/// This `struct` is created by the [`into_iter`] method on [`HashMap`][`HashMap`]
+/// (provided by the `IntoIterator` trait). See its documentation for more.
+///
+/// [`into_iter`]: struct.HashMap.html#method.into_iter
+/// [`HashMap`]: struct.HashMap.html
+pub struct IntoIter<K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    entry: *mut InternalHashEntry<K, V>,
+    back: *mut InternalHashEntry<K, V>,
+    map: HashMap<K, V, S, C, INIT>,
+}
+
+impl<K, V, S, C, const INIT: usize> IntoIter<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    /// Returns an iterator over the remaining entries without consuming them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..4).map(|i| (i, i)).collect();
+    /// let mut iter = map.into_iter();
+    /// iter.next();
+    /// iter.next_back();
+    /// assert_eq!(iter.iter().count(), 2);
+    /// assert_eq!(iter.count(), 2);
+    /// ```
+    pub fn iter(&self) -> Iter<K, V, S, C, INIT> {
+        Iter {
+            inner: self.entry,
+            map: &self.map,
+            len: self.map.len(),
+            mod_count: self.map.hash_table.mod_count(),
+        }
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Drop for IntoIter<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        for (_, _) in self {}
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Iterator for IntoIter<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entry;
+        if entry.is_null() || self.map.is_empty() {
+            return None;
+        }
+        self.entry = self.map.next(entry);
+        self.map.erase(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len(), Some(self.map.len()))
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> DoubleEndedIterator for IntoIter<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.back;
+        if entry.is_null() || self.map.is_empty() {
+            return None;
+        }
+        self.back = self.map.prev(entry);
+        self.map.erase(entry)
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> ExactSizeIterator for IntoIter<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S, C, const INIT: usize> FromIterator<(K, V)> for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher + Default,
+    C: Comparator<K> + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> HashMap<K, V, S, C, INIT> {
+        let mut map = HashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S, C, T, const INIT: usize> From<StdHashMap<K, V, T>> for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher + Default,
+    C: Comparator<K> + Default,
+    T: BuildHasher,
+{
+    fn from(other: StdHashMap<K, V, T>) -> HashMap<K, V, S, C, INIT> {
+        let mut map = HashMap::default();
+        map.reserve(other.len());
+        map.extend(other);
+        map
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> From<BTreeMap<K, V>> for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher + Default,
+    C: Comparator<K> + Default,
+{
+    fn from(other: BTreeMap<K, V>) -> HashMap<K, V, S, C, INIT> {
+        let mut map = HashMap::default();
+        map.reserve(other.len());
+        map.extend(other);
+        map
+    }
+}
+
+impl<K, V, S, C, T, const INIT: usize> From<HashMap<K, V, S, C, INIT>> for StdHashMap<K, V, T>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    T: BuildHasher + Default,
+{
+    fn from(other: HashMap<K, V, S, C, INIT>) -> StdHashMap<K, V, T> {
+        let mut map = StdHashMap::with_capacity_and_hasher(other.len(), Default::default());
+        map.extend(other);
+        map
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> From<HashMap<K, V, S, C, INIT>> for BTreeMap<K, V>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+{
+    fn from(other: HashMap<K, V, S, C, INIT>) -> BTreeMap<K, V> {
+        other.into_iter().collect()
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Clone for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+    C: Comparator<K> + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut map = HashMap::new_in(
+            self.hash_builder.clone(),
+            self.comparator.clone(),
+            self.tracks_insertion_order(),
+        );
+        map.auto_shrink = self.auto_shrink;
+        map.reserve(self.len());
+        if self.tracks_insertion_order() {
+            for (k, v) in self.iter_insertion_order() {
+                map.insert(k.clone(), v.clone());
+            }
+            return map;
+        }
+        for (k, v) in self.iter() {
+            map.insert(k.clone(), v.clone());
+        }
+        map
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> PartialEq for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    fn eq(&self, other: &HashMap<K, V, S, C, INIT>) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        self.iter()
+            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> Eq for HashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    V: Eq,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+}
+
+/// The hash is independent of the bucket layout: each `(key, value)` pair is hashed on its own
+/// with a fixed-key hasher and the results are folded with a commutative sum, so maps which
+/// compare equal hash the same.
+///
+/// Only maps ordered by `OrdComparator` are `Hash`: a custom comparator may treat keys as equal
+/// which `K::hash` tells apart, and those maps would compare equal but hash differently.
+impl<K, V, S, const INIT: usize> Hash for HashMap<K, V, S, OrdComparator, INIT>
+where
+    K: Ord + Hash,
+    V: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut sum = 0u64;
+        for (k, v) in self.iter() {
+            let mut hasher = fnv::FnvHasher::default();
+            k.hash(&mut hasher);
+            v.hash(&mut hasher);
+            sum = sum.wrapping_add(hasher.finish());
+        }
+        state.write_usize(self.len());
+        state.write_u64(sum);
+    }
+}
+
+/// A `HashMap` with temporarily reserved capacity, which shrinks the map to fit when dropped.
+///
+/// This `struct` is created by the [`reserve_scoped`] method on [`HashMap`], and dereferences
+/// to it. See its documentation for more.
+///
+/// [`reserve_scoped`]: struct.HashMap.html#method.reserve_scoped
+/// [`HashMap`]: struct.HashMap.html
+pub struct ReserveGuard<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a + Ord + Hash,
+    V: 'a,
+    S: 'a + BuildHasher,
+    C: 'a + Comparator<K>,
+{
+    map: &'a mut HashMap<K, V, S, C, INIT>,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Deref for ReserveGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    type Target = HashMap<K, V, S, C, INIT>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S, C, INIT> {
+        self.map
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> DerefMut for ReserveGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut HashMap<K, V, S, C, INIT> {
+        self.map
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Drop for ReserveGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    fn drop(&mut self) {
+        self.map.shrink_to_fit();
+    }
+}
+
+/// A `HashMap` whose index array doesn't grow on insert, and is resized to fit once when the
+/// guard is dropped.
+///
+/// This `struct` is created by the [`defer_rehash`] method on [`HashMap`], and dereferences
+/// to it. See its documentation for more.
+///
+/// [`defer_rehash`]: struct.HashMap.html#method.defer_rehash
+/// [`HashMap`]: struct.HashMap.html
+pub struct RehashGuard<'a, K, V, S, C = OrdComparator, const INIT: usize = 8>
+where
+    K: 'a + Ord + Hash,
+    V: 'a,
+    S: 'a + BuildHasher,
+    C: 'a + Comparator<K>,
+{
+    map: &'a mut HashMap<K, V, S, C, INIT>,
+    deferred_before: bool,
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Deref for RehashGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    type Target = HashMap<K, V, S, C, INIT>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S, C, INIT> {
+        self.map
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> DerefMut for RehashGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut HashMap<K, V, S, C, INIT> {
+        self.map
+    }
+}
+
+impl<'a, K, V, S, C, const INIT: usize> Drop for RehashGuard<'a, K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    fn drop(&mut self) {
+        // a nested guard leaves the resize to the outer one
+        self.map.rehash_deferred = self.deferred_before;
+        let len = self.map.len();
+        self.map.auto_rehash(len);
+    }
+}
+
+/// A read-only `HashMap`, created by [`freeze`].
+///
+/// Nothing is mutated through a shared reference of `HashMap`, so once no `&mut` access is
+/// possible, the raw pointers inside can be read from many threads at once. `FrozenHashMap`
+/// is `Sync` when `K`, `V`, the hasher and the comparator are, and only exposes the reading
+/// part of the API. [`thaw`] gives the `HashMap` back.
+///
+/// [`freeze`]: struct.HashMap.html#method.freeze
+/// [`thaw`]: #method.thaw
+pub struct FrozenHashMap<K, V, S = RandomState, C = OrdComparator, const INIT: usize = 8> {
+    map: HashMap<K, V, S, C, INIT>,
+}
+
+unsafe impl<K: Send, V: Send, S: Send, C: Send, const INIT: usize> Send
+    for FrozenHashMap<K, V, S, C, INIT>
+{
+}
+
+unsafe impl<K: Sync, V: Sync, S: Sync, C: Sync, const INIT: usize> Sync
+    for FrozenHashMap<K, V, S, C, INIT>
+{
+}
+
+impl<K, V, S, C, const INIT: usize> FrozenHashMap<K, V, S, C, INIT> {
+    /// Returns the number of elements in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if the map contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[inline]
+    pub fn iter(&self) -> Iter<K, V, S, C, INIT> {
+        self.map.iter()
+    }
+
+    /// Turns the view back into a mutable `HashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::hash_map::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// let mut map = map.freeze().thaw();
+    /// map.insert(2, "b");
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    #[inline]
+    pub fn thaw(self) -> HashMap<K, V, S, C, INIT> {
+        self.map
+    }
+}
+
+impl<K, V, S, C, const INIT: usize> FrozenHashMap<K, V, S, C, INIT>
+where
+    K: Ord + Hash,
+    S: BuildHasher,
+    C: Comparator<K>,
+{
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get<Q: ?Sized>(&self, q: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        self.map.get(q)
+    }
+
+    /// Returns true if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q: ?Sized>(&self, q: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+        C: Comparator<Q>,
+    {
+        self.map.contains_key(q)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+
+    use hash_map::HashMap;
+    use std::cell::RefCell;
+    use hash_map::Entry::*;
+
+    #[test]
+    fn test_hash_map() {
+        let mut m = HashMap::new();
+        for i in 100..200 {
+            m.insert(i, -i);
+        }
+        assert!(m.contains_key(&100));
+        assert_eq!(m[&111], -111);
+        assert_eq!(m.len(), 100);
+        let mut a = m.first();
+        let mut cnt = 0;
+        while !a.is_null() {
+            cnt += 1;
+            a = m.next(a);
+        }
+        assert_eq!(cnt, m.len());
+        let mut a = m.last();
+        let mut cnt = 0;
+        while !a.is_null() {
+            cnt += 1;
+            a = m.prev(a);
+        }
+        assert_eq!(cnt, m.len());
+        assert_eq!(*m.get(&111).unwrap(), -111);
+        {
+            let v = m.get_mut(&111).unwrap();
+            *v *= -1;
+        }
+        assert_eq!(*m.get(&111).unwrap(), 111);
+        assert!(m.get(&-100).is_none());
+    }
+
+    #[test]
+    fn test_hash_map_remove() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let test_num = 199;
+        let mut map = HashMap::new();
+        for i in 0..test_num {
+            map.insert(i, Node { b: &cnt });
+        }
+        assert_eq!(*cnt.borrow(), 0);
+        for i in 0..test_num / 2 {
+            map.remove(&i);
+        }
+        assert_eq!(*cnt.borrow(), test_num / 2);
+        for i in test_num / 2..test_num {
+            map.insert(i, Node { b: &cnt });
+        }
+        assert_eq!(*cnt.borrow(), test_num);
+    }
+
+    #[test]
+    fn test_hash_map_clear() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let test_num = 199;
+        let mut map = HashMap::new();
+        for i in 0..test_num {
+            map.insert(i, Node { b: &cnt });
+        }
+        assert_eq!(*cnt.borrow(), 0);
+        for i in 0..test_num / 2 {
+            map.remove(&i);
+        }
+        assert_eq!(*cnt.borrow(), test_num / 2);
+        map.clear();
+        assert_eq!(*cnt.borrow(), test_num);
+    }
+
+    #[test]
+    fn test_hash_map_insert_duplicate() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let test_num = 100;
+        let mut map = HashMap::new();
+        for i in 0..test_num {
+            map.insert(i, Node { b: &cnt });
+        }
+        assert_eq!(test_num as usize, map.len());
+        assert_eq!(*cnt.borrow(), 0);
+        for i in 0..test_num / 2 {
+            map.insert(i, Node { b: &cnt });
+        }
+        assert_eq!(*cnt.borrow(), test_num / 2);
+    }
+
+    #[test]
+    fn test_hash_map_keys() {
+        let test_num = 100;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        let mut sum = 0;
+        for key in m.keys() {
+            sum += *key;
+        }
+        assert_eq!(sum, test_num * (test_num - 1) / 2);
+    }
+
+    #[test]
+    fn test_hash_map_values() {
+        let test_num = 100;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        let mut sum = 0;
+        for value in m.values() {
+            sum += *value;
+        }
+        assert_eq!(sum, -test_num * (test_num - 1) / 2);
+    }
+
+    #[test]
+    fn test_hash_map_values_mut() {
+        let test_num = 100;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        let mut sum = 0;
+        for value in m.values_mut() {
+            *value *= 2;
+        }
+        for value in m.values() {
+            sum += *value;
+        }
+        assert_eq!(sum, -test_num * (test_num - 1));
+    }
+
+    #[test]
+    fn test_hash_map_iter() {
+        let test_num = 100;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        let mut sum = 0;
+        let mut sum1 = 0;
+        for value in m.iter() {
+            sum += *value.0;
+            sum1 += *value.1;
+        }
+        assert_eq!(sum, test_num * (test_num - 1) / 2);
+        assert_eq!(sum1, -test_num * (test_num - 1) / 2);
+    }
+
+    #[test]
+    fn test_hash_map_iter_mut() {
+        let test_num = 100;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        for (_, value) in m.iter_mut() {
+            *value *= 2;
+        }
+        let mut sum = 0;
+        let mut sum1 = 0;
+        for value in m.iter() {
+            sum += *value.0;
+            sum1 += *value.1;
+        }
+        assert_eq!(sum, test_num * (test_num - 1) / 2);
+        assert_eq!(sum1, -test_num * (test_num - 1));
+    }
+
+    #[test]
+    fn test_hash_map_insertion_order() {
+        let test_num = 1000;
+        let mut keys: Vec<i32> = (0..test_num).collect();
+        for idx in 0..keys.len() {
+            let pos = rand::random::<usize>() % (idx + 1);
+            keys.swap(idx, pos);
+        }
+        let mut m = HashMap::with_insertion_order();
+        assert!(m.tracks_insertion_order());
+        for &k in &keys {
+            m.insert(k, -k);
+        }
+        assert!(m.iter_insertion_order().map(|(k, _)| *k).eq(keys.iter().cloned()));
+        assert!(m.iter_insertion_order().rev().map(|(k, _)| *k).eq(keys.iter().rev().cloned()));
+
+        // replacing keeps the position, removing and inserting again moves to the end
+        m.insert(keys[0], 1);
+        *m.entry(keys[1]).or_insert(0) = 2;
+        assert_eq!(m.remove(&keys[2]), Some((keys[2], -keys[2])));
+        m.entry(keys[2]).or_insert(3);
+        let mut expect = keys.clone();
+        expect.remove(2);
+        expect.push(keys[2]);
+        assert!(m.iter_insertion_order().map(|(k, _)| *k).eq(expect.iter().cloned()));
+        assert_eq!(m[&keys[0]], 1);
+        assert_eq!(m[&keys[1]], 2);
+
+        let c = m.clone();
+        assert!(c.iter_insertion_order().map(|(k, _)| *k).eq(expect.iter().cloned()));
+
+        for &k in &expect[10..] {
+            m.remove(&k);
+        }
+        m.shrink_to_fit();
+        assert!(m.iter_insertion_order().map(|(k, _)| *k).eq(expect[..10].iter().cloned()));
+        let drained: Vec<_> = m.drain().collect();
+        assert_eq!(drained.len(), 10);
+        assert_eq!(m.iter_insertion_order().count(), 0);
+        m.insert(7, 7);
+        assert!(m.iter_insertion_order().map(|(k, _)| *k).eq(Some(7)));
+        m.clear();
+        assert_eq!(m.iter_insertion_order().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hash_map_insertion_order_untracked() {
+        let m: HashMap<i32, i32> = HashMap::new();
+        assert!(!m.tracks_insertion_order());
+        m.iter_insertion_order();
+    }
+
+    #[test]
+    fn test_hash_map_with_capacity_inline() {
+        use fastbin;
+
+        let mut m = HashMap::with_capacity_inline();
+        assert!(m.is_index_inline());
+        // (8 * 4 + 3) / 6, the inline slots at the maximum load factor
+        assert_eq!(m.capacity(), 5);
+        let mallocs = fastbin::malloc_count();
+        for i in 0..5 {
+            m.insert(i, i);
+            assert!(m.is_index_inline());
+        }
+        // only the first page of each fastbin, never an index array
+        assert_eq!(fastbin::malloc_count() - mallocs, 2);
+        assert_eq!(m.hash_table.heap_size(), 0);
+        assert_eq!(m.capacity(), 5);
+
+        // the 6th insert allocates the first index array
+        m.insert(5, 5);
+        assert_eq!(fastbin::malloc_count() - mallocs, 3);
+        assert!(!m.is_index_inline());
+        assert!(m.hash_table.heap_size() > 0);
+        assert!(m.capacity() > 5);
+        for i in 0..6 {
+            assert_eq!(m[&i], i);
+        }
+    }
+
+    #[test]
+    fn test_hash_map_insert_and_get_mut() {
+        let mut m = HashMap::new();
+        {
+            let (old, v) = m.insert_and_get_mut(1, 1);
+            assert_eq!(old, None);
+            *v = 5;
+        }
+        assert_eq!(m.get(&1), Some(&5));
+        {
+            let (old, v) = m.insert_and_get_mut(1, 7);
+            assert_eq!(old, Some(5));
+            assert_eq!(*v, 7);
+            *v += 1;
+        }
+        assert_eq!(m.get(&1), Some(&8));
+        assert_eq!(m.len(), 1);
+        for i in 2..100 {
+            let p = m.insert_and_get_mut(i, i).1 as *mut i32;
+            assert_eq!(p as *const i32, m.get(&i).unwrap() as *const i32);
+        }
+    }
+
+    #[test]
+    fn test_hash_map_get_disjoint_mut() {
+        let mut m = HashMap::new();
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        {
+            let [a, b, c, d] = m.get_disjoint_mut([&3, &100, &7, &-1]);
+            *a.unwrap() += 100;
+            assert!(b.is_none());
+            *c.unwrap() += 100;
+            assert!(d.is_none());
+        }
+        assert_eq!(m[&3], 103);
+        assert_eq!(m[&7], 107);
+        let [a, b] = m.get_disjoint_mut([&200, &200]);
+        assert!(a.is_none() && b.is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hash_map_get_disjoint_mut_duplicate() {
+        let mut m = HashMap::new();
+        m.insert(1, 1);
+        m.insert(2, 2);
+        let _ = m.get_disjoint_mut([&1, &2, &1]);
+    }
+
+    #[test]
+    fn test_hash_map_iter_sorted() {
+        let test_num = 1000;
+        let mut m = HashMap::new();
+        for i in (0..test_num).rev() {
+            m.insert(i, -i);
+        }
+        let mut prev = None;
+        let mut cnt = 0;
+        for (k, v) in m.iter_sorted() {
+            assert_eq!(*v, -*k);
+            if let Some(p) = prev {
+                assert!(p < *k);
+            }
+            prev = Some(*k);
+            cnt += 1;
+        }
+        assert_eq!(cnt, test_num);
+    }
+
+    #[test]
+    fn test_hash_map_iter_mut_sorted() {
+        let build = || {
+            let mut m = HashMap::new();
+            for i in (0..1000).map(|i| i * 7919 % 1000) {
+                m.insert(i, i);
+            }
+            m
+        };
+        let mut m = build();
+        let mut prev = None;
+        let mut total = 0;
+        for (k, v) in m.iter_mut_sorted() {
+            if let Some(p) = prev {
+                assert!(p < *k);
+            }
+            prev = Some(*k);
+            total += *v;
+            *v = total;
+        }
+        for i in 0..1000 {
+            assert_eq!(m[&i], i * (i + 1) / 2);
+        }
+
+        // every reference points at a distinct value
+        let mut addrs: Vec<usize> = m
+            .iter_mut_sorted()
+            .map(|(_, v)| v as *mut i32 as usize)
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        assert_eq!(addrs.len(), 1000);
+
+        let mut other = build();
+        let mut total = 0;
+        for (_, v) in other.iter_mut_sorted().rev() {
+            total += *v;
+            *v = total;
+        }
+        assert_eq!(other[&999], 999);
+        assert_eq!(other[&0], (0..1000).sum::<i32>());
+        assert_eq!(other.iter_mut_sorted().len(), 1000);
+    }
+
+    #[test]
+    fn test_hash_map_drain_sorted() {
+        let drop_cnt = RefCell::new(0);
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+
+        let mut m = HashMap::new();
+        for i in (0..1000).map(|i| i * 7919 % 1000) {
+            m.insert(i, -i);
+        }
+        let (capacity, memory) = (m.capacity(), m.memory_usage());
+        let drained: Vec<(i32, i32)> = m.drain_sorted().collect();
+        assert_eq!(drained, (0..1000).map(|i| (i, -i)).collect::<Vec<_>>());
+        assert!(m.is_empty());
+        assert_eq!(m.capacity(), capacity);
+        assert_eq!(m.memory_usage(), memory);
+
+        // refill the kept table
+        for i in 0..1000 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.memory_usage(), memory);
+
+        let mut m = HashMap::new();
+        for i in 0..100 {
+            m.insert(i, Node { b: &drop_cnt });
+        }
+        {
+            let mut drain = m.drain_sorted();
+            assert_eq!(drain.len(), 100);
+            let firsts: Vec<i32> = drain.by_ref().take(3).map(|(k, _)| k).collect();
+            assert_eq!(firsts, [0, 1, 2]);
+            assert_eq!(drain.next_back().map(|(k, _)| k), Some(99));
+        }
+        assert_eq!(*drop_cnt.borrow(), 100);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_keys_sorted() {
+        let mut m = HashMap::new();
+        for i in (0..1000).map(|i| i * 7919 % 1000) {
+            m.insert(format!("{:04}", i), vec![i; 16]);
+        }
+        let expect: Vec<&String> = m.iter_sorted().map(|(k, _)| k).collect();
+        assert_eq!(m.keys_to_vec_sorted(), expect);
+        let owned: Vec<String> = expect.into_iter().cloned().collect();
+        assert_eq!(m.clone().into_keys_sorted(), owned);
+        assert_eq!(m.len(), 1000);
+
+        let m: HashMap<i32, i32> = HashMap::new();
+        assert!(m.keys_to_vec_sorted().is_empty());
+        assert!(m.into_keys_sorted().is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_into_iter_double_ended() {
+        use std::collections::HashSet;
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let drop_cnt = RefCell::new(0);
+        {
+            let mut m = HashMap::new();
+            for i in 0..100 {
+                m.insert(i, Node { b: &drop_cnt });
+            }
+            let mut iter = m.into_iter();
+            let mut seen = HashSet::new();
+            for step in 0..60 {
+                let kv = if step % 2 == 0 {
+                    iter.next()
+                } else {
+                    iter.next_back()
+                };
+                let (k, _) = kv.unwrap();
+                assert!(seen.insert(k));
+                assert_eq!(iter.len(), 100 - seen.len());
+                assert_eq!(iter.iter().count(), iter.len());
+            }
+            assert_eq!(*drop_cnt.borrow(), 60);
+            for _ in 0..10 {
+                let (k, _) = iter.next_back().unwrap();
+                assert!(seen.insert(k));
+            }
+            for (k, _) in iter.by_ref() {
+                assert!(seen.insert(k));
+            }
+            assert!(iter.next_back().is_none());
+            assert_eq!(seen.len(), 100);
+        }
+        assert_eq!(*drop_cnt.borrow(), 100);
+        {
+            let mut m = HashMap::new();
+            for i in 0..100 {
+                m.insert(i, Node { b: &drop_cnt });
+            }
+            let mut iter = m.into_iter();
+            iter.next();
+            iter.next_back();
+        }
+        assert_eq!(*drop_cnt.borrow(), 200);
+    }
+
+    #[test]
+    fn test_hash_map_into_iter() {
+        let test_num = 100;
+        let mut a = HashMap::new();
+        let mut b = HashMap::new();
+        for i in 0..test_num as i32 {
+            a.insert(i, -i);
+        }
+        for i in 0..test_num as i32 {
+            b.insert(-i, i);
+        }
+        assert_eq!(a.len(), test_num);
+        assert_eq!(b.len(), test_num);
+        a.extend(b.into_iter());
+        assert_eq!(a.len(), test_num * 2 - 1);
+    }
+
+    #[test]
+    fn test_hash_map_shrink_to_fit() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let test_num = 200;
+        let mut map = HashMap::new();
+        for i in 0..test_num {
+            map.insert(i, Node { b: &cnt });
+        }
+        for i in 10..test_num {
+            map.remove(&i);
+        }
+        assert_eq!(*cnt.borrow(), test_num - 10);
+        assert!(map.capacity() >= test_num as usize);
+        map.shrink_to_fit();
+        assert!(map.capacity() < test_num as usize);
+        assert!(map.capacity() >= 10);
+        assert_eq!(*cnt.borrow(), test_num - 10);
+        drop(map);
+        assert_eq!(*cnt.borrow(), test_num);
+    }
+
+    #[test]
+    fn test_hash_map_shrink_to_fit_reuse_hash() {
+        use std::cell::Cell;
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasher;
+        use std::rc::Rc;
+        #[derive(Clone)]
+        struct CountingState {
+            cnt: Rc<Cell<usize>>,
+        }
+        impl BuildHasher for CountingState {
+            type Hasher = DefaultHasher;
+            fn build_hasher(&self) -> DefaultHasher {
+                self.cnt.set(self.cnt.get() + 1);
+                DefaultHasher::new()
+            }
+        }
+        let cnt = Rc::new(Cell::new(0));
+        for &ordered in &[false, true] {
+            let state = CountingState { cnt: cnt.clone() };
+            let mut map = if ordered {
+                HashMap::with_insertion_order_and_hasher(state)
+            } else {
+                HashMap::with_hasher(state)
+            };
+            for i in 0..1000 {
+                map.insert(i, i);
+            }
+            for i in 10..1000 {
+                map.remove(&i);
+            }
+            let old_cap = map.capacity();
+            let hash_cnt = cnt.get();
+            map.shrink_to_fit();
+            assert!(map.capacity() < old_cap);
+            assert_eq!(cnt.get(), hash_cnt);
+            for i in 0..10 {
+                assert_eq!(map[&i], i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_map_retain() {
+        let drop_cnt = RefCell::new(0);
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+            v: i32,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let mut m = HashMap::new();
+        for i in 0..100 {
+            m.insert(i, Node { b: &drop_cnt, v: i });
+        }
+        m.retain(|k, v| {
+            v.v += 1;
+            k % 3 == 0
+        });
+        assert_eq!(m.len(), 34);
+        assert_eq!(*drop_cnt.borrow(), 66);
+        for (k, v) in m.iter() {
+            assert_eq!(k % 3, 0);
+            assert_eq!(v.v, k + 1);
+        }
+    }
+
+    #[test]
+    fn test_hash_map_retain_keys_zst() {
+        let mut m: HashMap<i32, ()> = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, ());
+        }
+        m.retain_keys(|k| k % 2 == 0);
+        assert_eq!(m.len(), 500);
+        for i in 0..1000 {
+            assert_eq!(m.contains_key(&i), i % 2 == 0);
+        }
+        assert_eq!(m.iter().count(), 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn test_hash_map_reserve_overflow() {
+        let mut m: HashMap<i32, i32> = HashMap::new();
+        m.insert(1, 1);
+        m.reserve(usize::MAX / 2);
+    }
+
+    #[test]
+    fn test_hash_map_reserve_huge() {
+        use std::panic;
+        for &cap in &[
+            usize::MAX,
+            usize::MAX / 2,
+            usize::MAX / 6 + 1,
+            usize::MAX / 16,
+        ] {
+            let mut m: HashMap<i32, i32> = HashMap::new();
+            m.insert(1, 1);
+            let old_cap = m.capacity();
+            let res = panic::catch_unwind(panic::AssertUnwindSafe(|| m.reserve(cap)));
+            assert!(res.is_err());
+            // no wrapped allocation happened, the map keeps its old index
+            assert_eq!(m.capacity(), old_cap);
+            assert_eq!(m[&1], 1);
+        }
+    }
+
+    #[test]
+    fn test_hash_map_first_last_key_value() {
+        let mut m = HashMap::new();
+        assert_eq!(m.first_key_value(), None);
+        assert_eq!(m.last_key_value(), None);
+        for i in 0..100 {
+            m.insert(i, -i);
+            assert_eq!(m.first_key_value(), m.iter().next());
+            assert_eq!(m.last_key_value(), m.iter().last());
+        }
+        for i in 0..100 {
+            m.remove(&i);
+            assert_eq!(m.first_key_value(), m.iter().next());
+            assert_eq!(m.last_key_value(), m.iter().last());
+        }
+    }
+
+    /// A BuildHasher which maps every key into the same HashIndex.
+    #[derive(Clone, Default)]
+    struct CollisionState;
+
+    impl ::std::hash::BuildHasher for CollisionState {
+        type Hasher = CollisionHasher;
+        fn build_hasher(&self) -> CollisionHasher {
+            CollisionHasher
+        }
+    }
+
+    struct CollisionHasher;
+
+    impl ::std::hash::Hasher for CollisionHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    /// A BuildHasher for u64 keys which hashes each key `k` to `f(k)`, to lay out buckets by
+    /// hand.
+    #[derive(Clone)]
+    struct FnState(fn(u64) -> u64);
+
+    impl ::std::hash::BuildHasher for FnState {
+        type Hasher = FnHasher;
+        fn build_hasher(&self) -> FnHasher {
+            FnHasher(self.0, 0)
+        }
+    }
+
+    struct FnHasher(fn(u64) -> u64, u64);
+
+    impl ::std::hash::Hasher for FnHasher {
+        fn finish(&self) -> u64 {
+            (self.0)(self.1)
+        }
+        fn write(&mut self, _: &[u8]) {
+            unreachable!()
+        }
+        fn write_u64(&mut self, i: u64) {
+            self.1 = i;
+        }
+    }
+
+    #[test]
+    fn test_hash_map_probe_cost() {
+        let test_num = 1024;
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..test_num {
+            m.insert(i, i);
+        }
+        let log2 = 10;
+        let height = m.iter_with_layout().map(|(_, _, _, depth)| depth).max().unwrap() + 1;
+        assert!(height <= log2 * 3 / 2);
+        let mut total = 0;
+        for i in 0..test_num {
+            let cost = m.probe_cost(&i);
+            assert!(1 <= cost && cost <= height);
+            total += cost;
+        }
+        let mean = total / test_num as usize;
+        assert!(log2 - 2 <= mean && mean <= log2 + 2);
+        // a missing key descends to a leaf, which is at least half the height deep in an AVL
+        let cost = m.probe_cost(&-1);
+        assert!(height / 2 <= cost && cost <= height);
+
+        // spread over the buckets, lookups cost about one comparison
+        let m: HashMap<i32, i32> = (0..test_num).map(|i| (i, i)).collect();
+        let total: usize = (0..test_num).map(|i| m.probe_cost(&i)).sum();
+        assert!(total < test_num as usize * 2);
+    }
+
+    #[test]
+    fn test_hash_map_drop_single_bucket() {
+        let drop_cnt = RefCell::new(0);
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let test_num = 50000;
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..test_num {
+            m.insert(i, Node { b: &drop_cnt });
+        }
+        assert_eq!(m.get_max_node_of_single_index(), test_num);
+        m.clear();
+        assert_eq!(*drop_cnt.borrow(), test_num);
+        assert!(m.is_empty());
+        for i in 0..test_num {
+            m.insert(i, Node { b: &drop_cnt });
+        }
+        drop(m);
+        assert_eq!(*drop_cnt.borrow(), test_num * 2);
+    }
+
+    #[test]
+    fn test_hash_map_rehash_single_bucket() {
+        let test_num = 50000;
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..test_num {
+            m.insert(i, -i);
+        }
+        let old_cap = m.capacity();
+        m.reserve(old_cap * 4);
+        assert!(m.capacity() > old_cap);
+        assert_eq!(m.len(), test_num as usize);
+        assert_eq!(m.get_max_node_of_single_index(), test_num);
+        for i in 0..test_num {
+            assert_eq!(m[&i], -i);
+        }
+        for i in 0..test_num / 2 {
+            m.remove(&i);
+        }
+        m.shrink_to_fit();
+        assert!(m.iter().map(|(k, _)| *k).eq(test_num / 2..test_num));
+    }
+
+    #[test]
+    fn test_hash_map_comparator() {
+        use std::cmp::Ordering;
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{BuildHasher, Hasher};
+        // hashes ascii case-insensitively, to be consistent with the comparator below
+        #[derive(Clone, Default)]
+        struct LowercaseState;
+        struct LowercaseHasher(DefaultHasher);
+        impl BuildHasher for LowercaseState {
+            type Hasher = LowercaseHasher;
+            fn build_hasher(&self) -> LowercaseHasher {
+                LowercaseHasher(DefaultHasher::new())
+            }
+        }
+        impl Hasher for LowercaseHasher {
+            fn finish(&self) -> u64 {
+                self.0.finish()
+            }
+            fn write(&mut self, bytes: &[u8]) {
+                for b in bytes {
+                    self.0.write_u8(b.to_ascii_lowercase());
+                }
+            }
+        }
+        fn case_insensitive(a: &str, b: &str) -> Ordering {
+            a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase())
+        }
+
+        let mut m = HashMap::with_hasher_and_comparator(
+            LowercaseState,
+            |a: &String, b: &String| case_insensitive(a, b),
+        );
+        assert_eq!(m.insert("Foo".to_string(), 1), None);
+        assert_eq!(m.insert("foo".to_string(), 2), Some(("Foo".to_string(), 1)));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&"FOO".to_string()), Some(&2));
+        *m.entry("fOO".to_string()).or_insert(0) += 1;
+        assert_eq!(m[&"foo".to_string()], 3);
+        for i in 0..100 {
+            m.insert(format!("Key{}", i), i);
+        }
+        for i in 0..100 {
+            assert_eq!(m.remove(&format!("kEY{}", i)), Some((format!("Key{}", i), i)));
+        }
+        assert_eq!(m.len(), 1);
+        let c = m.clone();
+        assert!(c == m);
+        assert!(c.contains_key(&"foo".to_string()));
+
+        // the same key type ordered by the default comparator keeps them apart
+        let mut d = HashMap::with_hasher(LowercaseState);
+        d.insert("Foo".to_string(), 1);
+        d.insert("foo".to_string(), 2);
+        assert_eq!(d.len(), 2);
+    }
+
+    fn check_capacity_invariant<K, V, S, C>(m: &HashMap<K, V, S, C>) {
+        assert!(m.len() <= m.capacity());
+        assert!(m.capacity() < m.hash_table.index_size());
+    }
+
+    #[test]
+    fn test_hash_map_capacity() {
+        let mut m = HashMap::new();
+        for round in 0..4 {
+            for i in 0..1000 {
+                m.insert(i, i);
+                check_capacity_invariant(&m);
+            }
+            for i in 0..(1000 - round * 300) {
+                m.remove(&i);
+                check_capacity_invariant(&m);
+            }
+            m.shrink_to_fit();
+            check_capacity_invariant(&m);
+        }
+
+        // capacity is exact: filling it up doesn't resize, one more element does
+        let mut m = HashMap::new();
+        let mut i = 0;
+        for _ in 0..10 {
+            let cap = m.capacity();
+            while m.len() < cap {
+                m.insert(i, i);
+                i += 1;
+            }
+            assert_eq!(m.capacity(), cap);
+            m.entry(i).or_insert(i);
+            i += 1;
+            assert!(m.capacity() > cap);
+            check_capacity_invariant(&m);
+        }
+    }
+
+    #[test]
+    fn test_hash_map_raw_entry() {
+        use std::hash::BuildHasher;
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, -i);
+        }
+        for i in 0..1000 {
+            let hash = m.hasher().hash_one(i);
+            assert_eq!(m.raw_entry().from_key(&i), Some((&i, &-i)));
+            assert_eq!(m.raw_entry().from_key_hashed_nocheck(hash, &i), Some((&i, &-i)));
+            assert_eq!(m.raw_entry().from_hash(hash, |k| *k == i), Some((&i, &-i)));
+            assert_eq!(m.raw_entry().from_hash(hash, |_| false), None);
+            assert_eq!(m.raw_entry().from_key_hashed_nocheck(hash ^ 1, &i), None);
+        }
+        assert_eq!(m.raw_entry().from_key(&1000), None);
+
+        // every key shares one hash value
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        for i in 0..100 {
+            assert_eq!(m.raw_entry().from_hash(0, |k| *k == i), Some((&i, &i)));
+            assert_eq!(m.raw_entry().from_key_hashed_nocheck(0, &i), Some((&i, &i)));
+        }
+        assert_eq!(m.raw_entry().from_hash(1, |_| true), None);
+    }
+
+    #[test]
+    fn test_hash_map_from_std() {
+        use std::collections::BTreeMap;
+        use std::collections::HashMap as StdHashMap;
+        let std_map: StdHashMap<i32, String> = (0..1000).map(|i| (i, i.to_string())).collect();
+        let m: HashMap<_, _> = HashMap::from(std_map.clone());
+        assert_eq!(m.len(), std_map.len());
+        for (k, v) in &std_map {
+            assert_eq!(&m[k], v);
+        }
+        let back: StdHashMap<_, _> = m.into();
+        assert_eq!(back, std_map);
+
+        let btree: BTreeMap<i32, String> = (0..1000).map(|i| (-i, i.to_string())).collect();
+        let m: HashMap<_, _> = btree.clone().into();
+        assert_eq!(m.len(), btree.len());
+        for (k, v) in &btree {
+            assert_eq!(&m[k], v);
+        }
+        let back = BTreeMap::from(m);
+        assert_eq!(back, btree);
+    }
+
+    #[test]
+    fn test_hash_map_iter_mut_where() {
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, i);
+        }
+        let mut cnt = 0;
+        for (k, v) in m.iter_mut_where(|k| k % 2 == 0) {
+            assert_eq!(k % 2, 0);
+            *v = -*v;
+            cnt += 1;
+        }
+        assert_eq!(cnt, 500);
+        for (k, v) in m.iter() {
+            if k % 2 == 0 {
+                assert_eq!(*v, -k);
+            } else {
+                assert_eq!(v, k);
+            }
+        }
+        assert_eq!(m.iter_mut_where(|_| false).count(), 0);
+    }
+
+    #[test]
+    fn test_hash_map_remove_take() {
+        use std::cmp::Ordering;
+        use std::hash::{Hash, Hasher};
+
+        #[derive(Debug)]
+        struct Key {
+            id: i32,
+            payload: &'static str,
+        }
+        impl PartialEq for Key {
+            fn eq(&self, other: &Key) -> bool {
+                self.id == other.id
+            }
+        }
+        impl Eq for Key {}
+        impl PartialOrd for Key {
+            fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Key {
+            fn cmp(&self, other: &Key) -> Ordering {
+                self.id.cmp(&other.id)
+            }
+        }
+        impl Hash for Key {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+            }
+        }
+
+        let mut m = HashMap::new();
+        m.insert(Key { id: 1, payload: "stored-1" }, 10);
+        m.insert(Key { id: 2, payload: "stored-2" }, 20);
+
+        let (k, v) = m.remove_take(&Key { id: 1, payload: "probe" }).unwrap();
+        assert_eq!(k.payload, "stored-1");
+        assert_eq!(v, 10);
+        assert!(m.remove_take(&Key { id: 1, payload: "probe" }).is_none());
+
+        let k = m.take(&Key { id: 2, payload: "probe" }).unwrap();
+        assert_eq!(k.payload, "stored-2");
+        assert!(m.take(&Key { id: 2, payload: "probe" }).is_none());
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_resize_info() {
+        let mut m = HashMap::new();
+        let mut i = 0;
+        loop {
+            let info = m.resize_info();
+            assert_eq!(info.entries, m.len());
+            assert_eq!(info.index_size, m.hash_table.index_size());
+            if info.next_resize_at - info.entries == 1 {
+                break;
+            }
+            m.insert(i, i);
+            i += 1;
+        }
+        let info = m.resize_info();
+        m.insert(i, i);
+        assert_eq!(m.hash_table.index_size(), info.would_grow_to);
+        assert!(info.would_grow_to > info.index_size);
+        assert_eq!(m.resize_info().next_resize_at, m.capacity() + 1);
+    }
+
+    #[test]
+    fn test_hash_map_hash() {
+        use std::cmp::Ordering;
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashSet;
+        use std::hash::{Hash, Hasher};
+
+        fn hash_of<T: Hash>(t: &T) -> u64 {
+            let mut h = DefaultHasher::new();
+            t.hash(&mut h);
+            h.finish()
+        }
+
+        let mut a = HashMap::new();
+        for i in 0..1000 {
+            a.insert(i, -i);
+        }
+        let mut b = HashMap::with_capacity(5000);
+        for i in (0..1000).rev() {
+            b.insert(i, -i);
+        }
+        assert!(a == b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+
+        b.insert(0, 1);
+        assert_ne!(hash_of(&a), hash_of(&b));
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        b.insert(0, 0);
+        assert!(set.contains(&b));
+
+        // keys which are equal but differ in their bytes hash the same
+        #[derive(Eq)]
+        struct NoCase(String);
+        impl PartialEq for NoCase {
+            fn eq(&self, other: &NoCase) -> bool {
+                self.0.eq_ignore_ascii_case(&other.0)
+            }
+        }
+        impl PartialOrd for NoCase {
+            fn partial_cmp(&self, other: &NoCase) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for NoCase {
+            fn cmp(&self, other: &NoCase) -> Ordering {
+                self.0.to_ascii_lowercase().cmp(&other.0.to_ascii_lowercase())
+            }
+        }
+        impl Hash for NoCase {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.to_ascii_lowercase().hash(state);
+            }
+        }
+        let mut upper = HashMap::new();
+        upper.insert(NoCase("A".to_string()), 1);
+        let mut lower = HashMap::new();
+        lower.insert(NoCase("a".to_string()), 1);
+        assert!(upper == lower);
+        assert_eq!(hash_of(&upper), hash_of(&lower));
+    }
+
+    #[test]
+    fn test_hash_map_buckets() {
+        use std::collections::HashSet;
+
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, -i);
+        }
+        let mut positions = HashSet::new();
+        let mut from_buckets = Vec::new();
+        for bucket in m.buckets() {
+            assert!(bucket.index() < m.hash_table.index_size());
+            assert!(positions.insert(bucket.index()));
+            let mut cnt = 0;
+            for (k, v) in bucket {
+                from_buckets.push((*k, *v));
+                cnt += 1;
+            }
+            assert!(cnt > 0);
+        }
+        let mut from_iter: Vec<_> = m.iter().map(|(k, v)| (*k, *v)).collect();
+        from_buckets.sort();
+        from_iter.sort();
+        assert_eq!(from_buckets, from_iter);
+
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        let buckets: Vec<_> = m.buckets().collect();
+        assert_eq!(buckets.len(), 1);
+        let keys: Vec<_> = buckets[0].iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+
+        let m: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(m.buckets().count(), 0);
+    }
+
+    #[test]
+    fn test_hash_map_entry_with() {
+        use std::cell::Cell;
+        use std::borrow::Borrow;
+        use std::cmp::Ordering;
+        use std::hash::{Hash, Hasher};
+
+        // the owned key carries a suffix derived from the name, ignored by comparison
+        struct Key {
+            name: String,
+            suffix: String,
+        }
+        impl Borrow<str> for Key {
+            fn borrow(&self) -> &str {
+                &self.name
+            }
+        }
+        impl PartialEq for Key {
+            fn eq(&self, other: &Key) -> bool {
+                self.name == other.name
+            }
+        }
+        impl Eq for Key {}
+        impl PartialOrd for Key {
+            fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Key {
+            fn cmp(&self, other: &Key) -> Ordering {
+                self.name.cmp(&other.name)
+            }
+        }
+        impl Hash for Key {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.name.hash(state);
+            }
+        }
+
+        let calls = Cell::new(0);
+        let make_key = |q: &str| {
+            calls.set(calls.get() + 1);
+            Key {
+                name: q.to_owned(),
+                suffix: format!("{}-{}", q, q.len()),
+            }
+        };
+        let mut m = HashMap::new();
+        for i in 0..100 {
+            let name = (i % 10).to_string();
+            *m.entry_with(name.as_str(), &make_key).or_insert(0) += 1;
+        }
+        assert_eq!(calls.get(), 10);
+        assert_eq!(m.len(), 10);
+        for (k, v) in m.iter() {
+            assert_eq!(*v, 10);
+            assert_eq!(k.suffix, format!("{}-1", k.name));
+        }
+        match m.entry_with("3", &make_key) {
+            Occupied(e) => assert_eq!(e.remove(), 10),
+            Vacant(_) => unreachable!(),
+        }
+        assert_eq!(calls.get(), 10);
+        assert_eq!(m.get("3"), None);
+    }
+
+    #[test]
+    fn test_hash_map_rehash_to_index_size() {
+        let mut m = HashMap::new();
+        m.rehash_to_index_size(1024);
+        for i in 0..10 {
+            m.insert(i, -i);
+        }
+        assert_eq!(m.hash_table.index_size(), 1024);
+        assert_eq!(m.capacity(), (1024 * 4 + 3) / 6);
+        for i in 0..10 {
+            assert_eq!(m.get(&i), Some(&-i));
+        }
+
+        // never below what the entries need
+        m.rehash_to_index_size(3);
+        assert_eq!(m.hash_table.index_size(), 16);
+        check_capacity_invariant(&m);
+        for i in 0..10 {
+            assert_eq!(m.get(&i), Some(&-i));
+        }
+        for i in 10..100 {
+            m.insert(i, -i);
+            check_capacity_invariant(&m);
+        }
+        for i in 0..100 {
+            assert_eq!(m.get(&i), Some(&-i));
+        }
+    }
+
+    #[test]
+    fn test_hash_map_entry_peek() {
+        let mut m = HashMap::new();
+        for i in 0..100 {
+            m.insert(i, i * 2);
+        }
+        for i in 0..200 {
+            let e = m.entry(i);
+            if i < 100 {
+                assert!(e.is_occupied());
+                assert_eq!(e.get(), Some(&(i * 2)));
+            } else {
+                assert!(!e.is_occupied());
+                assert_eq!(e.get(), None);
+            }
+        }
+        // peeking does not insert anything
+        assert_eq!(m.len(), 100);
+        let e = m.entry(150);
+        if e.get().is_none() {
+            e.or_insert(7);
+        }
+        assert_eq!(m[&150], 7);
+    }
+
+    #[test]
+    fn test_hash_map_from_sorted_unchecked() {
+        let v = (0..10000).map(|i| (i, i * 3)).collect::<Vec<_>>();
+        let a: HashMap<i32, i32> = HashMap::from_sorted_unchecked(v.clone());
+        let b: HashMap<i32, i32> = v.iter().cloned().collect();
+        assert!(a == b);
+        check_capacity_invariant(&a);
+        assert!(a.hash_table.index_size() >= 10000);
+
+        let mut m: HashMap<i32, i32, CollisionState> =
+            HashMap::from_sorted_unchecked((0..100).rev().map(|i| (i, i)));
+        assert_eq!(m.keys().cloned().collect::<Vec<_>>(), (0..100).collect::<Vec<_>>());
+        m.insert(100, 100);
+        assert_eq!(m.remove(&50), Some((50, 50)));
+        assert_eq!(m.len(), 100);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "duplicate key")]
+    fn test_hash_map_from_sorted_unchecked_duplicate() {
+        let _: HashMap<i32, i32> = HashMap::from_sorted_unchecked(vec![(1, 1), (2, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn test_hash_map_drop_panic() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Bomb<'a> {
+            armed: bool,
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+                if self.armed {
+                    panic!("bomb");
+                }
+            }
+        }
+
+        let drop_cnt = RefCell::new(0);
+        // every key shares one bucket, so the panic interrupts the teardown of a deep tree
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..100 {
+            m.insert(
+                i,
+                Bomb {
+                    armed: i == 37,
+                    b: &drop_cnt,
+                },
+            );
+        }
+        for i in 100..200 {
+            m.insert(
+                i,
+                Bomb {
+                    armed: false,
+                    b: &drop_cnt,
+                },
+            );
+        }
+        let res = panic::catch_unwind(AssertUnwindSafe(|| m.clear()));
+        assert!(res.is_err());
+        // every value was dropped exactly once, and the counters agree with the contents
+        assert_eq!(*drop_cnt.borrow(), 200);
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.iter().count(), 0);
+
+        // the panic interrupts one bucket of many, with insertion order tracked
+        let mut m = HashMap::with_insertion_order();
+        for i in 0..100 {
+            m.insert(
+                i,
+                Bomb {
+                    armed: i == 37,
+                    b: &drop_cnt,
+                },
+            );
+        }
+        let res = panic::catch_unwind(AssertUnwindSafe(|| m.clear()));
+        assert!(res.is_err());
+        assert_eq!(*drop_cnt.borrow(), 300);
+        assert_eq!(m.iter_insertion_order().count(), 0);
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.iter().count(), 0);
+
+        for i in 0..10 {
+            m.insert(
+                i,
+                Bomb {
+                    armed: false,
+                    b: &drop_cnt,
+                },
+            );
+        }
+        assert_eq!(m.len(), 10);
+        drop(m);
+        assert_eq!(*drop_cnt.borrow(), 310);
+
+        // a panic on overwrite happens after the old pair left the map
+        let mut m = HashMap::new();
+        m.insert(
+            1,
+            Bomb {
+                armed: true,
+                b: &drop_cnt,
+            },
+        );
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            m.insert(
+                1,
+                Bomb {
+                    armed: false,
+                    b: &drop_cnt,
+                },
+            );
+        }));
+        assert!(res.is_err());
+        assert_eq!(m.len(), 1);
+        assert!(!m[&1].armed);
+        drop(m);
+        assert_eq!(*drop_cnt.borrow(), 312);
+    }
+
+    #[test]
+    fn test_hash_map_check_consistency() {
+        let mut plain = HashMap::new();
+        let mut ordered = HashMap::with_insertion_order();
+        let mut collided = HashMap::with_hasher(CollisionState);
+        for step in 0..5000 {
+            let k = rand::random::<u32>() % 2000;
+            match rand::random::<u32>() % 100 {
+                0..=54 => {
+                    plain.insert(k, step);
+                    ordered.insert(k, step);
+                    collided.insert(k % 300, step);
+                }
+                55..=94 => {
+                    plain.remove(&k);
+                    ordered.remove(&k);
+                    collided.remove(&(k % 300));
+                }
+                95..=97 => {
+                    let n = rand::random::<usize>() % 3000;
+                    plain.reserve(n);
+                    ordered.reserve(n);
+                    collided.reserve(n % 300);
+                }
+                _ => {
+                    plain.shrink_to_fit();
+                    ordered.shrink_to_fit();
+                    collided.shrink_to_fit();
+                }
+            }
+            assert!(plain.check_consistency());
+            assert!(ordered.check_consistency());
+            assert!(collided.check_consistency());
+            assert_eq!(plain.len(), ordered.len());
+        }
+        plain.clear();
+        assert!(plain.check_consistency());
+    }
+
+    #[test]
+    fn test_hash_map_inline_index_size() {
+        use comparator::OrdComparator;
+        use hash_map::RandomState;
+
+        let mut m: HashMap<u64, u64, RandomState, OrdComparator, 64> = HashMap::default();
+        assert_eq!(m.hash_table.index_size(), 64);
+        let inline_cap = m.capacity();
+        assert_eq!(inline_cap, (64 * 4 + 3) / 6);
+        for i in 0..inline_cap as u64 {
+            m.insert(i, i);
+            assert!(m.is_index_inline());
+        }
+        m.insert(inline_cap as u64, 0);
+        assert!(!m.is_index_inline());
+        assert!(m.check_consistency());
+        for i in 0..inline_cap as u64 {
+            assert_eq!(m[&i], i);
+        }
+        m.clear();
+        m.shrink_to_fit();
+        assert!(m.check_consistency());
+
+        let mut m = HashMap::new();
+        for i in 0..6 {
+            m.insert(i, i);
+        }
+        assert!(!m.is_index_inline());
+    }
+
+    #[test]
+    fn test_hash_map_may_contain_hash() {
+        use std::hash::BuildHasher;
+
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i * 2, i);
+        }
+        let hash_of = |m: &HashMap<i32, i32>, k: i32| m.hasher().hash_one(k);
+        for i in 0..1000 {
+            assert!(m.may_contain_hash(hash_of(&m, i * 2)));
+        }
+        // no false negatives, and an absent hash is rejected unless another key shares it
+        for i in 0..1000 {
+            let hash = hash_of(&m, i * 2 + 1);
+            let shared = m.keys().any(|k| hash_of(&m, *k) == hash);
+            assert_eq!(m.may_contain_hash(hash), shared);
+        }
+        // an empty bucket rejects at once
+        let hash = hash_of(&m, -1);
+        let mask = m.hash_table.index_size() - 1;
+        if m.buckets().all(|b| b.index() != hash as usize & mask) {
+            assert!(!m.may_contain_hash(hash));
+        }
+
+        // with every key in one bucket, the hash value alone decides
+        let mut m = HashMap::with_hasher(CollisionState);
+        assert!(!m.may_contain_hash(0));
+        m.insert(1, 1);
+        assert!(m.may_contain_hash(0));
+        assert!(!m.may_contain_hash(8));
+    }
+
+    #[test]
+    fn test_hash_map_iter_with_layout() {
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..100 {
+            m.insert(i, -i);
+        }
+        let layout: Vec<_> = m.iter_with_layout().collect();
+        assert_eq!(layout.len(), 100);
+        let mut prev_depth = 0;
+        for &(k, v, bucket, depth) in &layout {
+            assert_eq!(*v, -*k);
+            assert_eq!(bucket, 0);
+            assert!(depth >= prev_depth);
+            prev_depth = depth;
+        }
+        assert_eq!(layout[0].3, 0);
+        // an AVL of 100 nodes is at most 1.44 * log2(100) deep
+        assert!((6..10).contains(&prev_depth));
+
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, i);
+        }
+        let mask = m.hash_table.index_size() - 1;
+        let mut keys = Vec::new();
+        let mut last: Option<(usize, usize)> = None;
+        for (k, _, bucket, depth) in m.iter_with_layout() {
+            assert_eq!(m.make_hash(k) & mask, bucket);
+            if let Some((last_bucket, last_depth)) = last {
+                assert!(bucket != last_bucket || depth >= last_depth);
+            }
+            last = Some((bucket, depth));
+            keys.push(*k);
+        }
+        keys.sort();
+        assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_hash_map_compact() {
+        use std::collections::HashSet;
+
+        let mut m = HashMap::new();
+        for i in 0..2000 {
+            m.insert(i, i.to_string());
+        }
+        let mut removed = HashSet::new();
+        while removed.len() < 1000 {
+            let k = rand::random::<i32>().rem_euclid(2000);
+            if removed.insert(k) {
+                assert!(m.remove(&k).is_some());
+            }
+        }
+        let index_size = m.hash_table.index_size();
+        m.compact();
+        assert_eq!(m.hash_table.index_size(), index_size);
+        assert!(m.check_consistency());
+        assert_eq!(m.len(), 1000);
+        for i in 0..2000 {
+            assert_eq!(m.get(&i).is_some(), !removed.contains(&i));
+            if let Some(v) = m.get(&i) {
+                assert_eq!(*v, i.to_string());
+            }
+        }
+        assert_eq!(m.iter().count(), 1000);
+        // the storage is usable after compacting
+        for &k in &removed {
+            m.insert(k, k.to_string());
+        }
+        assert_eq!(m.len(), 2000);
+
+        let mut m = HashMap::with_insertion_order();
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        m.retain(|k, _| k % 2 == 1);
+        m.compact();
+        assert!(m.check_consistency());
+        assert_eq!(
+            m.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(),
+            (0..100).filter(|k| k % 2 == 1).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_hash_map_entry_replace_with() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::rc::Rc;
+
+        #[derive(Debug, PartialEq)]
+        struct Wrapped(Vec<i32>, usize);
+
+        let mut m = HashMap::new();
+        for i in 0..10 {
+            m.insert(i, Wrapped(vec![i], 0));
+        }
+        for i in 0..10 {
+            match m.entry(i) {
+                Occupied(o) => {
+                    let v = o.replace_with(|mut w| {
+                        w.0.push(-i);
+                        Wrapped(w.0, w.1 + 1)
+                    });
+                    v.1 += 1;
+                }
+                Vacant(_) => panic!("entry should be occupied"),
+            }
+        }
+        for i in 0..10 {
+            assert_eq!(m[&i], Wrapped(vec![i, -i], 2));
+        }
+
+        // a panicking closure takes the entry out of the map
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let drop_cnt = RefCell::new(0);
+        let key = Rc::new(5);
+        let mut m = HashMap::with_insertion_order();
+        for i in 0..10 {
+            let k = if i == 5 { key.clone() } else { Rc::new(i) };
+            m.insert(k, Node { b: &drop_cnt });
+        }
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Occupied(o) = m.entry(key.clone()) {
+                o.replace_with(|_| panic!("replace"));
+            }
+        }));
+        assert!(res.is_err());
+        assert_eq!(*drop_cnt.borrow(), 1);
+        assert_eq!(Rc::strong_count(&key), 1);
+        assert_eq!(m.len(), 9);
+        assert!(m.get(&key).is_none());
+        assert!(m.check_consistency());
+        assert_eq!(m.iter_insertion_order().count(), 9);
+    }
+
+    #[test]
+    fn test_hash_map_top_k_by() {
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, rand::random::<u32>() % 500);
+        }
+        let by_score_desc = |a: &u32, b: &u32| b.cmp(a);
+        let sorted = m.values_sorted_by(by_score_desc);
+        assert_eq!(sorted.len(), 1000);
+        assert!(sorted.windows(2).all(|w| w[0].1 >= w[1].1));
+        for &k in &[0, 1, 10, 999, 1000, 2000] {
+            let top = m.top_k_by(k, by_score_desc);
+            assert_eq!(top.len(), std::cmp::min(k, 1000));
+            let top_scores: Vec<_> = top.iter().map(|&(_, v)| *v).collect();
+            let prefix: Vec<_> = sorted.iter().take(k).map(|&(_, v)| *v).collect();
+            assert_eq!(top_scores, prefix);
+            assert!(top.iter().all(|&(k, v)| m[k] == *v));
+        }
+
+        let m: HashMap<i32, i32> = HashMap::new();
+        assert!(m.top_k_by(10, |a, b| a.cmp(b)).is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_estimate_memory_for() {
+        for &n in &[0usize, 5, 1000, 100_000] {
+            let mut m = HashMap::new();
+            for i in 0..n as u64 {
+                m.insert(i, i);
+            }
+            let estimate = HashMap::<u64, u64>::estimate_memory_for(n);
+            let actual = m.memory_usage();
+            assert!(actual >= estimate);
+            // only the tail of the last page of each fastbin is left out
+            assert!(actual - estimate <= 2 * (1 << 16));
+            if n == 100_000 {
+                assert!((actual - estimate) * 20 < estimate);
+            }
+        }
+        let mut m = HashMap::<String, String>::new();
+        m.insert("a".to_string(), "b".to_string());
+        assert!(m.memory_usage() >= HashMap::<String, String>::estimate_memory_for(1));
+    }
+
+    #[test]
+    fn test_hash_map_zero_sized_key() {
+        let mut m = HashMap::new();
+        assert_eq!(m.insert((), 1), None);
+        assert_eq!(m.insert((), 2), Some(((), 1)));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&()), Some(&2));
+        *m.get_mut(&()).unwrap() += 1;
+        assert_eq!(m[&()], 3);
+        assert_eq!(m.iter().count(), 1);
+        assert!(m.check_consistency());
+        assert_eq!(m.remove(&()), Some(((), 3)));
+        assert!(m.is_empty());
+        assert!(m.get(&()).is_none());
+        assert_eq!(m.remove(&()), None);
+        *m.entry(()).or_insert(10) += 1;
+        assert_eq!(m[&()], 11);
+
+        let mut m = HashMap::new();
+        assert_eq!(m.insert((), ()), None);
+        assert_eq!(m.insert((), ()), Some(((), ())));
+        assert_eq!(m.len(), 1);
+        assert!(m.contains_key(&()));
+        assert_eq!(m.into_iter().collect::<Vec<_>>(), [((), ())]);
+
+        let mut m = HashMap::with_insertion_order();
+        m.insert((), "a");
+        m.insert((), "b");
+        assert_eq!(m.iter_insertion_order().collect::<Vec<_>>(), [(&(), &"b")]);
+        m.clear();
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_freeze() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let mut m = HashMap::new();
+        for i in 0..10000 {
+            m.insert(i, i.to_string());
+        }
+        let frozen = Arc::new(m.freeze());
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let frozen = frozen.clone();
+                thread::spawn(move || {
+                    for i in 0..10000 {
+                        let k = (i * 7 + t * 2500) % 10000;
+                        assert_eq!(frozen.get(&k), Some(&k.to_string()));
+                        assert!(frozen.contains_key(&k));
+                    }
+                    assert!(frozen.get(&-1).is_none());
+                    frozen.iter().count()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 10000);
+        }
+        let frozen = Arc::try_unwrap(frozen).ok().unwrap();
+        assert_eq!(frozen.len(), 10000);
+        let mut m = frozen.thaw();
+        m.insert(10000, "10000".to_string());
+        assert_eq!(m.len(), 10001);
+        assert!(m.check_consistency());
+    }
+
+    #[test]
+    fn test_hash_map_bucket_len_histogram() {
+        let mut m = HashMap::new();
+        assert_eq!(m.bucket_len_histogram(), [8]);
+        for i in 0..10000 {
+            m.insert(i, i);
+        }
+        let histogram = m.bucket_len_histogram();
+        assert_eq!(histogram.iter().sum::<usize>(), m.hash_table.index_size());
+        assert_eq!(histogram.iter().enumerate().map(|(len, n)| len * n).sum::<usize>(), 10000);
+        assert_eq!(histogram.len() - 1, m.get_max_node_of_single_index() as usize);
+        // most entries sit in short buckets
+        let short: usize = histogram.iter().enumerate().take(4).map(|(len, n)| len * n).sum();
+        assert!(short * 10 > 10000 * 9);
+
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..100 {
+            m.insert(i, i);
+        }
+        let histogram = m.bucket_len_histogram();
+        assert_eq!(histogram.len(), 101);
+        assert_eq!(histogram[100], 1);
+        assert_eq!(histogram[0], m.hash_table.index_size() - 1);
+        assert!(histogram[1..100].iter().all(|&n| n == 0));
+    }
+
+    #[test]
+    fn test_hash_map_reset() {
+        let fresh = HashMap::<i32, i32>::new().memory_usage();
+        let mut m = HashMap::new();
+        for i in 0..10000 {
+            m.insert(i, i);
+        }
+        assert!(m.hash_table.heap_size() > 0);
+        m.reset();
+        assert!(m.is_empty());
+        assert_eq!(m.hash_table.heap_size(), 0);
+        assert_eq!(m.hash_table.index_size(), 8);
+        assert_eq!(m.memory_usage(), fresh);
+        assert!(m.check_consistency());
+        for i in 0..100 {
+            m.insert(i, -i);
+        }
+        assert_eq!(m.len(), 100);
+        assert_eq!(m[&99], -99);
+
+        let mut m = HashMap::with_insertion_order();
+        m.insert(2, 2);
+        m.insert(1, 1);
+        m.reset();
+        m.insert(3, 3);
+        m.insert(1, 1);
+        assert_eq!(m.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(), [3, 1]);
+
+        let mut a = HashMap::new();
+        a.insert(1, 1);
+        let mut b = HashMap::new();
+        b.insert(2, 2);
+        let old = a.replace(b);
+        assert_eq!(old.get(&1), Some(&1));
+        assert_eq!(a.get(&2), Some(&2));
+        assert!(a.get(&1).is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "HashMap modified during iteration")]
+    fn test_hash_map_modified_during_iteration() {
+        let mut m = HashMap::new();
+        for i in 0..10 {
+            m.insert(i, i);
+        }
+        let p = &mut m as *mut HashMap<i32, i32>;
+        // this aliasing is undefined behavior, which is exactly what the check is there for
+        for (k, _) in unsafe { (*p).iter() } {
+            unsafe {
+                (*p).insert(k + 100, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_map_with_new_hasher() {
+        use hashers::FixedState;
+        use std::hash::BuildHasher;
+        use std::rc::Rc;
+
+        let values: Vec<_> = (0..1000).map(Rc::new).collect();
+        let mut m = HashMap::new();
+        for (i, v) in values.iter().enumerate() {
+            m.insert(i.to_string(), v.clone());
+        }
+        let m = m.with_new_hasher(FixedState::with_seed(7));
+        assert_eq!(m.len(), 1000);
+        assert!(m.check_consistency());
+        let state = FixedState::with_seed(7);
+        for (k, _) in m.iter() {
+            assert!(m.may_contain_hash(state.hash_one(k)));
+        }
+        for (i, v) in values.iter().enumerate() {
+            assert_eq!(*m[&i.to_string()], i);
+            assert_eq!(Rc::strong_count(v), 2);
+        }
+        drop(m);
+        assert!(values.iter().all(|v| Rc::strong_count(v) == 1));
+
+        let mut m = HashMap::with_insertion_order();
+        for i in (0..100).rev() {
+            m.insert(i, i);
+        }
+        let m = m.with_new_hasher(CollisionState);
+        assert_eq!(m.get_max_node_of_single_index(), 100);
+        assert_eq!(
+            m.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(),
+            (0..100).rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_hash_map_is_submap_of() {
+        let a: HashMap<i32, i32> = (0..50).map(|i| (i, i * 2)).collect();
+        let b: HashMap<i32, i32> = (25..100).map(|i| (i, i * 2)).collect();
+        let c: HashMap<i32, i32> = (0..100).map(|i| (i, i * 2)).collect();
+        let d: HashMap<i32, i32> = (200..300).map(|i| (i, i)).collect();
+        let empty = HashMap::new();
+        assert!(a.is_submap_of(&c) && b.is_submap_of(&c));
+        assert!(c.is_supermap_of(&a) && c.is_supermap_of(&b));
+        assert!(!a.is_submap_of(&b) && !b.is_submap_of(&a));
+        assert!(!c.is_submap_of(&a));
+        assert!(!d.is_submap_of(&c) && !c.is_supermap_of(&d));
+        assert!(a.is_submap_of(&a) && a.is_supermap_of(&a));
+        assert!(empty.is_submap_of(&d) && d.is_supermap_of(&empty));
+        // same keys, different values
+        let e: HashMap<i32, i32> = (0..50).map(|i| (i, i)).collect();
+        assert!(!a.is_submap_of(&e) && !e.is_submap_of(&c));
+    }
+
+    #[test]
+    fn test_hash_map_retain_until() {
+        use hash_map::RetainAction;
+
+        let mut m: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let order: Vec<_> = m.keys().cloned().collect();
+        let mut scanned = Vec::new();
+        let mut len = m.len();
+        m.retain_until(|&k, _| {
+            if len == 10 {
+                return RetainAction::StopKeepRest;
+            }
+            scanned.push(k);
+            len -= 1;
+            RetainAction::Remove
+        });
+        assert_eq!(m.len(), 10);
+        assert_eq!(scanned, &order[..90]);
+        assert!(order[90..].iter().all(|k| m.contains_key(k)));
+        assert!(m.check_consistency());
+
+        // keep odd keys, double them, never stop
+        let mut m: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        m.retain_until(|&k, v| {
+            if k % 2 == 0 {
+                RetainAction::Remove
+            } else {
+                *v *= 2;
+                RetainAction::Keep
+            }
+        });
+        assert_eq!(m.len(), 50);
+        assert!(m.iter().all(|(k, v)| k % 2 == 1 && *v == k * 2));
+    }
+
+    #[test]
+    fn test_hash_map_get_many() {
+        let m: HashMap<String, usize> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let keys: Vec<String> = (0..200).rev().map(|i| (i % 150).to_string()).collect();
+        let refs: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+        let values = m.get_many(&refs);
+        assert_eq!(values.len(), 200);
+        for (k, v) in keys.iter().zip(values) {
+            let i: usize = k.parse().unwrap();
+            assert_eq!(v, if i < 100 { Some(&i) } else { None });
+        }
+        assert!(m.get_many::<str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_clone_layout() {
+        let mut m = HashMap::new();
+        for i in 0..5000 {
+            m.insert(i, i.to_string());
+        }
+        for i in (0..5000).filter(|i| i % 7 == 0) {
+            m.remove(&i);
+        }
+        let c = m.clone_layout();
+        assert!(c.check_consistency());
+        assert_eq!(c.len(), m.len());
+        assert_eq!(c.hash_table.index_size(), m.hash_table.index_size());
+        assert!(m.iter().eq(c.iter()));
+        assert_eq!(
+            m.iter_with_layout().map(|(k, _, b, d)| (*k, b, d)).collect::<Vec<_>>(),
+            c.iter_with_layout().map(|(k, _, b, d)| (*k, b, d)).collect::<Vec<_>>()
+        );
+        // the clone is independent of the original
+        drop(m);
+        assert_eq!(c[&1], "1");
+
+        let mut m = HashMap::with_hasher(CollisionState);
+        for i in 0..300 {
+            m.insert(i, i);
+        }
+        let mut c = m.clone_layout();
+        assert!(c.check_consistency());
+        assert!(m.iter().eq(c.iter()));
+        c.insert(1000, 1000);
+        c.remove(&0);
+        assert!(c.check_consistency());
+
+        let mut m = HashMap::with_insertion_order();
+        for i in (0..100).rev() {
+            m.insert(i, i);
+        }
+        m.rehash_to_index_size(1024);
+        let c = m.clone_layout();
+        assert_eq!(c.hash_table.index_size(), 1024);
+        assert!(m.iter_insertion_order().eq(c.iter_insertion_order()));
+        assert!(m.iter().eq(c.iter()));
+
+        let m: HashMap<i32, i32> = HashMap::new();
+        assert!(m.clone_layout().is_empty());
+    }
+
+    #[test]
+    fn test_hash_map_entry_or_try_insert_with() {
+        let mut m: HashMap<String, Vec<i32>> = HashMap::new();
+        let res: Result<_, String> = m.entry("a".to_string()).or_try_insert_with(|| Ok(vec![1]));
+        res.unwrap().push(2);
+        assert_eq!(m["a"], [1, 2]);
+
+        let len = m.len();
+        let usage = m.memory_usage();
+        let res = m.entry("b".to_string())
+            .or_try_insert_with(|| Err::<Vec<i32>, _>("can't build"));
+        assert_eq!(res, Err("can't build"));
+        assert_eq!(m.len(), len);
+        assert_eq!(m.memory_usage(), usage);
+        assert!(!m.contains_key("b"));
+        assert!(m.check_consistency());
+
+        // an occupied entry never calls `f`
+        let res = m.entry("a".to_string())
+            .or_try_insert_with(|| -> Result<Vec<i32>, ()> { panic!("called") });
+        assert_eq!(res, Ok(&mut vec![1, 2]));
+    }
+
+    #[test]
+    fn test_hash_map_into_sorted_vec() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let drop_cnt = RefCell::new(0);
+        let mut m = HashMap::new();
+        for i in (0..1000).rev() {
+            m.insert(i * 7 % 1000, Node { b: &drop_cnt });
+        }
+        let v = m.into_sorted_vec();
+        assert_eq!(*drop_cnt.borrow(), 0);
+        assert_eq!(v.iter().map(|&(k, _)| k).collect::<Vec<_>>(), (0..1000).collect::<Vec<_>>());
+        drop(v);
+        assert_eq!(*drop_cnt.borrow(), 1000);
+
+        let m: HashMap<i32, i32, _, _> =
+            HashMap::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        assert!(m.into_sorted_vec().is_empty());
+        let mut m = HashMap::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        for i in 0..10 {
+            m.insert(i, i);
+        }
+        assert_eq!(m.into_sorted_vec(), (0..10).rev().map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_hash_map_reserve_scoped() {
+        let mut m = HashMap::new();
+        for i in 0..10 {
+            m.insert(i, i);
+        }
+        let before = m.capacity();
+        {
+            let mut guard = m.reserve_scoped(10000);
+            assert!(guard.capacity() >= 10010);
+            let reserved = guard.capacity();
+            for i in 10..5000 {
+                guard.insert(i, i);
+            }
+            assert_eq!(guard.capacity(), reserved);
+            for i in 100..5000 {
+                guard.remove(&i);
+            }
+        }
+        assert_eq!(m.len(), 100);
+        assert!(m.capacity() >= 100 && m.capacity() < 1000);
+        assert!(m.check_consistency());
+        {
+            let _guard = m.reserve_scoped(0);
+        }
+        assert!(m.capacity() >= before);
+        assert!((0..100).all(|i| m[&i] == i));
     }
-}
 
-impl<'a, K, V, S> Iterator for Drain<'a, K, V, S> {
-    type Item = (K, V);
+    #[test]
+    fn test_hash_map_for_each_entry() {
+        let mut m = HashMap::new();
+        m.insert(0, 100);
+        let keys = (0..1000).map(|i| i % 10);
+        m.for_each_entry(keys, |entry| *entry.or_insert(0) += 1);
+        assert_eq!(m.len(), 10);
+        assert_eq!(m[&0], 200);
+        assert!((1..10).all(|k| m[&k] == 100));
+
+        let mut vacant = 0;
+        m.for_each_entry(vec![5, 10, 11, 10], |entry| match entry {
+            Occupied(o) => {
+                o.remove();
+            }
+            Vacant(v) => {
+                vacant += 1;
+                v.insert(0);
+            }
+        });
+        assert_eq!(vacant, 2);
+        assert!(!m.contains_key(&5) && !m.contains_key(&10));
+        assert_eq!(m[&11], 0);
+    }
 
-    #[inline]
-    fn next(&mut self) -> Option<(K, V)> {
-        let entry = self.entry;
-        if entry.is_null() {
-            return None;
+    #[test]
+    fn test_hash_map_auto_shrink() {
+        let mut m = HashMap::new();
+        assert_eq!(m.load(), 0.0);
+        for i in 0..10000 {
+            m.insert(i, i);
         }
-        self.entry = self.map.next(entry);
-        self.map.erase(entry)
-    }
+        assert!(m.load() > 0.3 && m.load() <= 2.0 / 3.0);
+        let full = m.capacity();
 
-    #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.map.len(), Some(self.map.len()))
-    }
-}
+        // off by default
+        let mut n = m.clone();
+        for i in 100..10000 {
+            n.remove(&i);
+        }
+        assert_eq!(n.capacity(), full);
 
-impl<'a, K, V, S> ExactSizeIterator for Drain<'a, K, V, S> {
-    #[inline]
-    fn len(&self) -> usize {
-        self.map.len()
+        m.set_auto_shrink(true);
+        for i in 100..10000 {
+            m.remove(&i);
+            assert!(m.load() >= 1.0 / 8.0 || m.hash_table.index_size() == 8);
+        }
+        assert_eq!(m.len(), 100);
+        assert!(m.capacity() < full / 10);
+        assert!(m.check_consistency());
+        assert!((0..100).all(|i| m[&i] == i));
+        for i in 0..100 {
+            if let Occupied(o) = m.entry(i) {
+                o.remove();
+            }
+        }
+        assert!(m.is_empty());
+        assert_eq!(m.hash_table.index_size(), 8);
+
+        // kept by the rebuilding constructors
+        assert!(m.clone_layout().auto_shrink);
+        assert!(m.with_new_hasher(super::RandomState::default()).auto_shrink);
     }
-}
 
-impl<'a, K, Q, V, S> Index<&'a Q> for HashMap<K, V, S>
-where
-    Q: ?Sized + Hash + Ord,
-    K: Hash + Ord + Borrow<Q>,
-    S: BuildHasher,
-{
-    type Output = V;
+    #[test]
+    fn test_hash_map_forget_contents() {
+        let fresh = HashMap::<u64, (u64, u64)>::new().memory_usage();
+        let mut m = HashMap::new();
+        for i in 0..10000u64 {
+            m.insert(i, (i, i * 2));
+        }
+        assert!(m.memory_usage() > fresh);
+        m.forget_contents();
+        assert!(m.is_empty());
+        assert_eq!(m.memory_usage(), fresh);
+        assert!(m.check_consistency());
+        for i in 0..100 {
+            m.insert(i, (i, 0));
+        }
+        assert_eq!(m.len(), 100);
+        assert_eq!(m[&99], (99, 0));
 
-    /// Returns a reference to the value corresponding to the supplied key.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the key is not present in the `HashMap`.
-    #[inline]
-    fn index(&self, q: &Q) -> &Self::Output {
-        self.get(q).expect("no entry found for key")
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let mut m = HashMap::with_insertion_order();
+        for i in 0..100 {
+            m.insert(i, Node { b: &cnt });
+        }
+        m.forget_contents();
+        assert_eq!(*cnt.borrow(), 0);
+        m.insert(7, Node { b: &cnt });
+        assert_eq!(m.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(), [7]);
+        drop(m);
+        assert_eq!(*cnt.borrow(), 1);
     }
-}
 
-impl<K, V, S> Extend<(K, V)> for HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
-        let iter = iter.into_iter();
-        let reserve = if self.is_empty() {
-            iter.size_hint().0
-        } else {
-            (iter.size_hint().0 + 1) / 2 + self.len()
-        };
-        self.reserve(reserve);
-        for (k, v) in iter {
-            self.insert(k, v);
+    #[test]
+    fn test_hash_map_reuse_freed_entries() {
+        let test_num = 1000;
+        let mut m = HashMap::new();
+        for i in 0..test_num {
+            m.insert(i, i.to_string());
         }
+        for i in 0..test_num {
+            m.remove(&i);
+        }
+        let fresh = (m.entry_fastbin.fresh_alloc_count(), m.kv_fastbin.fresh_alloc_count());
+        let bytes = m.memory_usage();
+        for i in test_num..test_num * 2 {
+            m.get_or_insert(i, i.to_string());
+        }
+        assert_eq!(
+            (m.entry_fastbin.fresh_alloc_count(), m.kv_fastbin.fresh_alloc_count()),
+            fresh
+        );
+        assert_eq!(m.entry_fastbin.reused_count(), test_num);
+        assert_eq!(m.kv_fastbin.reused_count(), test_num);
+        assert_eq!(m.memory_usage(), bytes);
+        assert_eq!(m.len(), test_num);
     }
-}
 
-impl<'a, K, V, S> Extend<(&'a K, &'a V)> for HashMap<K, V, S>
-where
-    K: Ord + Hash + Copy,
-    V: Copy,
-    S: BuildHasher,
-{
-    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
-        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    #[test]
+    fn test_hash_map_prewarm() {
+        let test_num = 1000;
+        let mut m = HashMap::new();
+        m.insert(-1, String::new());
+        m.prewarm(test_num);
+        let index_size = m.hash_table.index_size();
+        let bytes = (m.entry_fastbin.allocated_bytes(), m.kv_fastbin.allocated_bytes());
+        for i in 0..test_num as i32 {
+            m.insert(i, i.to_string());
+        }
+        assert_eq!(m.hash_table.index_size(), index_size);
+        assert_eq!(
+            (m.entry_fastbin.allocated_bytes(), m.kv_fastbin.allocated_bytes()),
+            bytes
+        );
+        assert_eq!(m.len(), test_num + 1);
+        m.insert(test_num as i32, String::new());
+        assert!(m.kv_fastbin.allocated_bytes() > bytes.1);
     }
-}
 
-impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    type Item = (&'a K, &'a V);
-    type IntoIter = Iter<'a, K, V, S>;
+    #[test]
+    fn test_hash_map_remove_if() {
+        let mut m: HashMap<i32, i32> = (0..100).map(|i| (i, i * 10)).collect();
+        let threshold = 500;
+        // present, predicate false
+        assert_eq!(m.remove_if(&10, |_, &v| v > threshold), None);
+        assert_eq!(m.get(&10), Some(&100));
+        // present, predicate true
+        assert_eq!(m.remove_if(&60, |_, &v| v > threshold), Some((60, 600)));
+        assert!(!m.contains_key(&60));
+        // absent, predicate never called
+        assert_eq!(m.remove_if(&1000, |_, _| panic!("called for an absent key")), None);
+        assert_eq!(m.remove_if(&60, |_, _| panic!("called for an absent key")), None);
 
-    fn into_iter(self) -> Iter<'a, K, V, S> {
-        self.iter()
+        for i in 0..100 {
+            m.remove_if(&i, |k, &v| *k == i && v > threshold);
+        }
+        assert_eq!(m.len(), 51);
+        assert!(m.values().all(|&v| v <= threshold));
+        assert!(m.check_consistency());
     }
-}
 
-impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    type Item = (&'a K, &'a mut V);
-    type IntoIter = IterMut<'a, K, V, S>;
-
-    fn into_iter(self) -> IterMut<'a, K, V, S> {
-        self.iter_mut()
+    #[test]
+    fn test_hash_map_compare_and_update() {
+        let mut m = HashMap::new();
+        m.insert(1, String::from("a"));
+        m.insert(2, String::from("b"));
+        // matching
+        assert_eq!(m.compare_and_update(&1, &String::from("a"), String::from("c")), Ok(()));
+        assert_eq!(m[&1], "c");
+        // mismatching
+        assert_eq!(
+            m.compare_and_update(&2, &String::from("a"), String::from("d")),
+            Err(Some(String::from("b")))
+        );
+        assert_eq!(m[&2], "b");
+        // missing
+        assert_eq!(m.compare_and_update(&3, &String::from("b"), String::from("d")), Err(None));
+        assert!(!m.contains_key(&3));
+        assert_eq!(m.len(), 2);
+
+        // a retry loop building on it
+        let mut counter = HashMap::new();
+        counter.insert("n", 0);
+        for _ in 0..10 {
+            let mut seen = counter["n"];
+            while let Err(Some(cur)) = counter.compare_and_update("n", &seen, seen + 1) {
+                seen = cur;
+            }
+        }
+        assert_eq!(counter["n"], 10);
     }
-}
 
-impl<K, V, S> IntoIterator for HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    type Item = (K, V);
-    type IntoIter = IntoIter<K, V, S>;
+    #[test]
+    fn test_hash_map_modify_or_insert() {
+        use std::borrow::Borrow;
+        use std::cell::Cell;
+
+        thread_local!(static OWNED: Cell<usize> = const { Cell::new(0) });
+
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+        struct Word(String);
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+        struct Key(Word);
+        impl Borrow<Word> for Key {
+            fn borrow(&self) -> &Word {
+                &self.0
+            }
+        }
+        impl ToOwned for Word {
+            type Owned = Key;
+            fn to_owned(&self) -> Key {
+                OWNED.with(|c| c.set(c.get() + 1));
+                Key(Word(self.0.clone()))
+            }
+        }
 
-    fn into_iter(self) -> Self::IntoIter {
-        IntoIter {
-            entry: self.first(),
-            map: self,
+        let text = "the quick fox jumps over the lazy dog the end";
+        let words: Vec<_> = text.split(' ').map(|w| Word(w.to_string())).collect();
+        let mut counts: HashMap<Key, u32> = HashMap::new();
+        for w in words.iter() {
+            counts.modify_or_insert(w, |n| *n += 1, || 1);
+        }
+        // only the 8 distinct words were turned into owned keys
+        assert_eq!(OWNED.with(|c| c.get()), 8);
+        assert_eq!(counts.len(), 8);
+        assert_eq!(counts[&Word("the".to_string())], 3);
+        assert_eq!(counts[&Word("fox".to_string())], 1);
+
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for w in text.split(' ') {
+            counts.modify_or_insert(w, |n| *n += 1, || 1);
         }
+        assert_eq!(counts["the"], 3);
+        assert_eq!(counts.values().sum::<u32>(), 10);
     }
-}
 
-/// An owning iterator over the entries of a `HashMap`.
-///
-/// This `struct` is created by the [`into_iter`] method on [`HashMap`][`HashMap`]
-/// (provided by the `IntoIterator` trait). See its documentation for more.
-///
-/// [`into_iter`]: struct.HashMap.html#method.into_iter
-/// [`HashMap`]: struct.HashMap.html
-pub struct IntoIter<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    entry: *mut InternalHashEntry<K, V>,
-    map: HashMap<K, V, S>,
-}
+    #[test]
+    fn test_hash_map_split_off_where() {
+        struct Node<'a> {
+            b: &'a RefCell<i32>,
+        }
+        impl<'a> Drop for Node<'a> {
+            fn drop(&mut self) {
+                *self.b.borrow_mut() += 1;
+            }
+        }
+        let cnt = RefCell::new(0);
+        let test_num = 1000;
+        let mut odds = HashMap::new();
+        for i in 0..test_num {
+            odds.insert(i, Node { b: &cnt });
+        }
+        let evens = odds.split_off_where(|&k, _| k % 2 == 0);
+        // nothing was dropped or cloned on the way
+        assert_eq!(*cnt.borrow(), 0);
+        assert_eq!(evens.len(), test_num as usize / 2);
+        assert_eq!(odds.len(), test_num as usize / 2);
+        assert!((0..test_num).all(|i| evens.contains_key(&i) == (i % 2 == 0)));
+        assert!((0..test_num).all(|i| odds.contains_key(&i) == (i % 2 == 1)));
+        assert!(evens.check_consistency() && odds.check_consistency());
+        drop(evens);
+        assert_eq!(*cnt.borrow(), test_num / 2);
+        assert!(odds.split_off_where(|_, _| false).is_empty());
+        odds.set_auto_shrink(true);
+        assert!(odds.split_off_where(|_, _| false).auto_shrink);
+        assert_eq!(odds.split_off_where(|_, _| true).len(), test_num as usize / 2);
+        assert!(odds.is_empty());
+        assert_eq!(*cnt.borrow(), test_num);
 
-impl<K, V, S> Drop for IntoIter<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    fn drop(&mut self) {
-        for (_, _) in self {}
+        let mut m = HashMap::with_insertion_order();
+        for &i in [5, 2, 8, 1, 4].iter() {
+            m.insert(i, i);
+        }
+        let small = m.split_off_where(|&k, _| k < 5);
+        assert_eq!(small.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(), [2, 1, 4]);
+        assert_eq!(m.iter_insertion_order().map(|(k, _)| *k).collect::<Vec<_>>(), [5, 8]);
     }
-}
 
-impl<K, V, S> Iterator for IntoIter<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher,
-{
-    type Item = (K, V);
+    #[test]
+    fn test_hash_map_pod_round_trip() {
+        use std::io::ErrorKind;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let entry = self.entry;
-        if entry.is_null() {
-            return None;
+        let mut m: HashMap<u64, u64> = HashMap::new();
+        for i in 0..10000u64 {
+            m.insert(i.wrapping_mul(0x9e37_79b9_7f4a_7c15), i);
         }
-        self.entry = self.map.next(entry);
-        self.map.erase(entry)
+        for i in 0..5000u64 {
+            m.remove(&i.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        }
+        let mut buf = Vec::new();
+        m.dump_pod(&mut buf).unwrap();
+        assert_eq!(buf.len(), 4 + 4 * 8 + m.len() * 16);
+        let loaded: HashMap<u64, u64> = HashMap::load_pod(&mut &buf[..]).unwrap();
+        assert!(loaded == m);
+        assert_eq!(loaded.hash_table.index_size(), m.hash_table.index_size());
+        assert!(loaded.check_consistency());
+
+        let empty: HashMap<u64, u64> = HashMap::new();
+        let mut buf2 = Vec::new();
+        empty.dump_pod(&mut buf2).unwrap();
+        assert!(HashMap::<u64, u64>::load_pod(&mut &buf2[..]).unwrap().is_empty());
+
+        let arrays: HashMap<i32, [f32; 3]> = (0..10).map(|i| (i, [i as f32; 3])).collect();
+        let mut buf3 = Vec::new();
+        arrays.dump_pod(&mut buf3).unwrap();
+        assert!(HashMap::<i32, [f32; 3]>::load_pod(&mut &buf3[..]).unwrap() == arrays);
+
+        // wrong types, truncated input and garbage are all rejected
+        let err = HashMap::<u32, u64>::load_pod(&mut &buf[..]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = HashMap::<u64, u64>::load_pod(&mut &buf[..buf.len() - 1]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let err = HashMap::<u64, u64>::load_pod(&mut &b"HOP"[..]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // a forged header neither panics nor allocates for what it claims
+        let forge = |len: u64, index_size: u64| {
+            let mut header = b"HOPD".to_vec();
+            for n in &[8, 8, len, index_size] {
+                header.extend_from_slice(&n.to_ne_bytes());
+            }
+            HashMap::<u64, u64>::load_pod(&mut &header[..]).err().unwrap().kind()
+        };
+        assert_eq!(forge(u64::MAX, 1 << 20), ErrorKind::InvalidData);
+        assert_eq!(forge(1 << 40, 1 << 20), ErrorKind::InvalidData);
+        assert_eq!(forge(1 << 40, 1 << 41), ErrorKind::UnexpectedEof);
+        assert_eq!(forge(100, 1000), ErrorKind::InvalidData);
+        assert_eq!(forge(100, 1 << 62), ErrorKind::InvalidData);
+        assert_eq!(forge(10, 1 << 40), ErrorKind::UnexpectedEof);
+
+        // an index array far larger than the pairs need is not restored
+        let mut header = b"HOPD".to_vec();
+        for n in &[8u64, 8, 1, 1 << 40] {
+            header.extend_from_slice(&n.to_ne_bytes());
+        }
+        header.extend_from_slice(&7u64.to_ne_bytes());
+        header.extend_from_slice(&8u64.to_ne_bytes());
+        let loaded = HashMap::<u64, u64>::load_pod(&mut &header[..]).unwrap();
+        assert_eq!(loaded[&7], 8);
+        assert_eq!(loaded.index_size(), 8);
+        // a map overloaded by defer_rehash still loads
+        let mut m: HashMap<u64, u64> = HashMap::new();
+        let mut buf = Vec::new();
+        {
+            let mut bulk = m.defer_rehash();
+            bulk.extend((0..100).map(|i| (i, i)));
+            bulk.insert(100, 100);
+            bulk.dump_pod(&mut buf).unwrap();
+        }
+        assert!(HashMap::<u64, u64>::load_pod(&mut &buf[..]).unwrap() == m);
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.map.len(), Some(self.map.len()))
+    #[test]
+    fn test_hash_map_worst_bucket() {
+        // hashes a key to itself, except that every key from 1000 on collides on 0
+        let mut m = HashMap::with_hasher(FnState(|k| if k >= 1000 { 0 } else { k }));
+        assert!(m.worst_bucket().is_none());
+        for i in 1..100u64 {
+            m.insert(i, ());
+        }
+        let (_, keys) = m.worst_bucket().unwrap();
+        assert_eq!(keys.len(), 1);
+        let colliding = [1013u64, 1002, 1005, 1000, 1011, 1007, 1001, 1009];
+        for &k in colliding.iter() {
+            m.insert(k, ());
+        }
+        let (index, keys) = m.worst_bucket().unwrap();
+        assert_eq!(index, 0);
+        let mut expect = colliding.to_vec();
+        expect.sort();
+        assert_eq!(keys.into_iter().cloned().collect::<Vec<_>>(), expect);
+        assert_eq!(m.get_max_node_of_single_index(), colliding.len() as i32);
     }
-}
 
-impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    S: BuildHasher + Default,
-{
-    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> HashMap<K, V, S> {
-        let mut map = HashMap::with_hasher(Default::default());
-        map.extend(iter);
-        map
+    #[test]
+    fn test_hash_map_try_reserve_failure() {
+        use fastbin;
+        use std::panic;
+
+        let mut m = HashMap::new();
+        for i in 0..1000 {
+            m.insert(i, i.to_string());
+        }
+        let (capacity, index_size) = (m.capacity(), m.index_size());
+        let resizes = m.hash_table.resize_count();
+        fastbin::set_fail_malloc(true);
+        assert!(!m.try_reserve(100_000));
+        assert!(m.try_reserve(capacity - m.len()));
+        fastbin::set_fail_malloc(false);
+        assert_eq!(m.capacity(), capacity);
+        assert_eq!(m.index_size(), index_size);
+        assert_eq!(m.hash_table.resize_count(), resizes);
+        assert_eq!(m.len(), 1000);
+        assert!(m.check_consistency());
+        assert!((0..1000).all(|i| m[&i] == i.to_string()));
+        assert_eq!(m.iter().count(), 1000);
+
+        // a failing reserve panics, but leaves the map intact as well
+        fastbin::set_fail_malloc(true);
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| m.reserve(100_000)));
+        fastbin::set_fail_malloc(false);
+        assert!(res.is_err());
+        assert!(m.check_consistency());
+        assert!((0..1000).all(|i| m[&i] == i.to_string()));
+
+        // and it can grow once memory is back
+        assert!(m.try_reserve(100_000));
+        assert!(m.capacity() >= 101_000);
+        for i in 1000..2000 {
+            m.insert(i, i.to_string());
+        }
+        assert!((0..2000).all(|i| m[&i] == i.to_string()));
     }
-}
 
-impl<K, V, S> Clone for HashMap<K, V, S>
-where
-    K: Ord + Hash + Clone,
-    V: Clone,
-    S: BuildHasher + Clone,
-{
-    fn clone(&self) -> Self {
-        let mut map = HashMap::with_capacity_and_hasher(self.len(), self.hash_builder.clone());
-        for (k, v) in self.iter() {
-            map.insert(k.clone(), v.clone());
+    #[test]
+    fn test_hash_map_try_insert_alloc() {
+        use fastbin;
+
+        let mut m = HashMap::new();
+        fastbin::set_fail_malloc(true);
+        assert_eq!(m.try_insert_alloc(0, "0".to_string()), Err((0, "0".to_string())));
+        assert!(m.is_empty());
+        fastbin::set_fail_malloc(false);
+        assert_eq!(m.try_insert_alloc(0, "0".to_string()), Ok(None));
+
+        // fill the inline index array, then fail to grow it
+        let capacity = m.capacity();
+        for i in 1..capacity as i32 {
+            m.insert(i, i.to_string());
         }
-        map
+        m.entry_fastbin.reserve(10);
+        m.kv_fastbin.reserve(10);
+        let index_size = m.hash_table.index_size();
+        fastbin::set_fail_malloc(true);
+        assert_eq!(m.try_insert_alloc(100, "x".to_string()), Err((100, "x".to_string())));
+        assert_eq!(m.hash_table.index_size(), index_size);
+        // replacing needs no room in the index array
+        assert_eq!(
+            m.try_insert_alloc(1, "one".to_string()),
+            Ok(Some((1, "1".to_string())))
+        );
+        fastbin::set_fail_malloc(false);
+        assert_eq!(m.len(), capacity);
+        assert!(m.check_consistency());
+
+        // the pair slot is given back if the entry can't be allocated
+        let mut m = HashMap::new();
+        m.kv_fastbin.reserve(1);
+        fastbin::set_fail_malloc(true);
+        assert_eq!(m.try_insert_alloc(7, 7), Err((7, 7)));
+        fastbin::set_fail_malloc(false);
+        assert_eq!(m.kv_fastbin.reused_count(), 0);
+        assert_eq!(m.try_insert_alloc(7, 7), Ok(None));
+        assert_eq!(m.kv_fastbin.reused_count(), 1);
+
+        // the map stays usable after failures
+        for i in 0..1000 {
+            assert_eq!(m.try_insert_alloc(i, i), Ok(if i == 7 { Some((7, 7)) } else { None }));
+        }
+        assert_eq!(m.len(), 1000);
+        assert!((0..1000).all(|i| m[&i] == i));
+        assert!(m.check_consistency());
     }
-}
 
-impl<K, V, S> PartialEq for HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    V: PartialEq,
-    S: BuildHasher,
-{
-    fn eq(&self, other: &HashMap<K, V, S>) -> bool {
-        if self.len() != other.len() {
-            return false;
+    #[test]
+    fn test_hash_map_iter_nodes() {
+        use avl_node::AVLNodePtrBase;
+        use hash_map::{HashEntryBase, NodeRole};
+        use hash_table::HashNodePtrOperation;
+
+        let mut m = HashMap::with_hasher(CollisionState);
+        m.insert(0, 0);
+        assert!(m.iter_nodes().eq(Some((&0, &0, NodeRole::Root))));
+        for i in 1..100 {
+            m.insert(i, i);
         }
-        self.iter()
-            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+        assert_eq!(m.iter_nodes().len(), 100);
+        let roles: Vec<_> = m.iter_nodes().map(|(k, _, role)| (*k, role)).collect();
+        assert_eq!(roles.iter().filter(|&&(_, r)| r == NodeRole::Root).count(), 1);
+        let leaves = roles.iter().filter(|&&(_, r)| r == NodeRole::Leaf).count();
+        // a balanced tree of 100 nodes is 7 levels high and holds between 32 and 50 leaves
+        assert!((32..=50).contains(&leaves));
+        for (k, _, role) in m.iter_nodes() {
+            let node = m.find(k).node_ptr().avl_node_ptr();
+            let children = node.left().not_null() as usize + node.right().not_null() as usize;
+            match role {
+                NodeRole::Root => assert!(node.parent().is_null()),
+                NodeRole::Internal => assert!(children > 0),
+                NodeRole::Leaf => assert_eq!(children, 0),
+            }
+        }
+
+        let m: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+        let roots = m.iter_nodes().filter(|&(_, _, r)| r == NodeRole::Root).count();
+        assert_eq!(roots, m.buckets().count());
     }
-}
 
-impl<K, V, S> Eq for HashMap<K, V, S>
-where
-    K: Ord + Hash,
-    V: Eq,
-    S: BuildHasher,
-{
-}
+    #[test]
+    fn test_hash_map_extend_exact() {
+        let test_num = 100000;
+        let pairs: Vec<_> = (0..test_num).map(|i| (i, i)).collect();
 
-#[cfg(test)]
-mod test {
-    use hash_map::HashMap;
-    use std::cell::RefCell;
-    use hash_map::Entry::*;
+        let mut m = HashMap::new();
+        m.extend_exact(pairs.clone());
+        assert_eq!(m.hash_table.resize_count(), 1);
+        assert_eq!(m.len(), test_num as usize);
+
+        let mut m: HashMap<_, _> = (test_num..test_num + 1000).map(|i| (i, i)).collect();
+        let resizes = m.hash_table.resize_count();
+        m.extend_exact(pairs.clone());
+        assert!(m.hash_table.resize_count() <= resizes + 1);
+        assert_eq!(m.len(), test_num as usize + 1000);
+        assert!((0..test_num + 1000).all(|i| m[&i] == i));
+
+        // extend only reserves half when the map is not empty
+        let mut m: HashMap<_, _> = (test_num..test_num + 1000).map(|i| (i, i)).collect();
+        let resizes = m.hash_table.resize_count();
+        m.extend(pairs);
+        assert!(m.hash_table.resize_count() > resizes + 1);
+    }
 
     #[test]
-    fn test_hash_map() {
-        let mut m = HashMap::new();
-        for i in 100..200 {
-            m.insert(i, -i);
+    fn test_hash_map_entry_bucket_len() {
+        // keys collide by their value divided by 100
+        let mut m = HashMap::with_hasher(FnState(|k| k / 100));
+        m.reserve(100);
+        for i in 0..10u64 {
+            match m.entry(300 + i) {
+                Vacant(v) => {
+                    assert_eq!(v.bucket_len(), i as usize);
+                    v.insert(i);
+                }
+                Occupied(_) => unreachable!(),
+            }
         }
-        assert!(m.contains_key(&100));
-        assert_eq!(m[&111], -111);
-        assert_eq!(m.len(), 100);
-        let mut a = m.first();
-        let mut cnt = 0;
-        while !a.is_null() {
-            cnt += 1;
-            a = m.next(a);
+        match m.entry(305) {
+            Occupied(o) => assert_eq!(o.bucket_len(), 10),
+            Vacant(_) => unreachable!(),
         }
-        assert_eq!(cnt, m.len());
-        let mut a = m.last();
-        let mut cnt = 0;
-        while !a.is_null() {
-            cnt += 1;
-            a = m.prev(a);
+        match m.entry(400) {
+            Vacant(v) => assert_eq!(v.bucket_len(), 0),
+            Occupied(_) => unreachable!(),
         }
-        assert_eq!(cnt, m.len());
-        assert_eq!(*m.get(&111).unwrap(), -111);
-        {
-            let v = m.get_mut(&111).unwrap();
-            *v *= -1;
+        m.insert(401, 0);
+        match m.entry(401) {
+            Occupied(o) => assert_eq!(o.bucket_len(), 1),
+            Vacant(_) => unreachable!(),
         }
-        assert_eq!(*m.get(&111).unwrap(), 111);
-        assert!(m.get(&-100).is_none());
     }
 
     #[test]
-    fn test_hash_map_remove() {
-        struct Node<'a> {
-            b: &'a RefCell<i32>,
+    fn test_hash_map_rekey() {
+        let mut m = HashMap::with_insertion_order();
+        for i in 0..100 {
+            m.insert(i.to_string(), Box::new(i));
         }
-        impl<'a> Drop for Node<'a> {
-            fn drop(&mut self) {
-                *self.b.borrow_mut() += 1;
+        let value_addr = &m["42"] as *const Box<i32>;
+        let heap_addr = &*m["42"] as *const i32;
+        assert!(m.rekey("42", "forty-two".to_string()));
+        assert_eq!(&m["forty-two"] as *const Box<i32>, value_addr);
+        assert_eq!(&*m["forty-two"] as *const i32, heap_addr);
+        assert!(!m.contains_key("42"));
+        assert_eq!(m.len(), 100);
+        // same position in insertion order
+        assert_eq!(m.iter_insertion_order().nth(42).unwrap().0, "forty-two");
+
+        assert!(!m.rekey("42", "x".to_string()));
+        assert!(!m.rekey("1", "2".to_string()));
+        assert_eq!(*m["1"], 1);
+        assert_eq!(*m["2"], 2);
+        assert!(!m.rekey("1", "1".to_string()));
+
+        for i in 0..100 {
+            if i != 42 {
+                assert!(m.rekey(&i.to_string()[..], format!("k{}", i)));
             }
         }
-        let cnt = RefCell::new(0);
-        let test_num = 199;
-        let mut map = HashMap::new();
-        for i in 0..test_num {
-            map.insert(i, Node { b: &cnt });
+        assert!(m.check_consistency());
+        assert!((0..100).filter(|&i| i != 42).all(|i| *m[&format!("k{}", i)[..]] == i));
+    }
+
+    #[test]
+    fn test_hash_map_entries_with_hash() {
+        // keys collide by their value divided by 10, all within bucket 0 up to 99
+        let mut m = HashMap::with_hasher(FnState(|k| k / 10 * 64));
+        for &i in [31u64, 5, 37, 30, 12, 99, 38, 0].iter() {
+            m.insert(i, i * 2);
         }
-        assert_eq!(*cnt.borrow(), 0);
-        for i in 0..test_num / 2 {
-            map.remove(&i);
+        let group: Vec<_> = m.entries_with_hash(3 * 64).map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(group, [(30, 60), (31, 62), (37, 74), (38, 76)]);
+        let group: Vec<_> = m.entries_with_hash(0).map(|(k, _)| *k).collect();
+        assert_eq!(group, [0, 5]);
+        assert!(m.entries_with_hash(2 * 64).next().is_none());
+        assert!(m.entries_with_hash(7).next().is_none());
+        assert_eq!(m.entries_with_hash(9 * 64).count(), 1);
+        let total: usize = (0..10).map(|h| m.entries_with_hash(h * 64).count()).sum();
+        assert_eq!(total, m.len());
+    }
+
+    #[test]
+    fn test_hash_map_bucket_skew() {
+        use hashers::FixedState;
+
+        // hashes the first 100 keys to themselves and all others to 0
+        let mut m = HashMap::with_hasher(FnState(|k| if k < 100 { k } else { 0 }));
+        assert_eq!(m.bucket_skew(), 1.0);
+        for i in 0..100u64 {
+            m.insert(i, i);
         }
-        assert_eq!(*cnt.borrow(), test_num / 2);
-        for i in test_num / 2..test_num {
-            map.insert(i, Node { b: &cnt });
+        assert_eq!(m.bucket_skew(), 1.0);
+        for i in 100..1100u64 {
+            m.insert(i, i);
         }
-        assert_eq!(*cnt.borrow(), test_num);
+        // 1001 entries in bucket 0, one in each of 99 others
+        assert_eq!(m.bucket_skew(), 1001.0 * 100.0 / 1100.0);
+        let before = m.bucket_skew();
+        let m = m.with_new_hasher(FixedState::default());
+        assert!(m.bucket_skew() < before / 10.0);
+        assert!(m.bucket_skew() < 5.0);
     }
 
     #[test]
-    fn test_hash_map_clear() {
-        struct Node<'a> {
-            b: &'a RefCell<i32>,
+    fn test_hash_map_get_ptr() {
+        let mut m = HashMap::new();
+        m.insert("key".to_string(), vec![1, 2, 3]);
+        assert!(m.get_ptr("none").is_none());
+        let ptr = m.get_ptr("key").unwrap();
+        assert_eq!(ptr.as_ptr() as *const _, &m["key"] as *const _);
+        for i in 0..10000 {
+            m.insert(i.to_string(), vec![i]);
         }
-        impl<'a> Drop for Node<'a> {
-            fn drop(&mut self) {
-                *self.b.borrow_mut() += 1;
+        // other inserts and index growth don't move the pair
+        assert_eq!(unsafe { &*ptr.as_ptr() }, &[1, 2, 3]);
+        assert_eq!(m.get_ptr("key"), Some(ptr));
+        for i in 0..10000 {
+            m.remove(&i.to_string());
+        }
+        assert_eq!(unsafe { &*ptr.as_ptr() }, &[1, 2, 3]);
+
+        let mut_ptr = m.get_mut_ptr("key").unwrap();
+        assert_eq!(mut_ptr, ptr);
+        unsafe { (*mut_ptr.as_ptr()).push(4) };
+        assert_eq!(m["key"], [1, 2, 3, 4]);
+        assert!(m.get_mut_ptr("none").is_none());
+    }
+
+    #[test]
+    fn test_hash_map_iter_peek() {
+        let m: HashMap<i32, String> = (0..100).map(|i| (i, i.to_string())).collect();
+        let mut iter = m.iter();
+        assert_eq!(iter.peek(), m.first_key_value());
+        let mut count = 0;
+        loop {
+            let peeked = iter.peek();
+            assert_eq!(iter.peek(), peeked);
+            let next = iter.next();
+            assert_eq!(peeked, next);
+            if next.is_none() {
+                break;
             }
+            count += 1;
         }
-        let cnt = RefCell::new(0);
-        let test_num = 199;
-        let mut map = HashMap::new();
-        for i in 0..test_num {
-            map.insert(i, Node { b: &cnt });
+        assert_eq!(count, 100);
+        assert!(HashMap::<i32, i32>::new().iter().peek().is_none());
+    }
+
+    #[test]
+    fn test_hash_map_defer_rehash() {
+        let test_num = 1_000_000;
+        let mut m = HashMap::new();
+        {
+            let mut bulk = m.defer_rehash();
+            for i in 0..test_num {
+                bulk.insert(i, i);
+            }
+            assert_eq!(bulk.hash_table.resize_count(), 0);
+            assert_eq!(bulk.hash_table.index_size(), 8);
+            assert_eq!(bulk[&4242], 4242);
         }
-        assert_eq!(*cnt.borrow(), 0);
-        for i in 0..test_num / 2 {
-            map.remove(&i);
+        assert_eq!(m.hash_table.resize_count(), 1);
+        assert_eq!(m.len(), test_num as usize);
+        let mut grown: HashMap<i32, i32> = HashMap::new();
+        grown.reserve(test_num as usize);
+        assert_eq!(m.hash_table.index_size(), grown.hash_table.index_size());
+        assert!(m.check_consistency());
+
+        // nested guards resize once, when the outer one drops
+        let mut m = HashMap::new();
+        {
+            let mut outer = m.defer_rehash();
+            {
+                let mut inner = outer.defer_rehash();
+                for i in 0..1000 {
+                    if let Vacant(v) = inner.entry(i) {
+                        v.insert(i);
+                    }
+                }
+            }
+            assert_eq!(outer.hash_table.resize_count(), 0);
+            outer.insert(1000, 1000);
         }
-        assert_eq!(*cnt.borrow(), test_num / 2);
-        map.clear();
-        assert_eq!(*cnt.borrow(), test_num);
+        assert_eq!(m.hash_table.resize_count(), 1);
+        assert!((0..1001).all(|i| m[&i] == i));
+        m.insert(1001, 1001);
+        assert!(m.check_consistency());
+
+        // try_insert_alloc doesn't grow the index array either, at or past the capacity
+        let mut m = HashMap::new();
+        {
+            let mut bulk = m.defer_rehash();
+            for i in 0..1000u32 {
+                if i.is_multiple_of(2) {
+                    assert_eq!(bulk.try_insert_alloc(i, i).ok(), Some(None));
+                } else {
+                    bulk.insert(i, i);
+                }
+                assert_eq!(bulk.hash_table.resize_count(), 0);
+            }
+            assert_eq!(bulk.try_insert_alloc(0, 1).ok(), Some(Some((0, 0))));
+            assert_eq!(bulk.hash_table.resize_count(), 0);
+        }
+        assert_eq!(m.hash_table.resize_count(), 1);
+        assert_eq!(m.len(), 1000);
+        assert!(m.check_consistency());
     }
 
     #[test]
-    fn test_hash_map_insert_duplicate() {
+    fn test_hash_map_defer_rehash_reserve() {
+        let mut m = HashMap::new();
+        {
+            let mut bulk = m.defer_rehash();
+            for i in 0..100 {
+                bulk.insert(i, i);
+            }
+            assert!(bulk.len() > bulk.capacity());
+            bulk.reserve(1000);
+            assert!(bulk.capacity() >= 1100);
+            for i in 100..2000 {
+                bulk.insert(i, i);
+            }
+            assert!(bulk.len() > bulk.capacity());
+            bulk.extend((2000..3000).map(|i| (i, i)));
+            assert!(bulk.capacity() >= 3000);
+            for i in 3000..5000 {
+                bulk.insert(i, i);
+            }
+            bulk.prewarm(1000);
+            assert!(bulk.capacity() >= 6000);
+            for i in 6000..9000 {
+                bulk.insert(i, i);
+            }
+            bulk.entry_batch(9000..10000, |entry| {
+                entry.or_insert(0);
+            });
+            assert!(bulk.capacity() >= 10000);
+            assert!(bulk.check_consistency());
+        }
+        assert_eq!(m.len(), 9000);
+        assert!((0..5000).chain(6000..10000).all(|i| m.contains_key(&i)));
+        assert!(m.check_consistency());
+    }
+
+    #[test]
+    fn test_hash_map_clear_catching() {
         struct Node<'a> {
             b: &'a RefCell<i32>,
+            panics: bool,
         }
         impl<'a> Drop for Node<'a> {
             fn drop(&mut self) {
                 *self.b.borrow_mut() += 1;
+                if self.panics {
+                    panic!("drop {}", *self.b.borrow());
+                }
             }
         }
         let cnt = RefCell::new(0);
         let test_num = 100;
-        let mut map = HashMap::new();
+        let mut m = HashMap::with_insertion_order();
         for i in 0..test_num {
-            map.insert(i, Node { b: &cnt });
-        }
-        assert_eq!(test_num as usize, map.len());
-        assert_eq!(*cnt.borrow(), 0);
-        for i in 0..test_num / 2 {
-            map.insert(i, Node { b: &cnt });
+            m.insert(i, Node { b: &cnt, panics: i % 2 == 0 });
         }
-        assert_eq!(*cnt.borrow(), test_num / 2);
+        let panics = m.clear_catching().err().unwrap();
+        assert_eq!(panics.len(), test_num as usize / 2);
+        assert!(panics.iter().all(|p| p.downcast_ref::<String>().unwrap().starts_with("drop")));
+        assert_eq!(*cnt.borrow(), test_num);
+        assert!(m.is_empty());
+        assert_eq!(m.iter_insertion_order().count(), 0);
+        assert!(m.check_consistency());
+        m.insert(1, Node { b: &cnt, panics: false });
+        assert!(m.clear_catching().is_ok());
+        assert!(m.clear_catching().is_ok());
+        assert_eq!(*cnt.borrow(), test_num + 1);
     }
 
     #[test]
-    fn test_hash_map_keys() {
-        let test_num = 100;
+    fn test_hash_map_scan_mut() {
+        let mut m: HashMap<i32, i64> = (0..1000).map(|i| (i, i as i64)).collect();
+        let (sum, count) = m.scan_mut((0, 0), |st, _, v| {
+            st.0 += *v;
+            st.1 += 1;
+            *v *= 2;
+        });
+        assert_eq!(sum, (0..1000).sum::<i64>());
+        assert_eq!(count, 1000);
+        assert!(m.iter().all(|(&k, &v)| v == k as i64 * 2));
+
+        // visited in iteration order
+        let order: Vec<i32> = m.iter().map(|(k, _)| *k).collect();
+        let seen = m.scan_mut(Vec::new(), |st, k, _| st.push(*k));
+        assert_eq!(seen, order);
+        assert_eq!(HashMap::<i32, i32>::new().scan_mut(7, |st, _, _| *st += 1), 7);
+    }
+
+    #[test]
+    fn test_hash_map_bucket_order() {
+        use hash_map::HashEntryBase;
+        use hash_table::HashNodePtrOperation;
+
         let mut m = HashMap::new();
-        for i in 0..test_num {
-            m.insert(i, -i);
+        for i in 0..1000 {
+            m.insert(i * 7919 % 1000, i);
         }
-        let mut sum = 0;
-        for key in m.keys() {
-            sum += *key;
+        for i in (0..1000).filter(|i| i % 5 == 0) {
+            m.remove(&i);
         }
-        assert_eq!(sum, test_num * (test_num - 1) / 2);
+        let order = m.bucket_order();
+        let mut seen: Vec<usize> = Vec::new();
+        let mut entry = m.first();
+        while !entry.is_null() {
+            let hash = entry.node_ptr().hash_val();
+            let position = m.hash_table.index_position(m.hash_table.get_hash_index(hash));
+            if seen.last() != Some(&position) {
+                seen.push(position);
+            }
+            entry = m.next(entry);
+        }
+        assert_eq!(order, seen);
+        assert_eq!(order.len(), m.buckets().count());
+        let mut sorted = order.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), order.len());
     }
 
     #[test]
-    fn test_hash_map_values() {
-        let test_num = 100;
+    fn test_hash_map_from_columns() {
+        let keys: Vec<String> = (0..100).map(|i| format!("k{}", i)).collect();
+        let values: Vec<i32> = (0..100).collect();
+        let m: HashMap<String, i32> = HashMap::from_columns(keys, values);
+        assert_eq!(m.len(), 100);
+        assert!((0..100).all(|i| m[&format!("k{}", i)] == i));
+
+        let m: HashMap<i32, i32> = HashMap::from_columns(vec![1, 2, 1], vec![10, 20, 30]);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m[&1], 30);
+        assert!(HashMap::<i32, i32>::from_columns(vec![], vec![]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "different lengths")]
+    fn test_hash_map_from_columns_mismatch() {
+        let _: HashMap<i32, i32> = HashMap::from_columns(vec![1, 2, 3], vec![1, 2]);
+    }
+
+    #[test]
+    fn test_hash_map_index_size() {
         let mut m = HashMap::new();
-        for i in 0..test_num {
-            m.insert(i, -i);
+        assert_eq!(m.index_size(), 8);
+        for i in 0..10000 {
+            m.insert(i, i);
+            assert!(m.index_size().is_power_of_two());
+            assert!(m.index_size() >= 8);
         }
-        let mut sum = 0;
-        for value in m.values() {
-            sum += *value;
+        assert_eq!(m.index_size(), m.resize_info().index_size);
+        let grown = m.index_size();
+        for i in 0..9990 {
+            m.remove(&i);
         }
-        assert_eq!(sum, -test_num * (test_num - 1) / 2);
+        m.shrink_to_fit();
+        assert!(m.index_size() < grown);
+        assert!(m.index_size().is_power_of_two());
+        m.clear();
+        m.shrink_to_fit();
+        assert!(m.index_size() >= 8);
+        assert!(m.index_size().is_power_of_two());
     }
 
     #[test]
-    fn test_hash_map_values_mut() {
-        let test_num = 100;
+    fn test_hash_map_entry_batch() {
         let mut m = HashMap::new();
-        for i in 0..test_num {
-            m.insert(i, -i);
-        }
-        let mut sum = 0;
-        for value in m.values_mut() {
-            *value *= 2;
-        }
-        for value in m.values() {
-            sum += *value;
+        m.entry_batch(0..100_000, |entry| *entry.or_insert(0) += 1);
+        assert_eq!(m.len(), 100_000);
+        assert_eq!(m.hash_table.resize_count(), 1);
+        assert!((0..100_000).all(|k| m[&k] == 1));
+
+        // the reserve assumes every key is new, so even a batch of existing keys may grow
+        // the index, but only once
+        let resizes = m.hash_table.resize_count();
+        m.entry_batch((0..100_000).rev(), |entry| *entry.or_insert(0) += 1);
+        assert!(m.hash_table.resize_count() <= resizes + 1);
+        assert!((0..100_000).all(|k| m[&k] == 2));
+
+        // without a size hint, it behaves like for_each_entry
+        let mut m = HashMap::new();
+        m.entry_batch((0..100).filter(|k| k % 2 == 0), |entry| {
+            entry.or_insert(());
+        });
+        assert_eq!(m.len(), 50);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_hash_map_par_retain() {
+        fn slow_pred(k: &u64, v: &u64) -> bool {
+            let mut h = *k;
+            for _ in 0..50 {
+                h = (h ^ (h >> 7)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            }
+            !(h ^ *v).is_multiple_of(3)
         }
-        assert_eq!(sum, -test_num * (test_num - 1));
+
+        let test_num = 1_000_000u64;
+        let mut serial: HashMap<u64, u64> = (0..test_num).map(|i| (i, i * 7)).collect();
+        let mut parallel = serial.clone();
+
+        serial.retain(|k, v| slow_pred(k, v));
+        parallel.par_retain(slow_pred);
+
+        assert!(serial.len() < test_num as usize);
+        assert!(serial == parallel);
+        assert!(parallel.iter().all(|(k, v)| slow_pred(k, v)));
+
+        parallel.par_retain(|_, _| false);
+        assert!(parallel.is_empty());
+        parallel.par_retain(|_, _| true);
+        assert!(parallel.is_empty());
     }
 
     #[test]
-    fn test_hash_map_iter() {
-        let test_num = 100;
+    fn test_hash_map_iter_index_range() {
         let mut m = HashMap::new();
-        for i in 0..test_num {
+        for i in 0..10000 {
             m.insert(i, -i);
         }
-        let mut sum = 0;
-        let mut sum1 = 0;
-        for value in m.iter() {
-            sum += *value.0;
-            sum1 += *value.1;
+        let n = m.index_size();
+        let mut chunks: Vec<(i32, i32)> = m
+            .iter_index_range(0, n / 2)
+            .chain(m.iter_index_range(n / 2, n))
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        assert_eq!(chunks.len(), m.len());
+        chunks.sort();
+        let mut all: Vec<(i32, i32)> = m.iter().map(|(k, v)| (*k, *v)).collect();
+        all.sort();
+        assert_eq!(chunks, all);
+
+        // every entry comes from a bucket inside the range
+        let expect: usize = m
+            .buckets()
+            .filter(|b| n / 4 <= b.index() && b.index() < n / 3)
+            .map(|b| b.iter().count())
+            .sum();
+        assert_eq!(m.iter_index_range(n / 4, n / 3).count(), expect);
+
+        assert_eq!(m.iter_index_range(n, n + 100).count(), 0);
+        assert_eq!(m.iter_index_range(10, 5).count(), 0);
+        assert_eq!(m.iter_index_range(0, usize::MAX).count(), m.len());
+    }
+
+    #[test]
+    fn test_hash_map_entry_hashed() {
+        use std::hash::BuildHasher;
+
+        let mut by_entry = HashMap::new();
+        let mut by_hash = HashMap::new();
+        for i in (0..1000).map(|i| i % 300) {
+            let key = format!("k{}", i);
+            let hash = by_hash.hasher().hash_one(&key);
+            *by_entry.entry(key.clone()).or_insert(0) += 1;
+            match by_hash.entry_hashed(hash, key) {
+                Occupied(mut o) => *o.get_mut() += 1,
+                Vacant(v) => {
+                    v.insert(1);
+                }
+            }
         }
-        assert_eq!(sum, test_num * (test_num - 1) / 2);
-        assert_eq!(sum1, -test_num * (test_num - 1) / 2);
+        assert_eq!(by_hash.len(), 300);
+        assert!(by_entry == by_hash);
+        assert_eq!(by_hash["k0"], 4);
+        assert_eq!(by_hash["k299"], 3);
     }
 
     #[test]
-    fn test_hash_map_iter_mut() {
-        let test_num = 100;
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "hash of the key doesn't match")]
+    fn test_hash_map_entry_hashed_wrong_hash() {
+        use std::hash::BuildHasher;
+
+        let mut m: HashMap<i32, i32> = HashMap::new();
+        let hash = m.hasher().hash_one(1);
+        m.entry_hashed(hash, 2).or_insert(0);
+    }
+
+    #[test]
+    fn test_hash_map_reserve_one() {
         let mut m = HashMap::new();
-        for i in 0..test_num {
-            m.insert(i, -i);
-        }
-        for (_, value) in m.iter_mut() {
-            *value *= 2;
+        let mut inserts_allocated = 0;
+        for i in 0..10000 {
+            // right before every insert, including those which would grow the index array
+            // or need a new fastbin page
+            m.reserve_one();
+            let (memory, resizes) = (m.memory_usage(), m.hash_table.resize_count());
+            m.insert(i, i);
+            if m.memory_usage() != memory || m.hash_table.resize_count() != resizes {
+                inserts_allocated += 1;
+            }
         }
-        let mut sum = 0;
-        let mut sum1 = 0;
-        for value in m.iter() {
-            sum += *value.0;
-            sum1 += *value.1;
+        assert_eq!(inserts_allocated, 0);
+        assert!(m.hash_table.resize_count() > 5);
+
+        // until the threshold, the headroom is already there
+        m.reserve_one();
+        assert!(!m.reserve_one());
+        while m.capacity() > m.len() {
+            let next = m.len() as i32;
+            m.insert(next, 0);
         }
-        assert_eq!(sum, test_num * (test_num - 1) / 2);
-        assert_eq!(sum1, -test_num * (test_num - 1));
+        assert!(m.reserve_one());
+        let resizes = m.hash_table.resize_count();
+        m.insert(-1, 0);
+        assert_eq!(m.hash_table.resize_count(), resizes);
     }
 
     #[test]
-    fn test_hash_map_into_iter() {
-        let test_num = 100;
-        let mut a = HashMap::new();
-        let mut b = HashMap::new();
-        for i in 0..test_num as i32 {
-            a.insert(i, -i);
+    fn test_hash_map_entry_insert() {
+        let mut m = HashMap::new();
+        {
+            let o = m.entry(1).insert(10);
+            assert_eq!((o.key(), o.get()), (&1, &10));
         }
-        for i in 0..test_num as i32 {
-            b.insert(-i, i);
+        assert_eq!(m[&1], 10);
+        {
+            let mut o = m.entry(1).insert(20);
+            assert_eq!(o.get(), &20);
+            *o.get_mut() += 1;
         }
-        assert_eq!(a.len(), test_num);
-        assert_eq!(b.len(), test_num);
-        a.extend(b.into_iter());
-        assert_eq!(a.len(), test_num * 2 - 1);
+        assert_eq!(m[&1], 21);
+        assert_eq!(m.len(), 1);
+
+        // or_insert keeps the existing value, insert doesn't
+        *m.entry(1).or_insert(0) += 1;
+        assert_eq!(m[&1], 22);
+        for i in 0..1000 {
+            let o = m.entry(i).insert(-i);
+            assert_eq!(*o.get(), -i);
+        }
+        assert_eq!(m.len(), 1000);
+        assert!((0..1000).all(|i| m[&i] == -i));
+
+        // a vacant entry turned occupied can be removed at once
+        let (k, v) = m.entry(5000).insert(1).remove_entry();
+        assert_eq!((k, v), (5000, 1));
+        assert!(!m.contains_key(&5000));
     }
 
     #[test]
-    fn test_hash_map_shrink_to_fit() {
-        struct Node<'a> {
-            b: &'a RefCell<i32>,
-        }
-        impl<'a> Drop for Node<'a> {
-            fn drop(&mut self) {
-                *self.b.borrow_mut() += 1;
+    fn test_hash_map_check_no_aliasing() {
+        use hash_map::HashEntryBase;
+        use hash_table::HashNodePtrOperation;
+
+        let mut plain = HashMap::new();
+        let mut ordered = HashMap::with_insertion_order();
+        let mut collided = HashMap::with_hasher(CollisionState);
+        for _ in 0..20000 {
+            let k = rand::random::<u16>() % 2000;
+            match rand::random::<u8>() % 4 {
+                0 | 1 => {
+                    plain.insert(k, k.to_string());
+                    ordered.insert(k, k.to_string());
+                    collided.insert(k, k.to_string());
+                }
+                2 => {
+                    plain.remove(&k);
+                    ordered.remove(&k);
+                    collided.remove(&k);
+                }
+                _ => {
+                    plain.rekey(&k, k / 2 + 2000);
+                    *ordered.entry(k).or_insert(String::new()) += "x";
+                    collided.retain(|key, _| *key != k);
+                }
+            }
+            if k.is_multiple_of(64) {
+                plain.shrink_to_fit();
+            }
+            if k.is_multiple_of(100) {
+                assert!(plain.check_no_aliasing());
+                assert!(ordered.check_no_aliasing());
+                assert!(collided.check_no_aliasing());
             }
         }
-        let cnt = RefCell::new(0);
-        let test_num = 200;
-        let mut map = HashMap::new();
-        for i in 0..test_num {
-            map.insert(i, Node { b: &cnt });
-        }
-        for i in 10..test_num {
-            map.remove(&i);
-        }
-        assert_eq!(*cnt.borrow(), test_num - 10);
-        assert!(map.capacity() >= test_num as usize);
-        map.shrink_to_fit();
-        assert!(map.capacity() < test_num as usize);
-        assert!(map.capacity() >= 10);
-        assert_eq!(*cnt.borrow(), test_num - 10);
-        drop(map);
-        assert_eq!(*cnt.borrow(), test_num);
+        assert!(plain.check_no_aliasing());
+        assert!(ordered.check_no_aliasing());
+        assert!(collided.check_no_aliasing());
+        plain.clear();
+        assert!(plain.check_no_aliasing());
+
+        // two entries claiming the same key are caught
+        let m: HashMap<u16, u16> = (0..10).map(|i| (i, i)).collect();
+        let (a, b) = (m.find(&1), m.find(&2));
+        let b_key = b.key();
+        b.node_ptr().set_key_ptr(a.key());
+        assert!(!m.check_no_aliasing());
+        b.node_ptr().set_key_ptr(b_key);
+        assert!(m.check_no_aliasing());
+        // nor a value paired with the key of another entry
+        let b_value = b.value();
+        b.set_value(a.value());
+        assert!(!m.check_no_aliasing());
+        b.set_value(b_value);
+        assert!(m.check_no_aliasing());
     }
 
     #[test]
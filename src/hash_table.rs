@@ -10,11 +10,14 @@ use std::hash::Hasher;
 use std::cmp;
 use std::borrow::Borrow;
 use std::cmp::Ordering;
-use libc::{c_void, free, malloc};
+use libc::{c_void, free};
+use fastbin::malloc;
+use comparator::Comparator;
 
 pub type HashUint = usize;
 
-const AVL_HASH_INIT_SIZE: usize = 8;
+/// The default number of HashIndex slots stored inline in the table.
+pub const AVL_HASH_INIT_SIZE: usize = 8;
 
 const DEFAULT_AVL_NODE: AVLNode = AVLNode {
     left: ptr::null_mut(),
@@ -127,13 +130,33 @@ impl HashIndexPtrOperation for *mut HashIndex {
     }
 }
 
-pub struct HashTable<K, V> {
+/// How many times a `HashTable` has been modified. Only counted in debug builds, where it lets
+/// iterators assert the table didn't change under them; in release builds it's empty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModCount {
+    #[cfg(debug_assertions)]
     count: usize,
+}
+
+impl ModCount {
+    #[inline]
+    fn bump(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.count = self.count.wrapping_add(1);
+        }
+    }
+}
+
+pub struct HashTable<K, V, const INIT: usize> {
+    count: usize,
+    mod_count: ModCount,
     index_size: usize,
     index_mask: usize,
+    resize_count: usize,
     head: ListHead,
     index: *mut HashIndex,
-    init: [HashIndex; AVL_HASH_INIT_SIZE],
+    init: [HashIndex; INIT],
     _marker: marker::PhantomData<(K, V)>,
 }
 
@@ -159,23 +182,54 @@ where
     state.finish() as HashUint
 }
 
+/// Hint the CPU to start loading the node a descent may visit next.
+#[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+#[inline(always)]
+fn prefetch_node(node: AVLNodePtr) {
+    use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+    // prefetching never faults, even for null
+    unsafe { _mm_prefetch::<_MM_HINT_T0>(node as *const i8) }
+}
+
+#[cfg(not(all(feature = "prefetch", target_arch = "x86_64")))]
+#[inline(always)]
+fn prefetch_node(_: AVLNodePtr) {}
+
 #[inline]
 pub fn calc_limit(capacity: usize) -> usize {
     capacity.saturating_mul(6usize) / 4usize
 }
 
 #[inline]
-pub unsafe fn find_duplicate_hash_node<K>(
-    mut link: *mut AVLNodePtr,
+pub unsafe fn find_duplicate_hash_node<K, C>(
+    link: *mut AVLNodePtr,
     new_key: *mut K,
     hash_val: HashUint,
+    cmp: &C,
 ) -> (*mut HashNode<K>, AVLNodePtr, *mut AVLNodePtr)
 where
-    K: Ord,
+    C: Comparator<K>,
+{
+    find_hash_node_link(link, &*new_key, hash_val, cmp)
+}
+
+/// Like `find_duplicate_hash_node`, but searches by any borrowed form of the key.
+#[inline]
+pub unsafe fn find_hash_node_link<K, Q: ?Sized, C>(
+    mut link: *mut AVLNodePtr,
+    q: &Q,
+    hash_val: HashUint,
+    cmp: &C,
+) -> (*mut HashNode<K>, AVLNodePtr, *mut AVLNodePtr)
+where
+    K: Borrow<Q>,
+    C: Comparator<Q>,
 {
     let mut parent = ptr::null_mut();
     while !(*link).is_null() {
         parent = *link;
+        prefetch_node(parent.left());
+        prefetch_node(parent.right());
         let snode = parent.avl_hash_deref_mut::<K>();
         let snode_hash = snode.hash_val();
         if hash_val != snode_hash {
@@ -185,7 +239,7 @@ where
                 &mut (*parent).right
             };
         } else {
-            match (*new_key).cmp(&(*snode.key_ptr())) {
+            match cmp.compare(q, (*snode.key_ptr()).borrow()) {
                 Ordering::Equal => {
                     return (snode, parent, link);
                 }
@@ -201,22 +255,24 @@ where
     (ptr::null_mut(), parent, link)
 }
 
-impl<K, V> HashTable<K, V>
+impl<K, V, const INIT: usize> HashTable<K, V, INIT>
 where
     K: Ord + Hash,
 {
     #[inline]
-    pub fn hash_find<Q: ?Sized>(&self, hash_val: HashUint, q: &Q) -> *mut HashNode<K>
+    pub fn hash_find<Q: ?Sized, C>(&self, hash_val: HashUint, q: &Q, cmp: &C) -> *mut HashNode<K>
     where
         K: Borrow<Q>,
-        Q: Ord,
+        C: Comparator<Q>,
     {
         let mut avl_node = self.get_hash_index(hash_val).avl_root_node();
         while avl_node.not_null() {
+            prefetch_node(avl_node.left());
+            prefetch_node(avl_node.right());
             let snode = avl_node.avl_hash_deref_mut::<K>();
             let shash_val = snode.hash_val();
             if hash_val == shash_val {
-                match unsafe { q.cmp((*snode.key_ptr()).borrow()) } {
+                match unsafe { cmp.compare(q, (*snode.key_ptr()).borrow()) } {
                     Ordering::Equal => {
                         return snode;
                     }
@@ -238,11 +294,42 @@ where
         ptr::null_mut::<HashNode<K>>()
     }
 
-    pub fn hash_swap(
+    /// The number of nodes `hash_find` visits to find `q`, or to find it is absent.
+    pub fn hash_probe_cost<Q: ?Sized, C>(&self, hash_val: HashUint, q: &Q, cmp: &C) -> usize
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let mut avl_node = self.get_hash_index(hash_val).avl_root_node();
+        let mut cost = 0;
+        while avl_node.not_null() {
+            cost += 1;
+            let snode = avl_node.avl_hash_deref_mut::<K>();
+            let shash_val = snode.hash_val();
+            let ord = if hash_val == shash_val {
+                unsafe { cmp.compare(q, (*snode.key_ptr()).borrow()) }
+            } else {
+                hash_val.cmp(&shash_val)
+            };
+            avl_node = match ord {
+                Ordering::Equal => break,
+                Ordering::Less => avl_node.left(),
+                Ordering::Greater => avl_node.right(),
+            };
+        }
+        cost
+    }
+
+    pub fn hash_swap<C>(
         &mut self,
         new_index: *mut HashIndex,
         new_index_size: usize,
-    ) -> *mut HashIndex {
+        cmp: &C,
+    ) -> *mut HashIndex
+    where
+        C: Comparator<K>,
+    {
+        self.mod_count.bump();
         let old_index = self.index;
         let mut head = ListHead::default();
         let head_ptr = &mut head as ListHeadPtr;
@@ -263,7 +350,7 @@ where
         self.head_ptr().list_init();
         while !head_ptr.list_is_empty() {
             let index = head.next.hash_index_deref_mut();
-            self.recursive_hash_add(index.avl_root_node());
+            self.tear_hash_add(index.avl_root_node(), cmp);
             index.node_ptr().list_del_init();
         }
         return if old_index == self.init.as_mut_ptr() {
@@ -273,47 +360,85 @@ where
         };
     }
 
-    fn recursive_hash_add(&mut self, node: AVLNodePtr) {
-        if node.left().not_null() {
-            self.recursive_hash_add(node.left());
-        }
-        if node.right().not_null() {
-            self.recursive_hash_add(node.right());
-        }
-        let snode = node.avl_hash_deref_mut::<K>();
-        unsafe {
-            self.hash_add(snode);
+    /// Move every node of an old AVL into the current index. The old tree is torn down in
+    /// post-order by `avl_node_tear`, so no recursion is needed, however deep the bucket is.
+    fn tear_hash_add<C>(&mut self, node: AVLNodePtr, cmp: &C)
+    where
+        C: Comparator<K>,
+    {
+        let mut root = AVLRoot { node };
+        let mut next = ptr::null_mut();
+        loop {
+            let node = unsafe { avl_node::avl_node_tear(&mut root, &mut next) };
+            if node.is_null() {
+                break;
+            }
+            let snode = node.avl_hash_deref_mut::<K>();
+            unsafe {
+                self.hash_add(snode, cmp);
+            }
         }
     }
 
     #[inline]
-    pub fn rehash(&mut self, len: usize) {
+    pub fn rehash<C>(&mut self, len: usize, cmp: &C)
+    where
+        C: Comparator<K>,
+    {
         let old_index_size = self.index_size;
-        let limit = calc_limit(len);
-        if old_index_size >= limit {
+        let need = self.grown_index_size(len);
+        if need == old_index_size {
             return;
         }
-        let mut need = old_index_size;
-        while need < limit {
-            need = need.saturating_mul(2usize);
+        self.resize_index(need, cmp);
+    }
+
+    /// Like `rehash`, but returns `false` instead of panicking if the index array can't be
+    /// allocated, leaving the table as it was.
+    pub fn try_rehash<C>(&mut self, len: usize, cmp: &C) -> bool
+    where
+        C: Comparator<K>,
+    {
+        let need = self.grown_index_size(len);
+        need == self.index_size || self.try_resize_index(need, cmp)
+    }
+
+    /// Move every node into a newly allocated index array of exactly `need` slots, which must
+    /// be a power of two. Stored hash values are reused, keys are never hashed again.
+    pub fn resize_index<C>(&mut self, need: usize, cmp: &C)
+    where
+        C: Comparator<K>,
+    {
+        if !self.try_resize_index(need, cmp) {
+            panic!("memory overflow");
         }
-        let buffer = unsafe {
-            let (new_alloc_size, oflo) = need.overflowing_mul(mem::size_of::<HashIndex>());
-            if oflo {
-                panic!("capacity overflow");
-            }
-            let buffer = malloc(new_alloc_size) as *mut HashIndex;
-            if buffer.is_null() {
-                panic!("memory overflow");
-            }
-            buffer
+    }
+
+    /// Like `resize_index`, but returns `false` instead of panicking if the index array can't
+    /// be allocated, leaving the table as it was.
+    pub fn try_resize_index<C>(&mut self, need: usize, cmp: &C) -> bool
+    where
+        C: Comparator<K>,
+    {
+        debug_assert!(need.is_power_of_two());
+        let new_alloc_size = match need.checked_mul(mem::size_of::<HashIndex>()) {
+            Some(x) if x <= isize::MAX as usize => x,
+            _ => panic!("capacity overflow"),
         };
-        let data_ptr = self.hash_swap(buffer, need);
+        let buffer = unsafe { malloc(new_alloc_size) as *mut HashIndex };
+        if buffer.is_null() {
+            return false;
+        }
+        let data_ptr = self.hash_swap(buffer, need, cmp);
         if !data_ptr.is_null() {
             unsafe {
                 free(data_ptr as *mut c_void);
             }
         }
+        self.resize_count += 1;
+        debug_assert!(self.check_consistency());
+        debug_assert!(self.check_no_aliasing());
+        true
     }
 
     pub fn new_with_box() -> Box<Self> {
@@ -323,12 +448,13 @@ where
     }
 }
 
-impl<K, V> HashTable<K, V> {
+impl<K, V, const INIT: usize> HashTable<K, V, INIT> {
     #[inline]
-    pub unsafe fn hash_add(&mut self, new_node: *mut HashNode<K>) -> *mut HashNode<K>
+    pub unsafe fn hash_add<C>(&mut self, new_node: *mut HashNode<K>, cmp: &C) -> *mut HashNode<K>
     where
-        K: Ord,
+        C: Comparator<K>,
     {
+        self.mod_count.bump();
         let hash_val = new_node.hash_val();
         let index = self.get_hash_index(hash_val);
         let link = index.avl_root_node_ptr();
@@ -344,7 +470,7 @@ impl<K, V> HashTable<K, V> {
             return ptr::null_mut();
         }
         let (duplicate, parent, link) =
-            find_duplicate_hash_node(link, new_node.key_ptr(), hash_val);
+            find_duplicate_hash_node(link, new_node.key_ptr(), hash_val, cmp);
         if !duplicate.is_null() {
             avl_node::avl_node_replace(
                 duplicate.avl_node_ptr(),
@@ -353,6 +479,59 @@ impl<K, V> HashTable<K, V> {
             );
             return duplicate;
         }
+        self.link_new_node(index, new_node, parent, link);
+        ptr::null_mut()
+    }
+
+    /// Like `hash_add`, but the caller guarantees no node with an equal key is in the table,
+    /// so the descent never checks for a duplicate. Debug builds still assert it.
+    #[inline]
+    pub unsafe fn hash_add_unique<C>(&mut self, new_node: *mut HashNode<K>, cmp: &C)
+    where
+        C: Comparator<K>,
+    {
+        self.mod_count.bump();
+        let hash_val = new_node.hash_val();
+        let index = self.get_hash_index(hash_val);
+        let mut link = index.avl_root_node_ptr();
+        let new_avl_node = new_node.avl_node_ptr();
+        if (*link).is_null() {
+            (*link) = new_avl_node;
+            ptr::write(new_avl_node, DEFAULT_AVL_NODE);
+            self.head_ptr().list_add_tail(index.node_ptr());
+            self.count += 1;
+            return;
+        }
+        let mut parent = ptr::null_mut();
+        while !(*link).is_null() {
+            parent = *link;
+            let snode = parent.avl_hash_deref_mut::<K>();
+            let snode_hash = snode.hash_val();
+            let go_left = if hash_val != snode_hash {
+                hash_val < snode_hash
+            } else {
+                let ord = cmp.compare(&*new_node.key_ptr(), &*snode.key_ptr());
+                debug_assert!(ord != Ordering::Equal, "duplicate key");
+                ord == Ordering::Less
+            };
+            link = if go_left {
+                &mut (*parent).left
+            } else {
+                &mut (*parent).right
+            };
+        }
+        self.link_new_node(index, new_node, parent, link);
+    }
+
+    #[inline]
+    unsafe fn link_new_node(
+        &mut self,
+        index: *mut HashIndex,
+        new_node: *mut HashNode<K>,
+        parent: AVLNodePtr,
+        link: *mut AVLNodePtr,
+    ) {
+        let new_avl_node = new_node.avl_node_ptr();
         debug_assert_ne!(parent, new_avl_node);
         self.count += 1;
         avl_node::link_node(new_avl_node, parent, link);
@@ -362,9 +541,59 @@ impl<K, V> HashTable<K, V> {
             (*avl_root_node).height = 2;
             (*new_avl_node).height = 1;
         } else {
-            avl_node::node_post_insert(new_node.avl_node_ptr(), index.avl_root_ptr());
+            avl_node::node_post_insert(new_avl_node, index.avl_root_ptr());
         }
-        ptr::null_mut()
+    }
+
+    /// Hang a complete, balanced AVL of `nodes` nodes under the HashIndex of its root, which
+    /// must be empty. Every node must map to that HashIndex and be ordered like `hash_add`
+    /// would order it.
+    pub unsafe fn attach_tree(&mut self, root: AVLNodePtr, nodes: usize) {
+        self.mod_count.bump();
+        let index = self.get_hash_index(root.avl_hash_deref_mut::<K>().hash_val());
+        debug_assert!(index.avl_root_node().is_null());
+        debug_assert!(root.parent().is_null());
+        index.set_avl_root_node(root);
+        self.head_ptr().list_add_tail(index.node_ptr());
+        self.count += nodes;
+    }
+
+    /// Whether any node has the hash value `hash_val`, found by hash values only.
+    #[inline]
+    pub fn contains_hash(&self, hash_val: HashUint) -> bool {
+        let mut avl_node = self.get_hash_index(hash_val).avl_root_node();
+        while avl_node.not_null() {
+            let shash_val = avl_node.avl_hash_deref_mut::<K>().hash_val();
+            if hash_val == shash_val {
+                return true;
+            }
+            avl_node = if hash_val < shash_val {
+                avl_node.left()
+            } else {
+                avl_node.right()
+            };
+        }
+        false
+    }
+
+    /// Find the first node, in the order of its AVL, whose hash value is `hash_val`. All nodes
+    /// sharing the hash value follow it in order.
+    pub fn hash_find_first(&self, hash_val: HashUint) -> *mut HashNode<K> {
+        let mut avl_node = self.get_hash_index(hash_val).avl_root_node();
+        let mut res = ptr::null_mut();
+        while avl_node.not_null() {
+            let snode = avl_node.avl_hash_deref_mut::<K>();
+            let shash_val = snode.hash_val();
+            if hash_val <= shash_val {
+                if hash_val == shash_val {
+                    res = snode;
+                }
+                avl_node = avl_node.left();
+            } else {
+                avl_node = avl_node.right();
+            }
+        }
+        res
     }
 
     #[inline]
@@ -372,9 +601,50 @@ impl<K, V> HashTable<K, V> {
         self.index_size
     }
 
+    #[inline]
+    pub fn is_index_inline(&self) -> bool {
+        ptr::eq(self.index, self.init.as_ptr())
+    }
+
+    /// The index size `rehash(len)` would resize to, or the current one if no growth is needed.
+    pub fn grown_index_size(&self, len: usize) -> usize {
+        // every step is checked, a wrapped size would produce a too small index array
+        let limit = match len.checked_mul(6usize) {
+            Some(x) => x / 4usize,
+            None => panic!("capacity overflow"),
+        };
+        let mut need = self.index_size;
+        while need < limit {
+            need = match need.checked_mul(2usize) {
+                Some(x) => x,
+                None => panic!("capacity overflow"),
+            };
+        }
+        need
+    }
+
+    /// How many times the index array was reallocated.
+    #[inline]
+    pub fn resize_count(&self) -> usize {
+        self.resize_count
+    }
+
+    /// The number of bytes of the index array allocated on the heap, 0 while the inline array
+    /// is used.
+    #[inline]
+    pub fn heap_size(&self) -> usize {
+        if ptr::eq(self.index, self.init.as_ptr()) {
+            0
+        } else {
+            self.index_size * mem::size_of::<HashIndex>()
+        }
+    }
+
+    /// The maximum count of nodes before `rehash` has to grow the index, which is the largest
+    /// `len` satisfying `calc_limit(len) <= index_size`.
     #[inline]
     pub fn capacity(&self) -> usize {
-        self.index_size
+        (self.index_size * 4 + 3) / 6
     }
 
     pub fn get_max_node_of_single_index(&self) -> i32 {
@@ -390,8 +660,97 @@ impl<K, V> HashTable<K, V> {
         num
     }
 
+    /// `histogram[i]` is the number of HashIndexes holding exactly `i` nodes. Only the
+    /// non-empty HashIndexes are walked, the rest are counted as empty.
+    pub fn bucket_len_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![0usize];
+        let mut non_empty = 0;
+        let mut head = self.head.next;
+        while !self.head.is_eq_ptr(head) {
+            let num = head.hash_index_deref_mut().avl_root_node().get_node_num() as usize;
+            if histogram.len() <= num {
+                histogram.resize(num + 1, 0);
+            }
+            histogram[num] += 1;
+            non_empty += 1;
+            head = head.next();
+        }
+        histogram[0] = self.index_size - non_empty;
+        histogram
+    }
+
+    /// Verify the bookkeeping of the table: `index_size` is a power of two and `index_mask`
+    /// matches it, every non-empty HashIndex is on the `head` list exactly once and no empty
+    /// one is, every node sits under the HashIndex its hash value maps to, every AVL is
+    /// balanced, and `count` is the number of nodes.
+    pub fn check_consistency(&self) -> bool {
+        if !self.index_size.is_power_of_two() || self.index_mask != self.index_size - 1 {
+            return false;
+        }
+        let mut non_empty = 0;
+        let mut nodes = 0;
+        for i in 0..self.index_size {
+            let index = unsafe { self.index.add(i) };
+            let root = index.avl_root_node();
+            if root.is_null() {
+                if !index.node_ptr().list_is_empty() {
+                    return false;
+                }
+                continue;
+            }
+            if index.node_ptr().list_is_empty() || !root.parent().is_null() || !root.check_valid()
+            {
+                return false;
+            }
+            non_empty += 1;
+            let mut node = root.first_node();
+            while node.not_null() {
+                if node.avl_hash_deref_mut::<K>().hash_val() & self.index_mask != i {
+                    return false;
+                }
+                nodes += 1;
+                node = node.next();
+            }
+        }
+        // a list longer than the number of non-empty HashIndex must revisit one of them
+        let mut listed = 0;
+        let mut head = self.head.next;
+        while !self.head.is_eq_ptr(head) {
+            listed += 1;
+            if listed > non_empty || head.hash_index_deref_mut().avl_root_node().is_null() {
+                return false;
+            }
+            head = head.next();
+        }
+        listed == non_empty && nodes == self.count
+    }
+
+    /// Return true if no two live nodes share an address, nor the address of their key, and
+    /// walking the nodes visits exactly `count` of them. Meant for tests and fuzzing, it takes
+    /// O(n log n) time.
+    pub fn check_no_aliasing(&self) -> bool {
+        let mut nodes = Vec::with_capacity(self.count);
+        let mut keys = Vec::with_capacity(self.count);
+        let mut node = self.node_first();
+        while !node.is_null() {
+            if nodes.len() == self.count {
+                return false;
+            }
+            nodes.push(node as usize);
+            keys.push(node.key_ptr() as usize);
+            node = self.node_next(node);
+        }
+        if nodes.len() != self.count {
+            return false;
+        }
+        nodes.sort_unstable();
+        keys.sort_unstable();
+        nodes.windows(2).all(|w| w[0] != w[1]) && keys.windows(2).all(|w| w[0] != w[1])
+    }
+
     #[inline]
     pub fn pop_first_index(&mut self) -> AVLNodePtr {
+        self.mod_count.bump();
         let head = self.head.next;
         if self.head.is_eq_ptr(head) {
             return ptr::null_mut();
@@ -404,6 +763,18 @@ impl<K, V> HashTable<K, V> {
         avl_node
     }
 
+    #[inline]
+    pub fn mod_count(&self) -> ModCount {
+        self.mod_count
+    }
+
+    /// Asserts, in debug builds only, that the table hasn't been modified since `seen` was
+    /// taken.
+    #[inline]
+    pub fn check_mod_count(&self, seen: ModCount) {
+        debug_assert!(self.mod_count == seen, "HashMap modified during iteration");
+    }
+
     #[inline]
     pub fn size(&self) -> usize {
         self.count
@@ -411,22 +782,26 @@ impl<K, V> HashTable<K, V> {
 
     #[inline]
     pub fn inc_count(&mut self, cnt: usize) {
+        self.mod_count.bump();
         self.count += cnt;
     }
 
     #[inline]
     pub fn dec_count(&mut self, cnt: usize) {
+        self.mod_count.bump();
         self.count -= cnt;
     }
 
     fn new() -> Self {
         HashTable {
             count: 0,
+            mod_count: ModCount::default(),
             index_size: 0,
             index_mask: 0,
+            resize_count: 0,
             head: Default::default(),
             index: ptr::null_mut(),
-            init: [HashIndex::default(); AVL_HASH_INIT_SIZE],
+            init: [HashIndex::default(); INIT],
             _marker: marker::PhantomData,
         }
     }
@@ -439,11 +814,12 @@ impl<K, V> HashTable<K, V> {
     #[inline]
     pub fn init(&mut self) {
         self.count = 0;
-        self.index_size = AVL_HASH_INIT_SIZE;
+        assert!(INIT.is_power_of_two(), "inline index size must be a power of two");
+        self.index_size = INIT;
         self.index_mask = self.index_size - 1;
         self.head_ptr().list_init();
         self.index = self.init.as_mut_ptr();
-        for i in 0..AVL_HASH_INIT_SIZE {
+        for i in 0..INIT {
             unsafe {
                 (*self.index.offset(i as isize)).avl_root.node = ptr::null_mut();
                 (&mut (*self.index.offset(i as isize)).node as ListHeadPtr).list_init();
@@ -479,6 +855,39 @@ impl<K, V> HashTable<K, V> {
         return ptr::null_mut();
     }
 
+    /// The first non-empty HashIndex in the `head` list, or null if the table is empty.
+    #[inline]
+    pub fn index_first(&self) -> *mut HashIndex {
+        let head = self.head.next;
+        if self.head.is_eq_ptr(head) {
+            return ptr::null_mut();
+        }
+        head.hash_index_deref_mut()
+    }
+
+    /// The non-empty HashIndex following `index` in the `head` list, or null.
+    #[inline]
+    pub fn index_next(&self, index: *mut HashIndex) -> *mut HashIndex {
+        let next = index.node_ptr().next();
+        if self.head.is_eq_ptr(next) {
+            return ptr::null_mut();
+        }
+        next.hash_index_deref_mut()
+    }
+
+    /// The position of `index` in the index array.
+    #[inline]
+    pub fn index_position(&self, index: *mut HashIndex) -> usize {
+        (index as usize - self.index as usize) / mem::size_of::<HashIndex>()
+    }
+
+    /// The HashIndex at `position` in the index array, which must be below `index_size`.
+    #[inline]
+    pub fn index_at(&self, position: usize) -> *mut HashIndex {
+        debug_assert!(position < self.index_size());
+        unsafe { self.index.add(position) }
+    }
+
     #[inline]
     pub fn get_hash_index(&self, hash_val: HashUint) -> *mut HashIndex {
         unsafe { self.index.offset((hash_val & self.index_mask) as isize) }
@@ -530,6 +939,7 @@ impl<K, V> HashTable<K, V> {
 
     #[inline]
     pub fn hash_erase(&mut self, node: *mut HashNode<K>) {
+        self.mod_count.bump();
         debug_assert!(!node.avl_node_ptr().empty());
         let index = self.get_hash_index(node.hash_val());
         if index.avl_root_node().height() == 1 {
@@ -545,7 +955,7 @@ impl<K, V> HashTable<K, V> {
     }
 }
 
-impl<K, V> Drop for HashTable<K, V> {
+impl<K, V, const INIT: usize> Drop for HashTable<K, V, INIT> {
     fn drop(&mut self) {
         if self.index != self.init.as_mut_ptr() {
             unsafe {
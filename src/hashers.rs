@@ -0,0 +1,295 @@
+//! `BuildHasher`s for `HashMap` besides the default `RandomState`.
+
+use std::hash::{BuildHasher, Hasher};
+
+const FX_SEED: u64 = 0x517c_c1b7_2722_0a95;
+
+/// A fast hasher in the style of FxHash, starting from a fixed state.
+///
+/// Every word is folded in with a rotate, a xor and a multiplication, so hashing is cheap and
+/// the same input always gives the same hash value, on every run and every machine with the
+/// same endianness.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedHasher {
+    hash: u64,
+}
+
+impl FixedHasher {
+    /// Creates a `FixedHasher` starting from `seed`.
+    #[inline]
+    pub fn with_seed(seed: u64) -> Self {
+        FixedHasher { hash: seed }
+    }
+
+    #[inline]
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED);
+    }
+}
+
+impl Default for FixedHasher {
+    #[inline]
+    fn default() -> Self {
+        FixedHasher::with_seed(0)
+    }
+}
+
+impl Hasher for FixedHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            self.add_to_hash(u64::from_le_bytes(word));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0u8; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add_to_hash(u64::from_le_bytes(word));
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A `BuildHasher` creating `FixedHasher`s with a fixed seed.
+///
+/// Unlike a randomly seeded state, maps built with equal `FixedState`s and the same inserts
+/// have the same bucket layout and iterate in the same order, which keeps benchmarks and tests
+/// reproducible.
+///
+/// # Examples
+///
+/// ```
+/// use hash_ord::hash_map::HashMap;
+/// use hash_ord::hashers::FixedState;
+///
+/// let mut a = HashMap::with_hasher(FixedState::default());
+/// let mut b = HashMap::with_hasher(FixedState::default());
+/// for i in 0..100 {
+///     a.insert(i, i);
+///     b.insert(i, i);
+/// }
+/// assert!(a.iter().eq(b.iter()));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FixedState {
+    seed: u64,
+}
+
+impl FixedState {
+    /// Creates a `FixedState` whose hashers start from `seed`.
+    #[inline]
+    pub fn with_seed(seed: u64) -> Self {
+        FixedState { seed }
+    }
+}
+
+impl BuildHasher for FixedState {
+    type Hasher = FixedHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> FixedHasher {
+        FixedHasher::with_seed(self.seed)
+    }
+}
+
+/// A hasher for integer keys which keeps the bits of the key, only mixing them with a
+/// multiplication so that keys with equal low bits don't all land in the same HashIndex.
+///
+/// This is far cheaper than a general purpose hash. It is not a good hash against an
+/// adversary, but every HashIndex of `HashMap` is an AVL, so even the worst integer keys only
+/// make lookups degrade to O(log n).
+///
+/// Other input is folded in like `FixedHasher` does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityHasher {
+    hash: u64,
+}
+
+impl IdentityHasher {
+    #[inline]
+    fn set(&mut self, i: u64) {
+        self.hash = self.hash.rotate_left(5) ^ i;
+    }
+}
+
+impl Hasher for IdentityHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut hasher = FixedHasher::with_seed(self.hash);
+        hasher.write(bytes);
+        self.hash = hasher.finish();
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.set(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.set(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.set(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.set(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.set(i as u64);
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.set(i as u8 as u64);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.set(i as u16 as u64);
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.set(i as u32 as u64);
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.set(i as u64);
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.set(i as usize as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        // the index is taken from the low bits, so fold the well mixed high half into them
+        let h = self.hash.wrapping_mul(FX_SEED);
+        h ^ (h >> 32)
+    }
+}
+
+/// A `BuildHasher` creating `IdentityHasher`s, for maps keyed by integers.
+///
+/// # Examples
+///
+/// ```
+/// use hash_ord::hash_map::HashMap;
+///
+/// let mut map = HashMap::with_identity_hasher();
+/// map.insert(7u64, "seven");
+/// assert_eq!(map.get(&7), Some(&"seven"));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IdentityState;
+
+impl BuildHasher for IdentityState {
+    type Hasher = IdentityHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> IdentityHasher {
+        IdentityHasher::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hash_map::HashMap;
+    use hashers::{FixedState, IdentityState};
+    use std::hash::BuildHasher;
+
+    #[test]
+    fn test_fixed_state() {
+        let state = FixedState::default();
+        assert_eq!(state.hash_one(42u64), FixedState::default().hash_one(42u64));
+        assert_eq!(state.hash_one("avl"), state.hash_one(String::from("avl")));
+        assert!(state.hash_one(1u64) != state.hash_one(2u64));
+        assert!(state.hash_one(1u64) != FixedState::with_seed(1).hash_one(1u64));
+
+        let build = |seed| {
+            let mut m = HashMap::with_hasher(FixedState::with_seed(seed));
+            for i in 0..1000 {
+                m.insert(format!("key{}", i * 7919 % 1000), i);
+            }
+            for i in (0..1000).filter(|i| i % 3 == 0) {
+                m.remove(&format!("key{}", i));
+            }
+            m
+        };
+        let (a, b) = (build(7), build(7));
+        assert!(a.iter().eq(b.iter()));
+        assert_eq!(
+            a.buckets().map(|b| b.index()).collect::<Vec<_>>(),
+            b.buckets().map(|b| b.index()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_identity_state() {
+        let state = IdentityState;
+        assert_eq!(state.hash_one(5u64), state.hash_one(5u64));
+        assert_eq!(state.hash_one(5u32), state.hash_one(5u64));
+        assert!(state.hash_one(5u64) != state.hash_one(6u64));
+
+        // keys sharing their low bits still spread over the buckets
+        let mut m = HashMap::with_identity_hasher();
+        for i in 0..4096u64 {
+            m.insert(i << 16, i);
+        }
+        assert_eq!(m.len(), 4096);
+        assert!(m.get_max_node_of_single_index() < 8);
+        for i in 0..4096u64 {
+            assert_eq!(m.get(&(i << 16)), Some(&i));
+        }
+        assert!(m.get(&1).is_none());
+
+        let mut m = HashMap::with_identity_hasher();
+        for i in -500i64..500 {
+            m.insert(i, ());
+        }
+        assert!((-500i64..500).all(|i| m.contains_key(&i)));
+        m.insert(-1, ());
+        assert_eq!(m.len(), 1000);
+    }
+}
@@ -2,11 +2,17 @@
 #![feature(libc)]
 
 extern crate libc;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 #[macro_use]
 mod macros;
 pub mod ord_map;
 mod hash_table;
 pub mod hash_map;
+pub mod comparator;
+pub mod hashers;
+pub mod pod;
+pub mod small_hash_map;
 mod avl_node;
 mod list;
 mod fastbin;
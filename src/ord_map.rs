@@ -10,6 +10,7 @@ use std::borrow::Borrow;
 use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::ops::RangeBounds;
 
+// the key and value are stored inline after the node, in one fastbin allocation
 struct AVLEntry<K, V> {
     node: AVLNode,
     key: K,
@@ -134,6 +135,56 @@ where
         }
     }
 
+    /// Returns the (&Key, &Value) pair after current pos, without moving the cursor.
+    ///
+    /// # Examples
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let mut map = OrdMap::new();
+    /// map.insert(1, 1);
+    /// map.insert(2, 2);
+    /// let mut cursors = map.find_cursors(&1);
+    /// assert_eq!(cursors.peek_next(), Some((&2, &2)));
+    /// assert_eq!(*cursors.get().unwrap().0, 1);
+    /// cursors.next();
+    /// assert_eq!(cursors.peek_next(), None);
+    /// ```
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.peek(CursorsOperation::NEXT)
+    }
+
+    /// Returns the (&Key, &Value) pair before current pos, without moving the cursor.
+    ///
+    /// # Examples
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let mut map = OrdMap::new();
+    /// map.insert(1, 1);
+    /// map.insert(2, 2);
+    /// let cursors = map.find_cursors(&2);
+    /// assert_eq!(cursors.peek_prev(), Some((&1, &1)));
+    /// ```
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.peek(CursorsOperation::PREV)
+    }
+
+    fn peek(&self, op: CursorsOperation) -> Option<(&K, &V)> {
+        if self.pos.is_null() {
+            return None;
+        }
+        let node = match op {
+            CursorsOperation::NEXT => self.pos.next(),
+            CursorsOperation::PREV => self.pos.prev(),
+        };
+        if node.not_null() {
+            Some((node.key_ref::<K, V>(), node.value_ref::<K, V>()))
+        } else {
+            None
+        }
+    }
+
     /// Returns the (&Key, &mut Value) pair of current pos.
     ///
     /// # Examples
@@ -218,6 +269,9 @@ where
 /// to C/C++, two classic macros `offset_of` and `container_of` are used to dereference member
 /// variables into main struct. `Fastbin` is implemented to reduce the cost of memory allocation.
 ///
+/// Every element is a single allocation holding the AVL node, the key and the value side by
+/// side, so comparing a key while descending touches the node's own cache lines, with no
+/// extra pointer to follow.
 ///
 /// # Examples
 ///
@@ -601,6 +655,43 @@ impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
     }
 }
 
+/// An iterator over a sub-range of entries in a `OrdMap`, in descending order.
+///
+/// This `struct` is created by the [`range_rev`] method on [`OrdMap`]. See its
+/// documentation for more.
+///
+/// [`range_rev`]: struct.OrdMap.html#method.range_rev
+/// [`OrdMap`]: struct.OrdMap.html
+pub struct RangeRev<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    inner: Range<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for RangeRev<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> Clone for RangeRev<'a, K, V> {
+    fn clone(&self) -> RangeRev<'a, K, V> {
+        RangeRev {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeRev<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
 /// A mutable iterator over a sub-range of entries in a `OrdMap`.
 ///
 /// This `struct` is created by the [`range_mut`] method on [`OrdMap`]. See its
@@ -669,10 +760,81 @@ impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
     }
 }
 
+/// An iterator which removes and yields the entries of a `OrdMap` matching a predicate, in
+/// ascending key order.
+///
+/// This `struct` is created by the [`drain_filter`] method on [`OrdMap`]. See its
+/// documentation for more.
+///
+/// [`drain_filter`]: struct.OrdMap.html#method.drain_filter
+/// [`OrdMap`]: struct.OrdMap.html
+pub struct DrainFilter<'a, K, V, F>
+where
+    K: 'a,
+    V: 'a,
+{
+    ord_map: &'a mut OrdMap<K, V>,
+    next: AVLNodePtr,
+    pred: F,
+}
+
+impl<'a, K, V, F> Iterator for DrainFilter<'a, K, V, F>
+where
+    K: Ord,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while !self.next.is_null() {
+            let node = self.next;
+            // the successor must be captured before `node` is erased and rebalanced away
+            self.next = node.next();
+            if (self.pred)(node.key_ref::<K, V>(), node.value_mut::<K, V>()) {
+                return unsafe { self.ord_map.remove_node(node) };
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.ord_map.len()))
+    }
+}
+
 impl<K, V> OrdMap<K, V>
 where
     K: Ord,
 {
+    /// Creates an iterator which uses a closure to determine if an element should be removed.
+    ///
+    /// If the closure returns true, the element is removed from the map and yielded. If the
+    /// closure returns false, the element remains in the map. Elements are visited in
+    /// ascending key order. Elements not yet visited when the iterator is dropped stay in the
+    /// map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let mut map: OrdMap<i32, i32> = (0..8).map(|x| (x, x)).collect();
+    /// let evens: Vec<_> = map.drain_filter(|k, _v| k % 2 == 0).collect();
+    /// assert_eq!(evens, vec![(0, 0), (2, 2), (4, 4), (6, 6)]);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![1, 3, 5, 7]);
+    /// ```
+    pub fn drain_filter<F>(&mut self, pred: F) -> DrainFilter<K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        DrainFilter {
+            next: self.first_node(),
+            ord_map: self,
+            pred,
+        }
+    }
+
     /// Splits the collection into two at the given key. Returns everything after the given key,
     /// including the key.
     ///
@@ -818,6 +980,39 @@ where
         }
     }
 
+    /// Constructs a double-ended iterator over a sub-range of elements in the map, from the
+    /// greatest key down to the least. The range takes the same forms as in [`range`], and
+    /// `range_rev(r)` yields exactly what `range(r).rev()` does.
+    ///
+    /// [`range`]: #method.range
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// // the three latest events before time 100
+    /// let events: OrdMap<u32, &str> =
+    ///     [(10, "a"), (40, "b"), (70, "c"), (90, "d"), (120, "e")].iter().cloned().collect();
+    /// let latest: Vec<_> = events.range_rev(..100).take(3).map(|(_, &e)| e).collect();
+    /// assert_eq!(latest, ["d", "c", "b"]);
+    /// ```
+    pub fn range_rev<T: ?Sized, R>(&self, range: R) -> RangeRev<K, V>
+    where
+        T: Ord,
+        K: Borrow<T>,
+        R: RangeBounds<T>,
+    {
+        RangeRev {
+            inner: self.range(range),
+        }
+    }
+
     /// Constructs a mutable double-ended iterator over a sub-range of elements in the map.
     /// The simplest way is to use the range syntax `min..max`, thus `range(min..max)` will
     /// yield elements from min (inclusive) to max (exclusive).
@@ -862,6 +1057,173 @@ where
         }
     }
 
+    /// Removes every element whose key falls in the given range, returning how many
+    /// were removed. The range takes the same forms as in [`range`].
+    ///
+    /// [`range`]: #method.range
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let mut map: OrdMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// assert_eq!(map.remove_range(3..7), 4);
+    /// assert_eq!(map.remove_range(3..7), 0);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [0, 1, 2, 7, 8, 9]);
+    /// ```
+    pub fn remove_range<T: ?Sized, R>(&mut self, range: R) -> usize
+    where
+        T: Ord,
+        K: Borrow<T>,
+        R: RangeBounds<T>,
+    {
+        let (mut node, back, _) = self.inner_range(range);
+        let mut cnt = 0;
+        while node != back {
+            let next = node.next();
+            unsafe {
+                self.remove_node(node);
+            }
+            node = next;
+            cnt += 1;
+        }
+        cnt
+    }
+
+    /// Returns `true` if any key falls in the given range. The range takes the same forms as
+    /// in [`range`]. O(log n) time complexity.
+    ///
+    /// [`range`]: #method.range
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let map: OrdMap<_, _> = [1, 5, 9].iter().map(|&i| (i, i)).collect();
+    /// assert!(map.contains_range(4..6));
+    /// assert!(!map.contains_range(6..9));
+    /// assert!(map.contains_range(6..=9));
+    /// ```
+    pub fn contains_range<T: ?Sized, R>(&self, range: R) -> bool
+    where
+        T: Ord,
+        K: Borrow<T>,
+        R: RangeBounds<T>,
+    {
+        let (front, back, _) = self.inner_range(range);
+        front != back
+    }
+
+    /// Returns how many keys fall in the given range. The range takes the same forms as in
+    /// [`range`].
+    ///
+    /// Nodes don't keep the sizes of their subtrees, so this walks the range: O(log n + k)
+    /// time complexity, where k is the returned count.
+    ///
+    /// [`range`]: #method.range
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let map: OrdMap<_, _> = (0..10).map(|i| (i * 10, i)).collect();
+    /// assert_eq!(map.count_range(15..=50), 4);
+    /// assert_eq!(map.count_range(..), 10);
+    /// assert_eq!(map.count_range(91..), 0);
+    /// ```
+    pub fn count_range<T: ?Sized, R>(&self, range: R) -> usize
+    where
+        T: Ord,
+        K: Borrow<T>,
+        R: RangeBounds<T>,
+    {
+        let (mut node, back, _) = self.inner_range(range);
+        let mut cnt = 0;
+        while node != back {
+            node = node.next();
+            cnt += 1;
+        }
+        cnt
+    }
+
+    /// Returns the element whose key is nearest to `target` by `key_to_metric`, in O(log n).
+    /// When two keys are equally near, the smaller one is returned.
+    ///
+    /// The search descends the AVL, so the metric must be non-decreasing along the order of
+    /// keys: `a < b` implies `key_to_metric(a) <= key_to_metric(b)`. For any other metric the
+    /// result is unspecified; scan `iter()` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    ///
+    /// let stops: OrdMap<u32, &str> =
+    ///     [(0, "depot"), (120, "mill"), (300, "bridge")].iter().cloned().collect();
+    /// let at = |metres: &u32| *metres as i64;
+    /// assert_eq!(stops.nearest_by(100, at), Some((&120, &"mill")));
+    /// assert_eq!(stops.nearest_by(50, at), Some((&0, &"depot")));
+    /// assert_eq!(stops.nearest_by(1000, at), Some((&300, &"bridge")));
+    /// ```
+    pub fn nearest_by<F>(&self, target: i64, key_to_metric: F) -> Option<(&K, &V)>
+    where
+        F: Fn(&K) -> i64,
+    {
+        // the smallest key whose metric is at least `target`, and the largest one below it
+        let lower_bound = |target: i64| {
+            let mut node = self.root.node;
+            let (mut floor, mut ceil) = (ptr::null_mut(), ptr::null_mut());
+            while node.not_null() {
+                if key_to_metric(node.key_ref::<K, V>()) < target {
+                    floor = node;
+                    node = node.right();
+                } else {
+                    ceil = node;
+                    node = node.left();
+                }
+            }
+            (floor, ceil)
+        };
+        let (floor, ceil) = lower_bound(target);
+        // a distance between two i64 never exceeds u64::MAX, so wrapping in u64 is exact
+        let distance = |from: i64, to: i64| (to as u64).wrapping_sub(from as u64);
+        let use_floor = match (floor.is_null(), ceil.is_null()) {
+            (true, true) => return None,
+            (false, true) => true,
+            (true, false) => false,
+            (false, false) => {
+                let below = distance(key_to_metric(floor.key_ref::<K, V>()), target);
+                let above = distance(target, key_to_metric(ceil.key_ref::<K, V>()));
+                below <= above
+            }
+        };
+        let node = if use_floor {
+            // floor is the largest key of its metric, step back to the smallest one
+            lower_bound(key_to_metric(floor.key_ref::<K, V>())).1
+        } else {
+            ceil
+        };
+        Some((node.key_ref::<K, V>(), node.value_ref::<K, V>()))
+    }
+
     /// Moves all elements from `other` into `Self`, leaving `other` empty.
     /// O(n) time complexity
     ///
@@ -1091,7 +1453,8 @@ where
         self.count == 0
     }
 
-    /// Returns the number of elements in the map.
+    /// Returns the number of elements in the map, in O(1): the count is kept up to date by
+    /// every insertion and removal, never recomputed by walking the AVL.
     ///
     /// # Examples
     ///
@@ -1139,6 +1502,41 @@ where
         }
     }
 
+    /// Creates an empty `OrdMap` whose node pool can hold at least `capacity` nodes, so the
+    /// first `capacity` inserts will not call the global allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    /// let mut map: OrdMap<usize, usize> = OrdMap::with_node_pool_capacity(1000);
+    /// for i in 0..1000 {
+    ///     map.insert(i, i);
+    /// }
+    /// assert_eq!(map.len(), 1000);
+    /// ```
+    #[inline]
+    pub fn with_node_pool_capacity(capacity: usize) -> Self {
+        let mut map = OrdMap::new();
+        map.reserve(capacity);
+        map
+    }
+
+    /// Reserves node storage for at least `additional` more elements, so that many inserts
+    /// will not call the global allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::ord_map::OrdMap;
+    /// let mut map: OrdMap<&str, isize> = OrdMap::new();
+    /// map.reserve(10);
+    /// ```
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.entry_fastbin.reserve(additional);
+    }
+
     #[inline]
     fn entry_alloc(&mut self, key: K, value: V) -> *mut AVLEntry<K, V> {
         let entry = self.entry_fastbin.alloc() as *mut AVLEntry<K, V>;
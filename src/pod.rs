@@ -0,0 +1,21 @@
+//! Plain old data, which can be dumped and loaded as raw bytes.
+
+/// A marker for types which are plain old data: `HashMap`s of them can be written with
+/// `dump_pod` and read back with `load_pod`, copying their bytes as they are in memory.
+///
+/// # Safety
+///
+/// A type may only implement `Pod` if it has no padding bytes, holds no pointers or references,
+/// and every bit pattern of its size is a valid value of it. Integers, floats and arrays of them
+/// qualify; `bool`, `char` and most structs don't.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t: ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
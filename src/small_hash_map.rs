@@ -0,0 +1,396 @@
+use hash_map::{self, HashMap, RandomState};
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem::MaybeUninit;
+use std::{mem, ptr, slice};
+
+/// Up to `N` entries stored in place, searched by linear scan.
+struct InlineMap<K, V, const N: usize> {
+    len: usize,
+    data: [MaybeUninit<(K, V)>; N],
+}
+
+impl<K, V, const N: usize> InlineMap<K, V, N> {
+    #[inline]
+    fn new() -> Self {
+        InlineMap {
+            len: 0,
+            // an array of `MaybeUninit` needs no initialization
+            data: unsafe { MaybeUninit::uninit().assume_init() },
+        }
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[(K, V)] {
+        unsafe { slice::from_raw_parts(self.data.as_ptr() as *const (K, V), self.len) }
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [(K, V)] {
+        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut (K, V), self.len) }
+    }
+
+    fn position<Q: ?Sized>(&self, q: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq,
+    {
+        self.as_slice().iter().position(|kv| kv.0.borrow() == q)
+    }
+
+    /// Moves the entry at `idx` out, filling the hole with the last entry.
+    fn swap_remove(&mut self, idx: usize) -> (K, V) {
+        debug_assert!(idx < self.len);
+        self.len -= 1;
+        unsafe {
+            let base = self.data.as_mut_ptr() as *mut (K, V);
+            let res = ptr::read(base.add(idx));
+            if idx != self.len {
+                ptr::copy_nonoverlapping(base.add(self.len), base.add(idx), 1);
+            }
+            res
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, key: K, value: V) {
+        debug_assert!(self.len < N);
+        self.data[self.len] = MaybeUninit::new((key, value));
+        self.len += 1;
+    }
+}
+
+impl<K, V, const N: usize> Drop for InlineMap<K, V, N> {
+    fn drop(&mut self) {
+        let len = mem::replace(&mut self.len, 0);
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.data.as_mut_ptr() as *mut (K, V),
+                len,
+            ));
+        }
+    }
+}
+
+enum Repr<K, V, const N: usize> {
+    Inline(InlineMap<K, V, N>),
+    Heap(HashMap<K, V>),
+}
+
+/// A map which keeps up to `N` entries inline and spills into a [`HashMap`] beyond that.
+///
+/// For tiny maps the hash index and the AVL under every bucket cost more than they save, so
+/// while the map holds at most `N` entries they live in an array inside the struct and lookups
+/// are a linear scan: no heap allocation and no pointer chasing. Once an insert pushes the
+/// map past `N` entries, all of them are moved into a `HashMap`, which is kept from then on.
+///
+/// [`HashMap`]: ../hash_map/struct.HashMap.html
+///
+/// # Examples
+///
+/// ```
+/// use hash_ord::small_hash_map::SmallHashMap;
+///
+/// let mut map: SmallHashMap<&str, i32, 2> = SmallHashMap::new();
+/// map.insert("a", 1);
+/// map.insert("b", 2);
+/// assert!(map.is_inline());
+///
+/// map.insert("c", 3);
+/// assert!(!map.is_inline());
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(("b", 2)));
+/// assert_eq!(map.len(), 2);
+/// ```
+pub struct SmallHashMap<K, V, const N: usize> {
+    repr: Repr<K, V, N>,
+}
+
+impl<K, V, const N: usize> SmallHashMap<K, V, N>
+where
+    K: Ord + Hash,
+{
+    /// Creates an empty `SmallHashMap`. It does not allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    /// let map: SmallHashMap<i32, i32, 4> = SmallHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        SmallHashMap {
+            repr: Repr::Inline(InlineMap::new()),
+        }
+    }
+
+    /// Returns true if the entries are still stored inline, i.e. the map never grew past `N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, i32, 1> = SmallHashMap::new();
+    /// map.insert(1, 1);
+    /// assert!(map.is_inline());
+    /// map.insert(2, 2);
+    /// assert!(!map.is_inline());
+    /// ```
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        match self.repr {
+            Repr::Inline(_) => true,
+            Repr::Heap(_) => false,
+        }
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut a: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// assert_eq!(a.len(), 0);
+    /// a.insert(1, "a");
+    /// assert_eq!(a.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.repr {
+            Repr::Inline(ref m) => m.len,
+            Repr::Heap(ref m) => m.len(),
+        }
+    }
+
+    /// Returns true if the map contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts a key-value pair into the map, moving every entry into a `HashMap` if the map
+    /// grows past `N` entries.
+    ///
+    /// If the map did not have this key present, `None` is returned.
+    ///
+    /// If the map did have this key present, the key and value are updated, and the old
+    /// key and value are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some((37, "a")));
+    /// assert_eq!(map.get(&37), Some(&"b"));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        let heap = match self.repr {
+            Repr::Heap(ref mut m) => return m.insert(key, value),
+            Repr::Inline(ref mut m) => {
+                if let Some(idx) = m.position(&key) {
+                    let kv = &mut m.as_mut_slice()[idx];
+                    return Some((mem::replace(&mut kv.0, key), mem::replace(&mut kv.1, value)));
+                }
+                if m.len < N {
+                    m.push(key, value);
+                    return None;
+                }
+                let mut heap = HashMap::with_capacity(N + 1);
+                while m.len > 0 {
+                    let (k, v) = m.swap_remove(m.len - 1);
+                    heap.insert(k, v);
+                }
+                heap.insert(key, value);
+                heap
+            }
+        };
+        self.repr = Repr::Heap(heap);
+        None
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// `Hash` and `Ord` on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    pub fn get<Q: ?Sized>(&self, q: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+    {
+        match self.repr {
+            Repr::Inline(ref m) => m.position(q).map(|idx| &m.as_slice()[idx].1),
+            Repr::Heap(ref m) => m.get(q),
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// map.insert(1, "a");
+    /// if let Some(x) = map.get_mut(&1) {
+    ///     *x = "b";
+    /// }
+    /// assert_eq!(map.get(&1), Some(&"b"));
+    /// ```
+    pub fn get_mut<Q: ?Sized>(&mut self, q: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+    {
+        match self.repr {
+            Repr::Inline(ref mut m) => match m.position(q) {
+                Some(idx) => Some(&mut m.as_mut_slice()[idx].1),
+                None => None,
+            },
+            Repr::Heap(ref mut m) => m.get_mut(q),
+        }
+    }
+
+    /// Returns true if the map contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.contains_key(&1), true);
+    /// assert_eq!(map.contains_key(&2), false);
+    /// ```
+    #[inline]
+    pub fn contains_key<Q: ?Sized>(&self, q: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+    {
+        self.get(q).is_some()
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map. A spilled map stays a `HashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, &str, 4> = SmallHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some((1, "a")));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    pub fn remove<Q: ?Sized>(&mut self, q: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord,
+    {
+        match self.repr {
+            Repr::Inline(ref mut m) => m.position(q).map(|idx| m.swap_remove(idx)),
+            Repr::Heap(ref mut m) => m.remove(q),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hash_ord::small_hash_map::SmallHashMap;
+    ///
+    /// let mut map: SmallHashMap<i32, i32, 4> = SmallHashMap::new();
+    /// map.insert(1, 10);
+    /// map.insert(2, 20);
+    /// assert_eq!(map.iter().map(|(_, v)| *v).sum::<i32>(), 30);
+    /// ```
+    pub fn iter(&self) -> Iter<K, V> {
+        match self.repr {
+            Repr::Inline(ref m) => Iter {
+                inner: IterRepr::Inline(m.as_slice().iter()),
+            },
+            Repr::Heap(ref m) => Iter {
+                inner: IterRepr::Heap(m.iter()),
+            },
+        }
+    }
+}
+
+impl<K, V, const N: usize> Default for SmallHashMap<K, V, N>
+where
+    K: Ord + Hash,
+{
+    #[inline]
+    fn default() -> Self {
+        SmallHashMap::new()
+    }
+}
+
+/// An iterator over the entries of a `SmallHashMap`.
+///
+/// This `struct` is created by the [`iter`] method on [`SmallHashMap`]. See its
+/// documentation for more.
+///
+/// [`iter`]: struct.SmallHashMap.html#method.iter
+/// [`SmallHashMap`]: struct.SmallHashMap.html
+pub struct Iter<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    inner: IterRepr<'a, K, V>,
+}
+
+enum IterRepr<'a, K, V>
+where
+    K: 'a,
+    V: 'a,
+{
+    Inline(slice::Iter<'a, (K, V)>),
+    Heap(hash_map::Iter<'a, K, V, RandomState>),
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        match self.inner {
+            IterRepr::Inline(ref mut it) => it.next().map(|kv| (&kv.0, &kv.1)),
+            IterRepr::Heap(ref mut it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.inner {
+            IterRepr::Inline(ref it) => it.size_hint(),
+            IterRepr::Heap(ref it) => it.size_hint(),
+        }
+    }
+}
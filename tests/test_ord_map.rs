@@ -107,6 +107,145 @@ fn test_avl_range() {
     }
 }
 
+#[test]
+fn test_avl_range_rev() {
+    let mut t = OrdMap::new();
+    for _ in 0..500 {
+        let x = (rand::random::<usize>() % 1000) as i32;
+        t.insert(x, x * 2);
+    }
+    for _ in 0..100 {
+        let a = (rand::random::<usize>() % 1100) as i32 - 50;
+        let b = a + (rand::random::<usize>() % 300) as i32;
+        let mut fwd: Vec<_> = t.range(a..b).collect();
+        fwd.reverse();
+        assert!(t.range_rev(a..b).eq(fwd.into_iter()));
+        let bounds = (Excluded(a), Included(b));
+        assert!(t.range_rev(bounds).eq(t.range(bounds).rev()));
+    }
+    assert!(t.range_rev(..).eq(t.iter().rev()));
+    assert!(t.range_rev(..).rev().eq(t.iter()));
+    assert_eq!(t.range_rev(2000..).next(), None);
+
+    let mut r = t.range_rev(..);
+    let last = t.iter().next_back();
+    let first = t.iter().next();
+    assert_eq!(r.next(), last);
+    assert_eq!(r.next_back(), first);
+}
+
+#[test]
+fn test_avl_remove_range() {
+    let cnt = RefCell::new(0);
+    let test_num = 1000;
+    let mut t = OrdMap::new();
+    for i in 0..test_num {
+        t.insert(i, Node { b: &cnt });
+    }
+    assert_eq!(t.remove_range(250..750), 500);
+    assert_eq!(*cnt.borrow(), 500);
+    assert_eq!(t.len(), 500);
+    assert!(t.check_balanced());
+    assert!(t.contains_key(&249));
+    assert!(!t.contains_key(&250));
+    assert!(!t.contains_key(&749));
+    assert!(t.contains_key(&750));
+    assert!(t.keys().cloned().eq((0..250).chain(750..test_num)));
+
+    assert_eq!(t.remove_range(300..300), 0);
+    assert_eq!(t.remove_range((Excluded(0), Included(249))), 249);
+    assert_eq!(t.remove_range((Excluded(997), Unbounded)), 2);
+    assert_eq!(t.remove_range(..), 249);
+    assert_eq!(*cnt.borrow(), test_num);
+    assert!(t.is_empty());
+}
+
+#[test]
+fn test_avl_count_range() {
+    let mut t = OrdMap::new();
+    for _ in 0..1000 {
+        let x = (rand::random::<usize>() % 2000) as i32;
+        t.insert(x, x);
+    }
+    assert_eq!(t.count_range(..), t.len());
+    for _ in 0..200 {
+        let a = (rand::random::<usize>() % 2100) as i32 - 50;
+        let b = a + (rand::random::<usize>() % 500) as i32;
+        let bounds = [
+            (Included(a), Excluded(b)),
+            (Excluded(a), Included(b)),
+            (Included(a), Unbounded),
+            (Unbounded, Excluded(b)),
+        ];
+        for &range in bounds.iter() {
+            let expect = t.range(range).count();
+            assert_eq!(t.count_range(range), expect);
+            assert_eq!(t.contains_range(range), expect > 0);
+        }
+    }
+    assert!(!OrdMap::<i32, i32>::new().contains_range(..));
+}
+
+#[test]
+fn test_avl_len() {
+    let mut t = OrdMap::new();
+    let mut expect = std::collections::BTreeSet::new();
+    for _ in 0..5000 {
+        let x = (rand::random::<usize>() % 1000) as i32;
+        if rand::random::<bool>() {
+            assert_eq!(t.insert(x, x).is_none(), expect.insert(x));
+        } else {
+            assert_eq!(t.remove(&x).is_some(), expect.remove(&x));
+        }
+        assert_eq!(t.len(), expect.len());
+        assert_eq!(t.is_empty(), expect.is_empty());
+    }
+    assert_eq!(t.iter().count(), t.len());
+
+    let removed = t.remove_range(200..400);
+    assert_eq!(t.len(), expect.len() - removed);
+    let mut right = t.split_off(&700);
+    assert_eq!(t.len() + right.len(), expect.len() - removed);
+    assert_eq!(right.iter().count(), right.len());
+    t.append(&mut right);
+    assert!(right.is_empty());
+    assert_eq!(t.len(), expect.len() - removed);
+    t.clear();
+    assert_eq!(t.len(), 0);
+    assert!(t.is_empty());
+}
+
+#[test]
+fn test_avl_nearest_by() {
+    let t: OrdMap<i32, i32> = (0..100).map(|i| (i * 10, i)).collect();
+    let metric = |k: &i32| *k as i64;
+    assert_eq!(t.nearest_by(50, metric), Some((&50, &5)));
+    assert_eq!(t.nearest_by(53, metric), Some((&50, &5)));
+    assert_eq!(t.nearest_by(57, metric), Some((&60, &6)));
+    assert_eq!(t.nearest_by(55, metric), Some((&50, &5)));
+    assert_eq!(t.nearest_by(-100, metric), Some((&0, &0)));
+    assert_eq!(t.nearest_by(5000, metric), Some((&990, &99)));
+    assert_eq!(t.nearest_by(i64::MIN, metric), Some((&0, &0)));
+    assert_eq!(t.nearest_by(i64::MAX, metric), Some((&990, &99)));
+    for target in -20..1010 {
+        let expect = t
+            .iter()
+            .min_by_key(|&(k, _)| ((*k as i64 - target).abs(), *k))
+            .unwrap();
+        assert_eq!(t.nearest_by(target, metric), Some(expect));
+    }
+
+    // a metric which is monotonic but not injective
+    let bucket = |k: &i32| (*k / 100) as i64;
+    assert_eq!(t.nearest_by(4, bucket), Some((&400, &40)));
+    assert_eq!(t.nearest_by(-1, bucket), Some((&0, &0)));
+    assert_eq!(t.nearest_by(12, bucket), Some((&900, &90)));
+    // equally near on both sides, the smaller key of the lower metric wins
+    let halves = |k: &i32| (*k / 100 * 2) as i64;
+    assert_eq!(t.nearest_by(9, halves), Some((&400, &40)));
+    assert!(OrdMap::<i32, i32>::new().nearest_by(0, metric).is_none());
+}
+
 #[test]
 fn test_avl_into_sorted_list() {
     let cnt = RefCell::new(0);
@@ -281,6 +420,27 @@ fn test_avl_cursors() {
     }
 }
 
+#[test]
+fn test_avl_cursors_peek() {
+    let mut map: OrdMap<_, _> = (0..100).map(|i| (i * 2, i)).collect();
+    let mut cursors = map.find_cursors(&0);
+    assert_eq!(cursors.peek_prev(), None);
+    let mut seen = 0;
+    while let Some((&k, _)) = cursors.get() {
+        let peeked = cursors.peek_next().map(|(&k, &v)| (k, v));
+        cursors.next();
+        assert_eq!(cursors.get().map(|(&k, &v)| (k, v)), peeked);
+        if let Some((&k2, _)) = cursors.get() {
+            assert_eq!(cursors.peek_prev().map(|(&k, _)| k), Some(k));
+            assert_eq!(k2, k + 2);
+        }
+        seen += 1;
+    }
+    assert_eq!(seen, 100);
+    assert_eq!(cursors.peek_next(), None);
+    assert_eq!(cursors.peek_prev(), None);
+}
+
 #[test]
 fn test_avl_clear() {
     let cnt = RefCell::new(0);
@@ -374,6 +534,26 @@ fn test_avl_values_index() {
     }
 }
 
+#[test]
+fn test_avl_values_mut() {
+    let v = default_make_avl_element(100);
+    let mut t = OrdMap::new();
+    for x in &v {
+        t.insert(*x, *x);
+    }
+    assert_eq!(t.keys().sum::<i32>(), (0..100).sum());
+    let mut last = -1;
+    for val in t.values_mut() {
+        // visited in ascending key order, where key == value before doubling
+        assert!(*val > last);
+        last = *val;
+        *val *= 2;
+    }
+    assert_eq!(t.values().cloned().collect::<Vec<_>>(), (0..100).map(|x| x * 2).collect::<Vec<_>>());
+    assert!(t.iter().all(|(k, v)| *v == k * 2));
+    assert_eq!(t.values_mut().count(), 100);
+}
+
 #[test]
 fn test_avl_find() {
     let t = default_build_avl(1000);
@@ -402,3 +582,51 @@ fn test_avl_erase() {
     assert!(t.check_ord_valid());
     assert!(t.check_balanced());
 }
+
+#[test]
+fn test_avl_node_pool_capacity() {
+    let n = 10000;
+    let v = default_make_avl_element(n);
+    let mut t = DefaultType::with_node_pool_capacity(n);
+    for d in &v {
+        t.insert(*d, Some(-*d));
+    }
+    assert_eq!(t.len(), n);
+    for d in &v {
+        assert_eq!(t.get(d), Some(&Some(-*d)));
+    }
+    t.reserve(n);
+    for d in &v {
+        t.insert(*d + n as i32, None);
+    }
+    assert_eq!(t.len(), n * 2);
+    for d in &v {
+        assert_eq!(t.get(&(*d + n as i32)), Some(&None));
+    }
+}
+
+#[test]
+fn test_avl_drain_filter() {
+    let cnt = RefCell::new(0);
+    let mut t = OrdMap::new();
+    for i in 0..1000 {
+        t.insert(i, Node { b: &cnt });
+    }
+    let drained: Vec<i32> = t.drain_filter(|k, _| k % 2 == 1).map(|(k, _)| k).collect();
+    assert_eq!(drained, (0..1000).filter(|k| k % 2 == 1).collect::<Vec<_>>());
+    assert_eq!(*cnt.borrow(), 500);
+    assert_eq!(t.len(), 500);
+    assert!(t.check_balanced());
+    assert!(t.check_ord_valid());
+    assert!(t.keys().all(|k| k % 2 == 0));
+
+    {
+        let mut iter = t.drain_filter(|_, _| true);
+        assert_eq!(iter.next().map(|(k, _)| k), Some(0));
+    }
+    assert_eq!(t.len(), 499);
+    assert!(t.check_balanced());
+    assert_eq!(t.drain_filter(|_, _| true).count(), 499);
+    assert!(t.is_empty());
+    assert_eq!(*cnt.borrow(), 1000);
+}
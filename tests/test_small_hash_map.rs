@@ -0,0 +1,77 @@
+extern crate hash_ord;
+
+use hash_ord::small_hash_map::SmallHashMap;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the heap allocations made by the current thread.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOC_CNT: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOC_CNT.try_with(|c| c.set(c.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn alloc_cnt() -> usize {
+    ALLOC_CNT.with(|c| c.get())
+}
+
+#[test]
+fn test_small_hash_map_inline_no_alloc() {
+    let before = alloc_cnt();
+    let mut map: SmallHashMap<u64, u64, 8> = SmallHashMap::new();
+    for i in 0..8 {
+        assert_eq!(map.insert(i, i * 10), None);
+    }
+    assert_eq!(map.insert(3, 33), Some((3, 30)));
+    for i in 0..8 {
+        assert!(map.contains_key(&i));
+    }
+    *map.get_mut(&5).unwrap() += 1;
+    assert_eq!(map.get(&5), Some(&51));
+    assert_eq!(map.remove(&0), Some((0, 0)));
+    assert_eq!(map.remove(&0), None);
+    assert_eq!(map.iter().count(), 7);
+    assert!(map.is_inline());
+    drop(map);
+    assert_eq!(alloc_cnt(), before);
+}
+
+#[test]
+fn test_small_hash_map_spill() {
+    let mut map: SmallHashMap<String, usize, 4> = SmallHashMap::new();
+    for i in 0..4 {
+        map.insert(i.to_string(), i);
+    }
+    assert!(map.is_inline());
+    map.insert(4.to_string(), 4);
+    assert!(!map.is_inline());
+    for i in 5..100 {
+        map.insert(i.to_string(), i);
+    }
+    assert_eq!(map.len(), 100);
+    for i in 0..100 {
+        assert_eq!(map.get(i.to_string().as_str()), Some(&i));
+    }
+    let mut all: Vec<usize> = map.iter().map(|(_, v)| *v).collect();
+    all.sort();
+    assert_eq!(all, (0..100).collect::<Vec<_>>());
+    for i in 0..100 {
+        assert_eq!(map.remove(i.to_string().as_str()), Some((i.to_string(), i)));
+    }
+    assert!(map.is_empty());
+    assert!(!map.is_inline());
+}